@@ -0,0 +1,688 @@
+use crate::decode::{Instruction, Op};
+use crate::platform::Platform;
+use crate::symbols::SymbolMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Calls that fit on the stack before `2nnn` overflows it
+pub const STACK_DEPTH: usize = 15;
+
+/// How control gets from one instruction to another
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Edge {
+    /// Falls through to the next instruction
+    Next,
+    /// Skips the next instruction, from `3xkk`, `4xkk`, `5xy0`, `9xy0`, `Ex9E` or `ExA1`
+    Skip,
+    /// `1nnn`
+    Jump,
+    /// `2nnn`, to the subroutine
+    Call,
+    /// `Bnnn`, to one of the places `V0` can take it
+    Table,
+}
+
+/// A value the traversal knows at some point, or not
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Known {
+    Value(u16),
+    Unknown,
+}
+
+impl Known {
+    fn join(self, other: Known) -> Known {
+        if self == other {
+            self
+        } else {
+            Known::Unknown
+        }
+    }
+}
+
+/// What the traversal tracks along each path: `I`, for memory accesses, and
+/// `V0`, for `Bnnn` jumps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct State {
+    i: Known,
+    v0: Known,
+}
+
+impl State {
+    fn join(self, other: State) -> State {
+        State {
+            i: self.i.join(other.i),
+            v0: self.v0.join(other.v0),
+        }
+    }
+}
+
+/// Kinds of problems the analyzer reports
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lint {
+    Unreachable,
+    OutsideRom,
+    OddTarget,
+    UnresolvedJump,
+    InvalidOpcode,
+    PlatformOpcode,
+    FontWrite,
+    StackDepth,
+}
+
+impl Lint {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::Unreachable => "unreachable",
+            Lint::OutsideRom => "outside-rom",
+            Lint::OddTarget => "odd-target",
+            Lint::UnresolvedJump => "unresolved-jump",
+            Lint::InvalidOpcode => "invalid-opcode",
+            Lint::PlatformOpcode => "platform-opcode",
+            Lint::FontWrite => "font-write",
+            Lint::StackDepth => "stack-depth",
+        }
+    }
+}
+
+/// A problem found at an address
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Finding {
+    pub address: u16,
+    pub lint: Lint,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(address: u16, lint: Lint, message: String) -> Self {
+        Finding {
+            address,
+            lint,
+            message,
+        }
+    }
+
+    /// One line for a report, placing the address by label when there is one
+    pub fn describe(&self, symbols: &SymbolMap) -> String {
+        format!(
+            "{}  {}: {}",
+            symbols.describe(self.address),
+            self.lint.name(),
+            self.message
+        )
+    }
+}
+
+/// What recursive descent from the platform's start address found in a ROM
+///
+/// Every path is followed through jumps, calls, skips and the `Bnnn` targets
+/// it can resolve, assuming subroutines return. `I` and `V0` are tracked as
+/// constants along the way, so loads, stores and sprites through a known `I`
+/// count as data. Code that modifies itself or is only reached through an
+/// unresolved `Bnnn` is not found.
+pub struct Traversal {
+    pub rom: Vec<u8>,
+    /// Where the ROM is loaded
+    pub origin: u16,
+    /// Where execution starts, past any patch at the front of the ROM
+    pub start: u16,
+    /// Reached instructions, by address
+    pub code: BTreeMap<u16, u16>,
+    /// Control flow between reached instructions, as (from, to, how)
+    pub edges: BTreeSet<(u16, u16, Edge)>,
+    /// ROM bytes read or written through a known `I`
+    pub data: BTreeSet<u16>,
+    /// Sprites drawn through a known `I`, by address, with the most rows drawn
+    pub sprites: BTreeMap<u16, u8>,
+    pub findings: Vec<Finding>,
+}
+
+impl Traversal {
+    pub fn run(rom: &[u8], platform: Platform) -> Self {
+        let mut traversal = Traversal {
+            rom: rom.to_vec(),
+            origin: platform.load_address(),
+            start: platform.start(),
+            code: BTreeMap::new(),
+            edges: BTreeSet::new(),
+            data: BTreeSet::new(),
+            sprites: BTreeMap::new(),
+            findings: Vec::new(),
+        };
+        let entry = State {
+            i: Known::Value(0),
+            v0: Known::Value(0),
+        };
+        let mut states: HashMap<u16, State> = HashMap::new();
+        let mut queue = vec![(traversal.start, entry)];
+        while let Some((address, state)) = queue.pop() {
+            let state = match states.get(&address) {
+                Some(&old) if old.join(state) == old => continue,
+                Some(&old) => old.join(state),
+                None => state,
+            };
+            states.insert(address, state);
+            for (to, edge, state) in traversal.visit(address, state) {
+                traversal.edges.insert((address, to, edge));
+                queue.push((to, state));
+            }
+        }
+        traversal.findings.sort();
+        traversal.findings.dedup();
+        traversal
+    }
+
+    /// One past the last ROM byte in memory
+    pub fn end(&self) -> u16 {
+        self.origin + self.rom.len() as u16
+    }
+
+    /// The word at an address, if both bytes are in the ROM
+    pub fn word(&self, address: u16) -> Option<u16> {
+        let offset = address.checked_sub(self.origin)? as usize;
+        match self.rom.get(offset..offset + 2) {
+            Some(&[high, low]) => Some((high as u16) << 8 | low as u16),
+            _ => None,
+        }
+    }
+
+    /// Records the instruction at `address`, returning where it can go next
+    fn visit(&mut self, address: u16, state: State) -> Vec<(u16, Edge, State)> {
+        let word = match self.word(address) {
+            Some(word) => word,
+            None => return Vec::new(),
+        };
+        self.code.insert(address, word);
+        let ins = Instruction::decode(word);
+        let next = address + 2;
+        let mut after = state;
+        let mut successors = Vec::new();
+
+        if let Some((_, name)) = platform_opcode(word) {
+            self.report(address, Lint::PlatformOpcode, name.to_string());
+        }
+        match ins.op {
+            Op::Ret => return successors,
+            // 00FD exits SCHIP
+            Op::Sys if word == 0x00FD => return successors,
+            Op::Jp => {
+                self.target(address, ins.nnn, Edge::Jump, state, &mut successors);
+                return successors;
+            }
+            Op::Call => {
+                self.target(address, ins.nnn, Edge::Call, state, &mut successors);
+                // the subroutine can change anything
+                after = State {
+                    i: Known::Unknown,
+                    v0: Known::Unknown,
+                };
+            }
+            Op::JpV0Addr => {
+                self.table(address, ins.nnn, state, &mut successors);
+                return successors;
+            }
+            Op::SeVxByte | Op::SneVxByte | Op::SeVxVy | Op::SneVxVy | Op::SkpVx | Op::SknpVx => {
+                self.target(address, address + 4, Edge::Skip, state, &mut successors);
+            }
+            Op::LdIAddr => after.i = Known::Value(ins.nnn),
+            Op::AddIVx | Op::LdFVx => after.i = Known::Unknown,
+            Op::Drw => {
+                self.access(address, state.i, ins.n as u16, false);
+                if let (Known::Value(i), 1..=15) = (state.i, ins.n) {
+                    let height = self.sprites.entry(i).or_insert(0);
+                    *height = (*height).max(ins.n);
+                }
+            }
+            Op::LdBVx => self.access(address, state.i, 3, true),
+            Op::LdIVx => self.access(address, state.i, ins.x as u16 + 1, true),
+            Op::LdVxI => {
+                self.access(address, state.i, ins.x as u16 + 1, false);
+                after.v0 = Known::Unknown;
+            }
+            Op::Invalid if platform_opcode(word).is_none() => {
+                self.report(
+                    address,
+                    Lint::InvalidOpcode,
+                    format!("{:04X} is not an instruction", word),
+                );
+                return successors;
+            }
+            _ => {}
+        }
+        if ins.x == 0 {
+            after.v0 = match (ins.op, state.v0) {
+                (Op::LdVxByte, _) => Known::Value(ins.kk as u16),
+                (Op::AddVxByte, Known::Value(v0)) => Known::Value((v0 + ins.kk as u16) & 0xFF),
+                (Op::AddVxByte, Known::Unknown) => Known::Unknown,
+                (Op::LdVxVy | Op::OrVxVy | Op::AndVxVy | Op::XorVxVy | Op::AddVxVy, _)
+                | (Op::SubVxVy | Op::ShrVxVy | Op::SubnVxVy | Op::ShlVxVy, _)
+                | (Op::RndVxByte | Op::LdVxDt | Op::LdVxK, _) => Known::Unknown,
+                _ => after.v0,
+            };
+        }
+        self.target(address, next, Edge::Next, after, &mut successors);
+        successors
+    }
+
+    /// Checks a control transfer and adds it if it can be followed
+    fn target(
+        &mut self,
+        from: u16,
+        to: u16,
+        edge: Edge,
+        state: State,
+        successors: &mut Vec<(u16, Edge, State)>,
+    ) {
+        if to & 1 == 1 && edge != Edge::Next && edge != Edge::Skip {
+            self.report(
+                from,
+                Lint::OddTarget,
+                format!("jumps to odd address {:03X}", to),
+            );
+        }
+        if self.word(to).is_none() {
+            let what = match edge {
+                Edge::Next | Edge::Skip => String::from("runs off the end of the ROM"),
+                _ => format!("jumps to {:03X}, outside the ROM", to),
+            };
+            self.report(from, Lint::OutsideRom, what);
+            return;
+        }
+        successors.push((to, edge, state));
+    }
+
+    /// Follows `Bnnn` to `nnn + V0` when `V0` is known, or else through a table of
+    /// jumps at `nnn`, which is how it is usually used
+    fn table(
+        &mut self,
+        from: u16,
+        base: u16,
+        state: State,
+        successors: &mut Vec<(u16, Edge, State)>,
+    ) {
+        if let Known::Value(v0) = state.v0 {
+            self.target(from, base + v0, Edge::Table, state, successors);
+            return;
+        }
+        let mut entry = base;
+        while let Some(word) = self.word(entry) {
+            if word >> 12 != 0x1 {
+                break;
+            }
+            self.target(from, entry, Edge::Table, state, successors);
+            entry += 2;
+        }
+        if entry == base {
+            self.report(
+                from,
+                Lint::UnresolvedJump,
+                format!(
+                    "JP V0 target depends on V0, and {:03X} is not a jump table",
+                    base
+                ),
+            );
+        }
+    }
+
+    /// Notes memory an instruction reads or writes through `I`
+    fn access(&mut self, address: u16, i: Known, length: u16, write: bool) {
+        let i = match i {
+            Known::Value(i) => i,
+            Known::Unknown => return,
+        };
+        if write && i < self.origin {
+            self.report(
+                address,
+                Lint::FontWrite,
+                format!("writes to {:03X}, below the program", i),
+            );
+        }
+        for byte in i..i + length {
+            if byte >= self.origin && byte < self.end() {
+                self.data.insert(byte);
+            }
+        }
+    }
+
+    fn report(&mut self, address: u16, lint: Lint, message: String) {
+        self.findings.push(Finding::new(address, lint, message));
+    }
+
+    /// Whether a ROM byte is one of the rows of a sprite
+    pub fn in_sprite(&self, address: u16) -> bool {
+        self.sprites
+            .range(..=address)
+            .any(|(&start, &height)| address < start + height as u16)
+    }
+
+    /// Where control can go from the instruction at `address`, and how
+    pub fn edges_from(&self, address: u16) -> impl Iterator<Item = (u16, Edge)> + '_ {
+        self.edges
+            .range((address, 0, Edge::Next)..(address + 1, 0, Edge::Next))
+            .map(|&(_, to, edge)| (to, edge))
+    }
+
+    /// Entry points of subroutines, `start` first
+    pub fn subroutines(&self) -> Vec<u16> {
+        let mut entries = vec![self.start];
+        let mut targets: Vec<u16> = self
+            .edges
+            .iter()
+            .filter(|(_, _, edge)| *edge == Edge::Call)
+            .map(|&(_, to, _)| to)
+            .collect();
+        targets.sort_unstable();
+        targets.dedup();
+        entries.extend(targets.into_iter().filter(|&to| to != self.start));
+        entries
+    }
+
+    /// Instructions reachable from a subroutine's entry without following calls
+    pub fn body(&self, entry: u16) -> BTreeSet<u16> {
+        let mut body = BTreeSet::new();
+        let mut queue = vec![entry];
+        while let Some(address) = queue.pop() {
+            if !self.code.contains_key(&address) || !body.insert(address) {
+                continue;
+            }
+            for (to, edge) in self.edges_from(address) {
+                if edge != Edge::Call {
+                    queue.push(to);
+                }
+            }
+        }
+        body
+    }
+}
+
+/// Lints a ROM for `platform`
+pub fn analyze(rom: &[u8], platform: Platform) -> Vec<Finding> {
+    let traversal = Traversal::run(rom, platform);
+    let mut findings = traversal.findings.clone();
+    findings.extend(unreachable(&traversal));
+    findings.extend(stack_depth(&traversal));
+    findings.sort();
+    findings
+}
+
+/// Runs of ROM bytes past any patch that are neither reached as code nor used
+/// as data
+fn unreachable(traversal: &Traversal) -> Vec<Finding> {
+    let used = |address: u16| {
+        traversal.data.contains(&address)
+            || traversal.code.contains_key(&address)
+            || (address > traversal.start && traversal.code.contains_key(&(address - 1)))
+    };
+    let mut findings = Vec::new();
+    let mut address = traversal.start;
+    while address < traversal.end() {
+        if used(address) {
+            address += 1;
+            continue;
+        }
+        let start = address;
+        while address < traversal.end() && !used(address) {
+            address += 1;
+        }
+        findings.push(Finding::new(
+            start,
+            Lint::Unreachable,
+            format!(
+                "{} bytes up to {:03X} are never run or used as data",
+                address - start,
+                address - 1
+            ),
+        ));
+    }
+    findings
+}
+
+/// Call chains deeper than the stack, and recursion
+fn stack_depth(traversal: &Traversal) -> Vec<Finding> {
+    let mut callees: BTreeMap<u16, Vec<(u16, u16)>> = BTreeMap::new();
+    for entry in traversal.subroutines() {
+        let body = traversal.body(entry);
+        let calls = traversal
+            .edges
+            .iter()
+            .filter(|(from, _, edge)| *edge == Edge::Call && body.contains(from))
+            .map(|&(from, to, _)| (from, to))
+            .collect();
+        callees.insert(entry, calls);
+    }
+
+    let mut findings = Vec::new();
+    let mut depths = HashMap::new();
+    let start = traversal.start;
+    let depth = max_depth(start, &callees, &mut Vec::new(), &mut depths, &mut findings);
+    if depth > STACK_DEPTH {
+        findings.push(Finding::new(
+            start,
+            Lint::StackDepth,
+            format!(
+                "calls nest {} deep, more than the {} the stack holds",
+                depth, STACK_DEPTH
+            ),
+        ));
+    }
+    findings
+}
+
+/// How deep calls from a subroutine can nest, reporting recursion on the way
+fn max_depth(
+    entry: u16,
+    callees: &BTreeMap<u16, Vec<(u16, u16)>>,
+    path: &mut Vec<u16>,
+    depths: &mut HashMap<u16, usize>,
+    findings: &mut Vec<Finding>,
+) -> usize {
+    if let Some(&depth) = depths.get(&entry) {
+        return depth;
+    }
+    path.push(entry);
+    let mut depth = 0;
+    for &(from, to) in callees.get(&entry).into_iter().flatten() {
+        if path.contains(&to) {
+            findings.push(Finding::new(
+                from,
+                Lint::StackDepth,
+                format!("recursive call to {:03X} can overflow the stack", to),
+            ));
+            continue;
+        }
+        depth = depth.max(1 + max_depth(to, callees, path, depths, findings));
+    }
+    path.pop();
+    depths.insert(entry, depth);
+    depth
+}
+
+/// Instruction sets beyond CHIP-8 that a ROM can use
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Extension {
+    /// SUPER-CHIP
+    Schip,
+    /// XO-CHIP
+    XoChip,
+    /// `0nnn` calls to CDP1802 code, which only the COSMAC VIP can run
+    MachineCode,
+}
+
+/// Names opcodes that only SUPER-CHIP, XO-CHIP or the COSMAC VIP understand
+pub fn platform_opcode(word: u16) -> Option<(Extension, &'static str)> {
+    use Extension::*;
+    let (x, low) = ((word >> 8) & 0xF, word & 0xFF);
+    match word >> 12 {
+        0x0 => Some(match word {
+            0x00FB => (Schip, "SCHIP scroll right (00FB)"),
+            0x00FC => (Schip, "SCHIP scroll left (00FC)"),
+            0x00FD => (Schip, "SCHIP exit (00FD)"),
+            0x00FE => (Schip, "SCHIP low resolution (00FE)"),
+            0x00FF => (Schip, "SCHIP high resolution (00FF)"),
+            0x00E0 | 0x00EE => return None,
+            _ if word & 0xFFF0 == 0x00C0 => (Schip, "SCHIP scroll down (00Cn)"),
+            _ if word & 0xFFF0 == 0x00D0 => (XoChip, "XO-CHIP scroll up (00Dn)"),
+            _ => (MachineCode, "machine code call, COSMAC VIP only (0nnn)"),
+        }),
+        0x5 if word & 0xF == 0x2 => Some((XoChip, "XO-CHIP save range (5xy2)")),
+        0x5 if word & 0xF == 0x3 => Some((XoChip, "XO-CHIP load range (5xy3)")),
+        0xD if word & 0xF == 0x0 => Some((Schip, "SCHIP 16x16 sprite (Dxy0)")),
+        0xF => match (x, low) {
+            (0, 0x00) => Some((XoChip, "XO-CHIP long I (F000)")),
+            (0, 0x02) => Some((XoChip, "XO-CHIP audio pattern (F002)")),
+            (_, 0x01) => Some((XoChip, "XO-CHIP plane select (Fn01)")),
+            (_, 0x30) => Some((Schip, "SCHIP large font (Fx30)")),
+            (_, 0x3A) => Some((XoChip, "XO-CHIP pitch (Fx3A)")),
+            (_, 0x75) => Some((Schip, "SCHIP save flags (Fx75)")),
+            (_, 0x85) => Some((Schip, "SCHIP load flags (Fx85)")),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rom(program: &[u16]) -> Vec<u8> {
+        program.iter().flat_map(|word| word.to_be_bytes()).collect()
+    }
+
+    fn lints(findings: &[Finding]) -> Vec<(u16, Lint)> {
+        findings
+            .iter()
+            .map(|finding| (finding.address, finding.lint))
+            .collect()
+    }
+
+    #[test]
+    fn test_traversal_follows_control_flow() {
+        // 200: call 20A; 202: skip if v0 == 0; 204: jump 208; 206: jump 206
+        // 208: jump 208; 20A: ret
+        let traversal = Traversal::run(
+            &rom(&[0x220A, 0x3000, 0x1208, 0x1206, 0x1208, 0x00EE]),
+            Platform::Chip8,
+        );
+        assert_eq!(
+            vec![0x200, 0x202, 0x204, 0x206, 0x208, 0x20A],
+            traversal.code.keys().copied().collect::<Vec<_>>()
+        );
+        assert!(traversal.edges.contains(&(0x200, 0x20A, Edge::Call)));
+        assert!(traversal.edges.contains(&(0x202, 0x206, Edge::Skip)));
+        assert_eq!(vec![0x200, 0x20A], traversal.subroutines());
+        assert!(analyze(&traversal.rom, Platform::Chip8).is_empty());
+    }
+
+    #[test]
+    fn test_data_and_unreachable() {
+        // 200: I = 208; 202: draw 2 rows; 204: jump 204; 206: -; 208: sprite; 20A: -
+        let findings = analyze(
+            &rom(&[0xA208, 0xD012, 0x1204, 0x1234, 0xFF81, 0x0000]),
+            Platform::Chip8,
+        );
+        assert_eq!(
+            vec![(0x206, Lint::Unreachable), (0x20A, Lint::Unreachable)],
+            lints(&findings)
+        );
+        assert_eq!(
+            "2 bytes up to 207 are never run or used as data",
+            findings[0].message
+        );
+        let symbols = SymbolMap::parse("0x206 unused").unwrap();
+        assert_eq!(
+            "unused  unreachable: 2 bytes up to 207 are never run or used as data",
+            findings[0].describe(&symbols)
+        );
+    }
+
+    #[test]
+    fn test_traversal_starts_where_the_platform_does() {
+        // 600: I = 606; 602: draw; 604: jump 604; 606: sprite
+        let program = rom(&[0xA606, 0xD012, 0x1604, 0xF090]);
+        let traversal = Traversal::run(&program, Platform::Eti660);
+        assert_eq!(
+            vec![0x600, 0x602, 0x604],
+            traversal.code.keys().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(&2), traversal.sprites.get(&0x606));
+        assert!(analyze(&program, Platform::Eti660).is_empty());
+
+        // a HIRES ROM runs from past its patch, which is not unreachable
+        let mut program = vec![0; 0xC0];
+        program.extend(rom(&[0x12C0]));
+        let traversal = Traversal::run(&program, Platform::Hires);
+        assert_eq!(vec![0x2C0], traversal.subroutines());
+        assert!(analyze(&program, Platform::Hires).is_empty());
+    }
+
+    #[test]
+    fn test_jump_tables() {
+        // 200: V0 = 2; 202: JP V0, 206; 204: -; 206: jump 20C; 208: jump 20E
+        // 20A: -; 20C: jump 20C; 20E: jump 20E
+        let program = [
+            0x6002, 0xB206, 0x0000, 0x120C, 0x120E, 0x0000, 0x120C, 0x120E,
+        ];
+        let traversal = Traversal::run(&rom(&program), Platform::Chip8);
+        assert!(traversal.edges.contains(&(0x202, 0x208, Edge::Table)));
+        assert!(
+            !traversal.code.contains_key(&0x206),
+            "should use the known V0"
+        );
+
+        // the same with V0 unknown: every jump in the table is a target
+        let program = [
+            0xC003, 0xB206, 0x0000, 0x120C, 0x120E, 0x0000, 0x120C, 0x120E,
+        ];
+        let traversal = Traversal::run(&rom(&program), Platform::Chip8);
+        assert!(traversal.edges.contains(&(0x202, 0x206, Edge::Table)));
+        assert!(traversal.edges.contains(&(0x202, 0x208, Edge::Table)));
+        assert!(traversal.code.contains_key(&0x20E));
+
+        let findings = analyze(&rom(&[0xC003, 0xB300]), Platform::Chip8);
+        assert_eq!(vec![(0x202, Lint::UnresolvedJump)], lints(&findings));
+    }
+
+    #[test]
+    fn test_lints() {
+        // 200: I = 0x050; 202: store V0; 204: hires; 206: jump 209; 208: F0FF
+        let findings = analyze(
+            &rom(&[0xA050, 0xF055, 0x00FF, 0x1209, 0xF0FF]),
+            Platform::Chip8,
+        );
+        assert_eq!(
+            vec![
+                (0x202, Lint::FontWrite),
+                (0x204, Lint::PlatformOpcode),
+                (0x206, Lint::OutsideRom),
+                (0x206, Lint::OddTarget),
+                (0x208, Lint::Unreachable),
+            ],
+            lints(&findings)
+        );
+
+        let findings = analyze(&rom(&[0x6000, 0xF0FF]), Platform::Chip8);
+        assert_eq!(vec![(0x202, Lint::InvalidOpcode)], lints(&findings));
+    }
+
+    #[test]
+    fn test_stack_depth() {
+        // 200: call 200; 202: loop
+        let findings = analyze(&rom(&[0x2200, 0x1202]), Platform::Chip8);
+        assert_eq!(vec![(0x200, Lint::StackDepth)], lints(&findings));
+        assert!(findings[0].message.starts_with("recursive call"));
+
+        // 200: call 204; 202: loop; then 16 routines that each call the next
+        let mut program = vec![0x2204, 0x1202];
+        for i in 1..16 {
+            program.extend([0x2204 + i * 4, 0x00EE]);
+        }
+        program.push(0x00EE);
+        let traversal = Traversal::run(&rom(&program), Platform::Chip8);
+        assert_eq!(17, traversal.subroutines().len());
+        let findings = analyze(&traversal.rom, Platform::Chip8);
+        assert_eq!(vec![(0x200, Lint::StackDepth)], lints(&findings));
+        assert_eq!(
+            "calls nest 16 deep, more than the 15 the stack holds",
+            findings[0].message
+        );
+    }
+}
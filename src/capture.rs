@@ -0,0 +1,446 @@
+use crate::chip8::Chip8;
+use crate::controls::FRAME_RATE;
+use crate::image::Image;
+use crate::palette::{Palette, Rgb, CHIP8X_BACKGROUNDS, CHIP8X_COLORS};
+use crate::platform::Platform;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Shortest frame delay, in hundredths of a second, that viewers honor;
+/// most show shorter ones much slower instead
+const MIN_DELAY: u64 = 2;
+
+/// The colors captures are drawn in: the palette's background and
+/// foreground, or on CHIP-8X its backgrounds followed by its zone colors
+pub fn colors(platform: Platform, palette: Palette) -> Vec<Rgb> {
+    match platform {
+        Platform::Chip8x => [CHIP8X_BACKGROUNDS.as_slice(), &CHIP8X_COLORS].concat(),
+        _ => vec![palette.background, palette.foreground],
+    }
+}
+
+/// The display as indices into `colors`, taking each lit CHIP-8X pixel's
+/// color from its zone
+pub fn pixels(chip8: &Chip8) -> Vec<u8> {
+    let width = chip8.width();
+    chip8
+        .gfx
+        .iter()
+        .enumerate()
+        .map(|(i, &pixel)| match (chip8.platform, pixel != 0) {
+            (Platform::Chip8x, false) => chip8.background % 4,
+            (Platform::Chip8x, true) => {
+                let zone = (i / width) * 8 + (i % width) / 8;
+                CHIP8X_BACKGROUNDS.len() as u8 + chip8.colors[zone] % 8
+            }
+            (_, lit) => lit as u8,
+        })
+        .collect()
+}
+
+/// The display, `width` pixels across, from `pixels` indexing `colors`, each
+/// pixel `scale` pixels square
+pub fn screenshot(pixels: &[u8], width: usize, colors: &[Rgb], scale: u32) -> Image {
+    let height = pixels.len() / width;
+    let mut image = Image::new(width as u32 * scale, height as u32 * scale, colors[0]);
+    for (i, &pixel) in pixels.iter().enumerate() {
+        if pixel != 0 {
+            let (x, y) = ((i % width) as u32, (i / width) as u32);
+            image.fill(x * scale, y * scale, scale, scale, colors[pixel as usize]);
+        }
+    }
+    image
+}
+
+/// The first of `game-1.png`, `game-2.png`, ... next to the ROM that does
+/// not exist yet, for captures taken with hotkeys
+pub fn next_path(rom: &str, extension: &str) -> String {
+    let stem = Path::new(rom).with_extension("");
+    (1..)
+        .map(|n| format!("{}-{}.{}", stem.to_string_lossy(), n, extension))
+        .find(|path| !Path::new(path).exists())
+        .unwrap()
+}
+
+/// Records the display into an animated GIF, one frame per emulated frame
+///
+/// Runs of identical frames become a single GIF frame. GIF delays are in
+/// hundredths of a second, so each frame's delay is rounded against the
+/// total time so far to keep the recording in step with the 60 Hz display;
+/// a frame shown for less than `MIN_DELAY` is dropped in favor of the next.
+pub struct GifRecorder<W: Write> {
+    gif: Gif<W>,
+    width: usize,
+    scale: u32,
+    /// The frame waiting to learn how long it is shown, and when it started
+    pending: Option<(Vec<u8>, u64)>,
+}
+
+impl GifRecorder<BufWriter<File>> {
+    pub fn create(
+        path: &str,
+        (width, height): (usize, usize),
+        colors: &[Rgb],
+        scale: u32,
+    ) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Error writing {}: {:?}", path, e))?;
+        GifRecorder::new(BufWriter::new(file), (width, height), colors, scale)
+            .map_err(|e| format!("Error writing {}: {:?}", path, e))
+    }
+}
+
+impl<W: Write> GifRecorder<W> {
+    /// Records a display of `width` by `height` pixels, indexing `colors`
+    pub fn new(
+        out: W,
+        (width, height): (usize, usize),
+        colors: &[Rgb],
+        scale: u32,
+    ) -> io::Result<Self> {
+        let (pixels_across, pixels_down) = (width as u32 * scale, height as u32 * scale);
+        Ok(GifRecorder {
+            gif: Gif::new(out, pixels_across, pixels_down, colors)?,
+            width,
+            scale,
+            pending: None,
+        })
+    }
+
+    /// Adds the display, as `pixels`, as it was during emulated frame `frame`
+    pub fn frame(&mut self, pixels: &[u8], frame: u64) -> io::Result<()> {
+        match &mut self.pending {
+            Some((pending, _)) if pending.as_slice() == pixels => {}
+            Some((pending, start)) if centiseconds(frame) - centiseconds(*start) < MIN_DELAY => {
+                *pending = pixels.to_vec();
+            }
+            _ => {
+                self.flush(frame)?;
+                self.pending = Some((pixels.to_vec(), frame));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the last frame, shown until `frame`, and ends the file
+    pub fn finish(mut self, frame: u64) -> io::Result<W> {
+        self.flush(frame)?;
+        self.gif.finish()
+    }
+
+    fn flush(&mut self, frame: u64) -> io::Result<()> {
+        if let Some((pixels, start)) = self.pending.take() {
+            let delay = (centiseconds(frame) - centiseconds(start)).max(MIN_DELAY);
+            let indices = scale_up(&pixels, self.width, self.scale);
+            self.gif.frame(&indices, delay as u16)?;
+        }
+        Ok(())
+    }
+}
+
+/// When emulated frame `frame` starts, in hundredths of a second
+fn centiseconds(frame: u64) -> u64 {
+    (frame * 100 + FRAME_RATE as u64 / 2) / FRAME_RATE as u64
+}
+
+/// The display's color indices, each pixel `scale` pixels square
+fn scale_up(pixels: &[u8], width: usize, scale: u32) -> Vec<u8> {
+    let scale = scale as usize;
+    let mut indices = Vec::with_capacity(pixels.len() * scale * scale);
+    for row in pixels.chunks(width) {
+        let line: Vec<u8> = row
+            .iter()
+            .flat_map(|&pixel| std::iter::repeat_n(pixel, scale))
+            .collect();
+        for _ in 0..scale {
+            indices.extend_from_slice(&line);
+        }
+    }
+    indices
+}
+
+/// A GIF89a stream with one global color table that loops forever
+struct Gif<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+    /// Bits per color index, as the color table's size
+    depth: u8,
+}
+
+/// LZW code size for two colors; GIF allows nothing smaller
+const MIN_CODE_SIZE: u8 = 2;
+
+/// Codes only go up to 12 bits
+const MAX_CODE: u16 = 4095;
+
+impl<W: Write> Gif<W> {
+    fn new(mut out: W, width: u32, height: u32, colors: &[Rgb]) -> io::Result<Self> {
+        let (width, height) = (width as u16, height as u16);
+        let depth = (usize::BITS - (colors.len() - 1).leading_zeros()).max(1) as u8;
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        // a global color table padded to a power of two, background color 0, square pixels
+        out.write_all(&[0x80 | (depth - 1), 0, 0])?;
+        for index in 0..1 << depth {
+            let Rgb(r, g, b) = colors.get(index).copied().unwrap_or(Rgb(0, 0, 0));
+            out.write_all(&[r, g, b])?;
+        }
+        // the Netscape extension, looping forever
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(Gif {
+            out,
+            width,
+            height,
+            depth,
+        })
+    }
+
+    /// Writes a full frame of palette indices, shown for `delay` hundredths of a second
+    fn frame(&mut self, indices: &[u8], delay: u16) -> io::Result<()> {
+        // graphic control: leave the frame in place for the next one
+        self.out.write_all(&[0x21, 0xF9, 4, 0x04])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0, 0])?;
+
+        self.out.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.out.write_all(&self.width.to_le_bytes())?;
+        self.out.write_all(&self.height.to_le_bytes())?;
+        let code_size = self.depth.max(MIN_CODE_SIZE);
+        self.out.write_all(&[0, code_size])?;
+        for block in lzw(indices, code_size).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0])
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Packs codes least significant bit first, as GIF wants them
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compresses color indices with GIF's variable-width LZW, starting from
+/// `code_size` bits per index
+fn lzw(indices: &[u8], code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << code_size;
+    let end = clear + 1;
+    let mut out = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut size = code_size + 1;
+    let mut next = end + 1;
+    out.write(clear, size);
+
+    let mut pixels = indices.iter();
+    let mut prefix = match pixels.next() {
+        Some(&first) => first as u16,
+        None => {
+            out.write(end, size);
+            return out.finish();
+        }
+    };
+    for &pixel in pixels {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        out.write(prefix, size);
+        // the decoder widens its codes one entry behind the encoder
+        if next >= 1 << size && size < 12 {
+            size += 1;
+        }
+        if next >= MAX_CODE {
+            out.write(clear, size);
+            table.clear();
+            size = code_size + 1;
+            next = end + 1;
+        } else {
+            table.insert((prefix, pixel), next);
+            next += 1;
+        }
+        prefix = pixel as u16;
+    }
+    out.write(prefix, size);
+    out.write(end, size);
+    out.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PALETTE: Palette = Palette {
+        background: Rgb(0, 0, 0),
+        foreground: Rgb(255, 255, 255),
+    };
+
+    /// Decodes GIF LZW data back to palette indices
+    fn unlzw(data: &[u8], code_size: u8) -> Vec<u8> {
+        let clear = 1u16 << code_size;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let reset = |table: &mut Vec<Vec<u8>>| {
+            *table = (0..clear).map(|i| vec![i as u8]).collect();
+            table.push(Vec::new());
+            table.push(Vec::new());
+        };
+        reset(&mut table);
+        let (mut size, mut position) = (code_size + 1, 0);
+        let mut previous: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        loop {
+            let mut code = 0u16;
+            for bit in 0..size as usize {
+                let (byte, shift) = ((position + bit) / 8, (position + bit) % 8);
+                code |= ((data[byte] >> shift & 1) as u16) << bit;
+            }
+            position += size as usize;
+            if code == clear {
+                reset(&mut table);
+                size = code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return out;
+            }
+            let entry = match (table.get(code as usize), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [previous.clone(), vec![previous[0]]].concat(),
+                (None, None) => panic!("code {} before any entry", code),
+            };
+            if let Some(previous) = previous {
+                table.push([previous, vec![entry[0]]].concat());
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+            out.extend_from_slice(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw_round_trip() {
+        // long enough to fill the table and clear it, with runs and noise
+        let mut seed = 1u32;
+        let indices: Vec<u8> = (0..40_000)
+            .map(|i| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                if i % 3000 < 1500 {
+                    (i / 7 % 2) as u8
+                } else {
+                    (seed >> 16 & 1) as u8
+                }
+            })
+            .collect();
+        assert_eq!(indices, unlzw(&lzw(&indices, 2), 2));
+        assert_eq!(vec![1], unlzw(&lzw(&[1], 2), 2));
+        let colored: Vec<u8> = indices
+            .iter()
+            .enumerate()
+            .map(|(i, &index)| index * (i % 12) as u8)
+            .collect();
+        assert_eq!(colored, unlzw(&lzw(&colored, 4), 4));
+    }
+
+    #[test]
+    fn test_screenshot() {
+        let mut gfx = [0; 64 * 32];
+        gfx[64 + 2] = 1;
+        let image = screenshot(&gfx, 64, &colors(Platform::Chip8, PALETTE), 3);
+        assert_eq!((192, 96), (image.width, image.height));
+        let at = |x: u32, y: u32| image.pixels[(y * image.width + x) as usize];
+        assert_eq!(PALETTE.foreground, at(6, 3));
+        assert_eq!(PALETTE.foreground, at(8, 5));
+        assert_eq!(PALETTE.background, at(9, 5));
+    }
+
+    #[test]
+    fn test_gif_frames() {
+        let (blank, mut lit) = ([0; 64 * 32], [0; 64 * 32]);
+        lit[0] = 1;
+        let colors = colors(Platform::Chip8, PALETTE);
+        let mut recorder = GifRecorder::new(Vec::new(), (64, 32), &colors, 1).unwrap();
+        for frame in 0..60 {
+            // lit for a frame at 10, too short to keep, and for 10 frames from 30
+            let shown = frame == 10 || (30..40).contains(&frame);
+            recorder
+                .frame(if shown { &lit } else { &blank }, frame)
+                .unwrap();
+        }
+        let gif = recorder.finish(60).unwrap();
+        assert_eq!(b"GIF89a", &gif[..6]);
+        assert_eq!([64, 0, 32, 0], gif[6..10]);
+        assert_eq!(Some(&0x3B), gif.last());
+
+        let delays: Vec<u16> = gif
+            .windows(4)
+            .enumerate()
+            .filter(|(_, window)| window[..3] == [0x21, 0xF9, 4])
+            .map(|(i, _)| u16::from_le_bytes([gif[i + 4], gif[i + 5]]))
+            .collect();
+        assert_eq!(
+            vec![17, 33, 17, 33],
+            delays,
+            "should drop the one-frame flash and add up to a second"
+        );
+    }
+
+    #[test]
+    fn test_chip8x_colors() {
+        let mut chip8 = Chip8::for_platform(Platform::Chip8x);
+        chip8.background = 2;
+        // the zone 8 pixels right of the top left one is red
+        chip8.colors[1] = 4;
+        chip8.gfx[0] = 1;
+        chip8.gfx[8] = 1;
+        let pixels = pixels(&chip8);
+        // zone colors, red unless set, come after the four backgrounds
+        assert_eq!([5, 2, 8], [pixels[0], pixels[1], pixels[8]]);
+
+        let colors = colors(Platform::Chip8x, PALETTE);
+        let image = screenshot(&pixels, 64, &colors, 1);
+        assert_eq!(CHIP8X_COLORS[1], image.pixels[0]);
+        assert_eq!(CHIP8X_BACKGROUNDS[2], image.pixels[1]);
+        assert_eq!(CHIP8X_COLORS[4], image.pixels[8]);
+
+        let mut recorder = GifRecorder::new(Vec::new(), (64, 32), &colors, 1).unwrap();
+        recorder.frame(&pixels, 0).unwrap();
+        let gif = recorder.finish(1).unwrap();
+        assert_eq!(0x80 | 3, gif[10], "should have a 16-color table");
+        let table = &gif[13..13 + 16 * 3];
+        assert_eq!([0, 128, 0], table[2 * 3..3 * 3]);
+        assert_eq!(Some(&4), gif.get(13 + 16 * 3 + 19 + 8 + 10), "code size");
+    }
+}
@@ -0,0 +1,459 @@
+/// What an 1802 is wired to: memory, the I/O ports and the EF input flags
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+
+    fn write(&mut self, address: u16, value: u8);
+
+    /// `INP n`, for ports 1-7
+    fn input(&mut self, _port: u8) -> u8 {
+        0
+    }
+
+    /// `OUT n`, for ports 1-7
+    fn output(&mut self, _port: u8, _value: u8) {}
+
+    /// Level of flag EF1-EF4, as tested by `B1`-`B4` and `BN1`-`BN4`
+    fn flag(&mut self, _flag: u8) -> bool {
+        false
+    }
+}
+
+/// RCA CDP1802 CPU
+///
+/// Registers are named as in the datasheet: sixteen 16-bit scratchpad
+/// registers `r`, with `r[p]` the program counter and `r[x]` the data pointer.
+/// The `Q` output is left in `q` for whatever is connected to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cdp1802 {
+    pub r: [u16; 16],
+    pub p: u8,
+    pub x: u8,
+    pub d: u8,
+    pub df: bool,
+    pub t: u8,
+    pub ie: bool,
+    pub q: bool,
+    /// Stopped by `IDL`; whatever drives the CPU decides when to carry on
+    pub idle: bool,
+}
+
+impl Cdp1802 {
+    /// The state after a reset: `P`, `X` and `R0` are 0 and interrupts are enabled
+    pub fn new() -> Self {
+        Cdp1802 {
+            r: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        }
+    }
+
+    /// Runs one instruction, returning the machine cycles it took (8 clocks each)
+    pub fn step<B: Bus>(&mut self, bus: &mut B) -> u32 {
+        if self.idle {
+            return 1;
+        }
+        let opcode = self.fetch(bus);
+        let (i, n) = (opcode >> 4, opcode & 0xF);
+        let rn = n as usize;
+        let rx = self.x as usize;
+        match i {
+            0x0 if n == 0 => self.idle = true,
+            0x0 => self.d = bus.read(self.r[rn]),
+            0x1 => self.r[rn] = self.r[rn].wrapping_add(1),
+            0x2 => self.r[rn] = self.r[rn].wrapping_sub(1),
+            0x3 => {
+                let condition = self.condition(bus, n);
+                self.short_branch(bus, condition);
+            }
+            0x4 => {
+                self.d = bus.read(self.r[rn]);
+                self.r[rn] = self.r[rn].wrapping_add(1);
+            }
+            0x5 => bus.write(self.r[rn], self.d),
+            0x6 => match n {
+                0x0 => self.r[rx] = self.r[rx].wrapping_add(1),
+                0x1..=0x7 => {
+                    let value = bus.read(self.r[rx]);
+                    bus.output(n, value);
+                    self.r[rx] = self.r[rx].wrapping_add(1);
+                }
+                // 68 is an 1804 prefix; on an 1802 it does nothing
+                0x8 => {}
+                _ => {
+                    let value = bus.input(n - 8);
+                    bus.write(self.r[rx], value);
+                    self.d = value;
+                }
+            },
+            0x7 => self.execute_7(bus, n),
+            0x8 => self.d = self.r[rn] as u8,
+            0x9 => self.d = (self.r[rn] >> 8) as u8,
+            0xA => self.r[rn] = self.r[rn] & 0xFF00 | self.d as u16,
+            0xB => self.r[rn] = self.r[rn] & 0x00FF | (self.d as u16) << 8,
+            0xC => {
+                self.long_branch_or_skip(bus, n);
+                return 3;
+            }
+            0xD => self.p = n,
+            0xE => self.x = n,
+            _ => self.execute_f(bus, n),
+        }
+        2
+    }
+
+    /// Takes an interrupt if they are enabled, returning whether it did
+    ///
+    /// `X` and `P` are saved in `T`, then the CPU carries on from `R1` with
+    /// `X` = 2 and interrupts disabled, which takes one machine cycle.
+    pub fn interrupt(&mut self) -> bool {
+        if !self.ie {
+            return false;
+        }
+        self.t = self.x << 4 | self.p;
+        self.x = 2;
+        self.p = 1;
+        self.ie = false;
+        self.idle = false;
+        true
+    }
+
+    /// One DMA out cycle, returning the byte at `R0` for the device and moving
+    /// `R0` on; like any DMA, it ends an `IDL`
+    pub fn dma_out<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let value = bus.read(self.r[0]);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        value
+    }
+
+    fn fetch<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let p = self.p as usize;
+        let value = bus.read(self.r[p]);
+        self.r[p] = self.r[p].wrapping_add(1);
+        value
+    }
+
+    /// The condition of a `3N` short branch; the high bit of `N` negates it
+    fn condition<B: Bus>(&mut self, bus: &mut B, n: u8) -> bool {
+        let condition = match n & 0x7 {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            0x3 => self.df,
+            flag => bus.flag(flag - 3),
+        };
+        condition != (n & 0x8 != 0)
+    }
+
+    fn short_branch<B: Bus>(&mut self, bus: &mut B, condition: bool) {
+        let p = self.p as usize;
+        if condition {
+            let low = bus.read(self.r[p]);
+            self.r[p] = self.r[p] & 0xFF00 | low as u16;
+        } else {
+            self.r[p] = self.r[p].wrapping_add(1);
+        }
+    }
+
+    /// `CN`: long branches, long skips and `NOP`
+    fn long_branch_or_skip<B: Bus>(&mut self, bus: &mut B, n: u8) {
+        let p = self.p as usize;
+        let condition = match n & 0x3 {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            _ => self.df,
+        };
+        match n {
+            // NOP
+            0x4 => {}
+            // LSIE
+            0xC => {
+                if self.ie {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            }
+            // LBR, LBQ, LBZ, LBDF and, with the high bit, their negations; the
+            // negated LBR is LSKP, a branch never taken
+            0x0..=0x3 | 0x8..=0xB => {
+                let condition = condition != (n & 0x8 != 0);
+                if condition {
+                    let high = bus.read(self.r[p]);
+                    let low = bus.read(self.r[p].wrapping_add(1));
+                    self.r[p] = (high as u16) << 8 | low as u16;
+                } else {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            }
+            // LSNQ, LSNZ and LSNF, then LSQ, LSZ and LSDF
+            _ => {
+                if condition != (n < 0x8) {
+                    self.r[p] = self.r[p].wrapping_add(2);
+                }
+            }
+        }
+    }
+
+    fn execute_7<B: Bus>(&mut self, bus: &mut B, n: u8) {
+        let rx = self.x as usize;
+        match n {
+            // RET and DIS
+            0x0 | 0x1 => {
+                let xp = bus.read(self.r[rx]);
+                self.r[rx] = self.r[rx].wrapping_add(1);
+                self.x = xp >> 4;
+                self.p = xp & 0xF;
+                self.ie = n == 0x0;
+            }
+            // LDXA
+            0x2 => {
+                self.d = bus.read(self.r[rx]);
+                self.r[rx] = self.r[rx].wrapping_add(1);
+            }
+            // STXD
+            0x3 => {
+                bus.write(self.r[rx], self.d);
+                self.r[rx] = self.r[rx].wrapping_sub(1);
+            }
+            // ADC, SDB, SMB and their immediate forms
+            0x4 | 0x5 | 0x7 | 0xC | 0xD | 0xF => {
+                let operand = if n < 0x8 {
+                    bus.read(self.r[rx])
+                } else {
+                    self.fetch(bus)
+                };
+                match n & 0x7 {
+                    0x4 => self.add(operand, self.df),
+                    0x5 => self.subtract(operand, self.d, self.df),
+                    _ => self.subtract(self.d, operand, self.df),
+                }
+            }
+            // SHRC
+            0x6 => {
+                let carry = self.d & 1 != 0;
+                self.d = self.d >> 1 | (self.df as u8) << 7;
+                self.df = carry;
+            }
+            // SAV
+            0x8 => bus.write(self.r[rx], self.t),
+            // MARK
+            0x9 => {
+                self.t = self.x << 4 | self.p;
+                bus.write(self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            0xA => self.q = false,
+            0xB => self.q = true,
+            // SHLC
+            _ => {
+                let carry = self.d & 0x80 != 0;
+                self.d = self.d << 1 | self.df as u8;
+                self.df = carry;
+            }
+        }
+    }
+
+    fn execute_f<B: Bus>(&mut self, bus: &mut B, n: u8) {
+        let rx = self.x as usize;
+        match n {
+            // SHR and SHL
+            0x6 => {
+                self.df = self.d & 1 != 0;
+                self.d >>= 1;
+            }
+            0xE => {
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            }
+            _ => {
+                let operand = if n < 0x8 {
+                    bus.read(self.r[rx])
+                } else {
+                    self.fetch(bus)
+                };
+                match n & 0x7 {
+                    0x0 => self.d = operand,
+                    0x1 => self.d |= operand,
+                    0x2 => self.d &= operand,
+                    0x3 => self.d ^= operand,
+                    0x4 => self.add(operand, false),
+                    0x5 => self.subtract(operand, self.d, true),
+                    _ => self.subtract(self.d, operand, true),
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, operand: u8, carry: bool) {
+        let sum = self.d as u16 + operand as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    /// `D = minuend - subtrahend`, with `DF` set when there was no borrow
+    fn subtract(&mut self, minuend: u8, subtrahend: u8, no_borrow: bool) {
+        let difference = minuend as i16 - subtrahend as i16 - !no_borrow as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Ram {
+        memory: Vec<u8>,
+        outputs: Vec<(u8, u8)>,
+    }
+
+    impl Ram {
+        fn with_program(program: &[u8]) -> Self {
+            let mut memory = vec![0; 0x1000];
+            memory[..program.len()].copy_from_slice(program);
+            Ram {
+                memory,
+                outputs: Vec::new(),
+            }
+        }
+    }
+
+    impl Bus for Ram {
+        fn read(&mut self, address: u16) -> u8 {
+            self.memory[address as usize & 0xFFF]
+        }
+
+        fn write(&mut self, address: u16, value: u8) {
+            self.memory[address as usize & 0xFFF] = value;
+        }
+
+        fn input(&mut self, port: u8) -> u8 {
+            port * 0x11
+        }
+
+        fn output(&mut self, port: u8, value: u8) {
+            self.outputs.push((port, value));
+        }
+
+        fn flag(&mut self, flag: u8) -> bool {
+            flag == 3
+        }
+    }
+
+    /// Runs until the CPU idles, returning it and the memory
+    fn run(program: &[u8]) -> (Cdp1802, Ram) {
+        let mut cpu = Cdp1802::new();
+        let mut ram = Ram::with_program(program);
+        for _ in 0..1000 {
+            if cpu.idle {
+                return (cpu, ram);
+            }
+            cpu.step(&mut ram);
+        }
+        panic!("should have reached IDL");
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // LDI 0xF0; ADI 0x20; (D = 0x10, DF = 1) PLO R5; LDI 0x10; SMI 0x20; PHI R5; IDL
+        let (cpu, _) = run(&[
+            0xF8, 0xF0, 0xFC, 0x20, 0xA5, 0xF8, 0x10, 0xFF, 0x20, 0xB5, 0x00,
+        ]);
+        assert_eq!(0xF010, cpu.r[5]);
+        assert!(!cpu.df, "should borrow");
+
+        // LDI 0x10; SDI 0x30; (D = 0x20, DF = 1) SHRC; ADCI 0x01; IDL
+        let (cpu, _) = run(&[0xF8, 0x10, 0xFD, 0x30, 0x76, 0x7C, 0x01, 0x00]);
+        assert_eq!(0x91, cpu.d, "should shift DF in at the top");
+        assert!(!cpu.df);
+    }
+
+    #[test]
+    fn test_memory_and_io() {
+        // LDI 0x80; PHI R2; SEX R2; LDI 0x42; STXD; IRX; OUT 3; INP 5; IDL
+        let (cpu, ram) = run(&[
+            0xF8, 0x80, 0xB2, 0xE2, 0xF8, 0x42, 0x73, 0x60, 0x63, 0x6D, 0x00,
+        ]);
+        assert_eq!(
+            0x42, ram.memory[0x000],
+            "STXD should store at R2 (0x8000 wraps)"
+        );
+        assert_eq!(vec![(3, 0x42)], ram.outputs);
+        assert_eq!(0x55, cpu.d);
+        assert_eq!(0x55, ram.memory[0x001]);
+        assert_eq!(0x8001, cpu.r[2]);
+    }
+
+    #[test]
+    fn test_branches() {
+        // 00: LDI 0; BZ 06; IDL; IDL; 06: B3 09; IDL; 09: LBNZ 0x0100; LSKP; IDL; IDL; SEQ; IDL
+        let (cpu, _) = run(&[
+            0xF8, 0x00, 0x32, 0x06, 0x00, 0x00, 0x36, 0x09, 0x00, 0xCA, 0x01, 0x00, 0xC8, 0x00,
+            0x00, 0x7B, 0x00,
+        ]);
+        assert!(cpu.q, "should reach SEQ");
+        assert_eq!(0x11, cpu.r[0]);
+    }
+
+    #[test]
+    fn test_subroutines() {
+        // 00: LDI 0x10; PLO R3; LDI 0x80; PLO R2; SEP R3
+        // 10: MARK; SEX R2; INC R2; DIS
+        let mut program = vec![0xF8, 0x10, 0xA3, 0xF8, 0x80, 0xA2, 0xD3, 0x00];
+        program.resize(0x10, 0);
+        program.extend_from_slice(&[0x79, 0xE2, 0x12, 0x71, 0x00]);
+        let mut cpu = Cdp1802::new();
+        let mut ram = Ram::with_program(&program);
+        for _ in 0..6 {
+            cpu.step(&mut ram);
+        }
+        assert_eq!((3, 3), (cpu.x, cpu.p), "MARK should set X to P");
+        assert_eq!(0x03, ram.memory[0x80], "MARK should save X and P");
+        assert_eq!(0x7F, cpu.r[2]);
+        for _ in 0..3 {
+            cpu.step(&mut ram);
+        }
+        assert_eq!((0, 3), (cpu.x, cpu.p), "DIS should restore X and P");
+        assert_eq!(0x81, cpu.r[2]);
+        assert!(!cpu.ie);
+    }
+
+    #[test]
+    fn test_interrupt_and_dma() {
+        // 00: IDL; 01: IDL; 10: SAV; RET with X and P popped from 0x7F
+        let mut program = vec![0x00, 0x00];
+        program.resize(0x10, 0);
+        program.extend_from_slice(&[0x78, 0x70]);
+        let mut ram = Ram::with_program(&program);
+        ram.memory[0x7F] = 0x00;
+        let mut cpu = Cdp1802::new();
+        cpu.r[1] = 0x10;
+        cpu.r[2] = 0x7E;
+        cpu.step(&mut ram);
+        assert!(cpu.idle);
+
+        assert!(cpu.interrupt());
+        assert!(!cpu.interrupt(), "should be disabled while handling one");
+        assert_eq!((2, 1, 0x00), (cpu.x, cpu.p, cpu.t));
+        cpu.step(&mut ram);
+        assert_eq!(0x00, ram.memory[0x7E], "SAV should store T");
+        cpu.r[2] = 0x7F;
+        cpu.step(&mut ram);
+        assert_eq!((0, 0, 0x01), (cpu.x, cpu.p, cpu.r[0]));
+        assert!(cpu.ie, "RET should enable interrupts");
+
+        cpu.step(&mut ram);
+        assert!(cpu.idle);
+        cpu.r[0] = 0x10;
+        assert_eq!(0x78, cpu.dma_out(&mut ram));
+        assert_eq!(0x11, cpu.r[0]);
+        assert!(!cpu.idle, "DMA should end IDL");
+    }
+}
@@ -0,0 +1,184 @@
+use crate::analyze::{Edge, Traversal};
+use crate::decode::{Instruction, Op};
+use crate::disasm::mnemonic;
+use crate::platform::Platform;
+use crate::symbols::SymbolMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// Splits the reached code into basic blocks, keyed by their first address
+///
+/// A block starts at the start address, at anything jumped, called or skipped to, and
+/// after anything that branches; each block lists its instruction addresses.
+pub fn blocks(traversal: &Traversal) -> BTreeMap<u16, Vec<u16>> {
+    let ends = |address: u16| {
+        let mut edges = traversal.edges_from(address).peekable();
+        edges.peek().is_none() || edges.any(|(_, edge)| edge != Edge::Next)
+    };
+    let mut leaders = BTreeSet::from([traversal.start]);
+    for &(from, to, edge) in &traversal.edges {
+        if edge != Edge::Next {
+            leaders.insert(to);
+            leaders.insert(from + 2);
+        }
+    }
+
+    let mut blocks: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+    let mut current = None;
+    for &address in traversal.code.keys() {
+        let start = match current {
+            Some(start) if !leaders.contains(&address) => start,
+            _ => address,
+        };
+        blocks.entry(start).or_default().push(address);
+        current = if ends(address) { None } else { Some(start) };
+    }
+    blocks
+}
+
+/// A Graphviz DOT graph of the ROM's control flow
+///
+/// Basic blocks are grouped into one cluster per subroutine and list their
+/// instructions, with labels from `symbols`. Edges show fallthrough, skips,
+/// jumps, `JP V0` table entries, calls and, dashed, returns to each call site.
+pub fn dot(rom: &[u8], platform: Platform, symbols: &SymbolMap) -> String {
+    let traversal = Traversal::run(rom, platform);
+    let blocks = blocks(&traversal);
+    let block_of = |address: u16| {
+        blocks
+            .range(..=address)
+            .next_back()
+            .map(|(&start, _)| start)
+    };
+
+    let mut out = String::from("digraph cfg {\n");
+    out.push_str("    node [shape=box, fontname=monospace];\n");
+    let mut placed = Vec::new();
+    for entry in traversal.subroutines() {
+        let body = traversal.body(entry);
+        writeln!(out, "    subgraph cluster_{:03X} {{", entry).unwrap();
+        writeln!(
+            out,
+            "        label=\"{}\";",
+            escape(&symbols.describe(entry))
+        )
+        .unwrap();
+        for (&start, addresses) in &blocks {
+            if !body.contains(&start) || placed.contains(&start) {
+                continue;
+            }
+            placed.push(start);
+            let mut label = String::new();
+            if let Some(name) = symbols.label_at(start) {
+                write!(label, "{}:\\l", escape(name)).unwrap();
+            }
+            for address in addresses {
+                let line = format!(
+                    "{:03X}  {}",
+                    address,
+                    mnemonic(traversal.code[address], symbols)
+                );
+                write!(label, "{}\\l", escape(&line)).unwrap();
+            }
+            writeln!(out, "        b{:03X} [label=\"{}\"];", start, label).unwrap();
+        }
+        out.push_str("    }\n");
+    }
+
+    for &(from, to, edge) in &traversal.edges {
+        let style = match edge {
+            Edge::Next if block_of(from) == block_of(to) => continue,
+            Edge::Next => "",
+            Edge::Skip => " [label=\"skip\"]",
+            Edge::Jump => "",
+            Edge::Call => " [label=\"call\", style=bold]",
+            Edge::Table => " [label=\"table\"]",
+        };
+        writeln!(
+            out,
+            "    b{:03X} -> b{:03X}{};",
+            block_of(from).unwrap(),
+            to,
+            style
+        )
+        .unwrap();
+    }
+    for (from, to) in returns(&traversal) {
+        writeln!(
+            out,
+            "    b{:03X} -> b{:03X} [label=\"ret\", style=dashed];",
+            block_of(from).unwrap(),
+            to
+        )
+        .unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// (return instruction, call site it goes back to) for every reached `RET`
+fn returns(traversal: &Traversal) -> Vec<(u16, u16)> {
+    let mut returns = Vec::new();
+    for entry in traversal.subroutines() {
+        for address in traversal.body(entry) {
+            if Instruction::decode(traversal.code[&address]).op != Op::Ret {
+                continue;
+            }
+            for &(from, to, edge) in &traversal.edges {
+                if edge == Edge::Call && to == entry && traversal.code.contains_key(&(from + 2)) {
+                    returns.push((address, from + 2));
+                }
+            }
+        }
+    }
+    returns
+}
+
+/// Makes text safe inside a quoted DOT label
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 200: call 208; 202: skip if key V0 down; 204: jump 200; 206: loop
+    // 208: V1 = 1; 20A: ret
+    const PROGRAM: [u8; 12] = [
+        0x22, 0x08, 0xE0, 0x9E, 0x12, 0x00, 0x12, 0x06, 0x61, 0x01, 0x00, 0xEE,
+    ];
+
+    #[test]
+    fn test_blocks() {
+        let traversal = Traversal::run(&PROGRAM, Platform::Chip8);
+        let blocks = blocks(&traversal);
+        assert_eq!(
+            vec![
+                (0x200, vec![0x200]),
+                (0x202, vec![0x202]),
+                (0x204, vec![0x204]),
+                (0x206, vec![0x206]),
+                (0x208, vec![0x208, 0x20A]),
+            ],
+            blocks.into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_dot() {
+        let symbols = SymbolMap::parse("0x208 set_v1").unwrap();
+        let dot = dot(&PROGRAM, Platform::Chip8, &symbols);
+        assert!(dot.starts_with("digraph cfg {\n"));
+        assert!(dot.contains("    subgraph cluster_200 {\n        label=\"200\";\n"));
+        assert!(dot.contains("        label=\"set_v1\";\n"));
+        assert!(
+            dot.contains("        b208 [label=\"set_v1:\\l208  LD V1, 0x01\\l20A  RET\\l\"];\n")
+        );
+        assert!(dot.contains("    b200 -> b208 [label=\"call\", style=bold];\n"));
+        assert!(dot.contains("    b202 -> b206 [label=\"skip\"];\n"));
+        assert!(dot.contains("    b204 -> b200;\n"));
+        assert!(dot.contains("    b208 -> b202 [label=\"ret\", style=dashed];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
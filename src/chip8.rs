@@ -1,40 +1,142 @@
 extern crate rand;
 
-use crate::instructions::*;
-use crate::utils::*;
-use std::fs::File;
-use std::io::Read;
+use crate::decode::{execute, Instruction};
+use crate::observer::{Observer, Register, Timer};
+use crate::platform::Platform;
+use crate::quirks::Quirks;
+use crate::utils::gen_rand_u8;
+use std::collections::VecDeque;
+use std::fs;
+
+/// Something a program did that the machine cannot carry on from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// The word at `pc` is not an instruction
+    InvalidOpcode,
+    /// `2nnn` with every stack slot in use
+    StackOverflow,
+    /// `00EE` with nothing to return to
+    StackUnderflow,
+    /// `pc` ran off the end of memory
+    PcOutOfRange,
+    /// An instruction reached past the end of memory through `I`
+    MemoryOutOfRange,
+    /// `0nnn` called machine code, which the settings treat as an error
+    MachineCode,
+}
+
+impl Fault {
+    /// Every fault, in the order `name` lists them
+    pub const ALL: [Fault; 6] = [
+        Fault::InvalidOpcode,
+        Fault::StackOverflow,
+        Fault::StackUnderflow,
+        Fault::PcOutOfRange,
+        Fault::MemoryOutOfRange,
+        Fault::MachineCode,
+    ];
+
+    /// Short name, as written to crash dumps
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fault::InvalidOpcode => "invalid-opcode",
+            Fault::StackOverflow => "stack-overflow",
+            Fault::StackUnderflow => "stack-underflow",
+            Fault::PcOutOfRange => "pc-out-of-range",
+            Fault::MemoryOutOfRange => "memory-out-of-range",
+            Fault::MachineCode => "machine-code",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Fault> {
+        Fault::ALL
+            .iter()
+            .copied()
+            .find(|fault| fault.name() == name)
+    }
+
+    /// What went wrong, for people
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Fault::InvalidOpcode => "Invalid opcode",
+            Fault::StackOverflow => "Stack overflow",
+            Fault::StackUnderflow => "Return with an empty stack",
+            Fault::PcOutOfRange => "PC out of memory",
+            Fault::MemoryOutOfRange => "Memory access past the end through I",
+            Fault::MachineCode => "Machine code call",
+        }
+    }
+}
+
+/// A key going down or up, stamped with the cycle count when it happened
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: usize,
+    pub pressed: bool,
+    pub cycle: u64,
+}
 
 pub struct Chip8 {
-    pub opcode: u16,        // current opcode
-    pub memory: [u8; 4096], // 4K memory
-    pub v: [u8; 16],        // V0-VE registers
-    pub i: u16,             // index register
-    pub pc: u16,            // program counter
-    pub gfx: [u8; 64 * 32], // graphics
-    pub stack: [u16; 16],   // opcode stack
-    pub sp: u16,            // stack pointer
-    pub key: [bool; 16],    // hex keypad to store key state
-    pub delay_timer: u8,    // counter register at 60Hz, counts down to 0
-    pub sound_timer: u8,    // counter plays sound at 0, counts down to 0
-    pub should_draw: bool,  // draw flag
+    pub opcode: u16,                    // current opcode
+    pub memory: [u8; 4096],             // 4K memory
+    pub v: [u8; 16],                    // V0-VE registers
+    pub i: u16,                         // index register
+    pub pc: u16,                        // program counter
+    pub gfx: Vec<u8>,                   // graphics, a byte per pixel
+    pub stack: [u16; 16],               // opcode stack
+    pub sp: u16,                        // stack pointer
+    pub key: [bool; 32],                // hex keypad state, CHIP-8X's second from 0x10
+    pub delay_timer: u8,                // counter register at 60Hz, counts down to 0
+    pub sound_timer: u8,                // counter plays sound at 0, counts down to 0
+    pub should_draw: bool,              // draw flag
+    pub key_events: VecDeque<KeyEvent>, // key changes not yet seen by the CPU
+    pub key_latch: [bool; 32],          // keys pressed since an instruction last checked them
+    pub key_wait: Option<usize>,        // key held down while `Fx0A` waits for its release
+    pub cycles: u64,                    // instructions executed
+    pub quirks: Quirks,                 // interpreter behavior differences
+    pub fault: Option<Fault>,           // why the machine stopped, with pc at the culprit
+    pub trap: Option<u16>,              // invalid opcode that trapped, skipped when run again
+    pub random: Option<u64>,            // seeded generator state for reproducible runs
+    pub platform: Platform,             // display size, memory layout and extra opcodes
+    pub colors: [u8; 8 * 32],           // CHIP-8X color of each 8x1 pixel zone
+    pub background: u8,                 // CHIP-8X background color, cycled by `02A0`
+    decoded: Vec<Option<Instruction>>,  // pre-decoded instruction cache, by address
+    observers: Vec<Box<dyn Observer>>,  // hooks watching the core run
 }
 
 impl Chip8 {
     pub fn initialize() -> Self {
+        Chip8::for_platform(Platform::Chip8)
+    }
+
+    pub fn for_platform(platform: Platform) -> Self {
+        let (width, height) = platform.display();
         let mut chip8 = Chip8 {
             opcode: 0,
             memory: [0; 4096],
             v: [0; 16],
             i: 0,
-            pc: 0x200,
-            gfx: [0; 64 * 32],
+            pc: platform.start(),
+            gfx: vec![0; width * height],
             delay_timer: 0,
             sound_timer: 0,
             stack: [0; 16],
             sp: 0,
-            key: [false; 16],
+            key: [false; 32],
             should_draw: false,
+            key_events: VecDeque::new(),
+            key_latch: [false; 32],
+            key_wait: None,
+            cycles: 0,
+            quirks: Quirks::default(),
+            fault: None,
+            trap: None,
+            random: None,
+            platform,
+            colors: [CHIP8X_FOREGROUND; 8 * 32],
+            background: 0,
+            decoded: vec![None; 4096],
+            observers: Vec::new(),
         };
 
         for (i, font_byte) in FONTS.iter().enumerate() {
@@ -46,84 +148,222 @@ impl Chip8 {
 
     pub fn load_rom(&mut self, rom_path: &str) {
         // read game data into memory
-        let rom = match File::open(rom_path) {
-            Ok(file) => file,
+        let rom = match fs::read(rom_path) {
+            Ok(rom) => rom,
             Err(e) => panic!("Error opening file: {:?}", e),
         };
+        self.load_bytes(&rom);
+    }
+
+    /// Copies a ROM already read into memory, where the platform loads it
+    pub fn load_bytes(&mut self, rom: &[u8]) {
+        let start = self.platform.load_address() as usize;
+        self.memory[start..start + rom.len()].copy_from_slice(rom);
+        self.invalidate_decoded();
+    }
+
+    /// Writes a byte to memory, dropping any cached instruction that covers it
+    pub fn write_memory(&mut self, address: usize, value: u8) {
+        self.memory[address] = value;
+        self.decoded[address] = None;
+        if address > 0 {
+            self.decoded[address - 1] = None;
+        }
+        self.notify(|observer| observer.memory_write(address as u16, value));
+    }
+
+    /// Reads a byte of memory as data, as opposed to fetching an opcode
+    pub fn read_memory(&mut self, address: usize) -> u8 {
+        let value = self.memory[address];
+        self.notify(|observer| observer.memory_read(address as u16, value));
+        value
+    }
+
+    /// Sets register Vx
+    pub fn set_v(&mut self, x: usize, value: u8) {
+        self.v[x] = value;
+        self.notify(|observer| observer.register_write(Register::V(x as u8), value.into()));
+    }
+
+    /// Sets the index register
+    pub fn set_i(&mut self, value: u16) {
+        self.i = value;
+        self.notify(|observer| observer.register_write(Register::I, value));
+    }
+
+    /// Installs an observer, called after any already installed
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    /// Removes and returns every observer, e.g. to move them to a fresh machine
+    pub fn take_observers(&mut self) -> Vec<Box<dyn Observer>> {
+        std::mem::take(&mut self.observers)
+    }
 
-        for (i, byte) in rom.bytes().enumerate() {
-            self.memory[0x200 + i] = byte.unwrap();
+    /// Calls `hook` on each observer; does nothing but check the length when there are none
+    #[inline]
+    pub fn notify<H: Fn(&mut dyn Observer)>(&mut self, hook: H) {
+        for observer in self.observers.iter_mut() {
+            hook(observer.as_mut());
         }
     }
 
+    /// Stops the machine; `pc` and `opcode` are left at the faulting instruction
+    pub fn fault(&mut self, fault: Fault) {
+        self.fault = Some(fault);
+        self.notify(|observer| observer.fault(fault));
+    }
+
+    /// Why the machine stopped by itself: a fault, or an invalid opcode that trapped
+    pub fn stop_cause(&self) -> Option<Fault> {
+        self.fault.or(self.trap.map(|_| Fault::InvalidOpcode))
+    }
+
+    /// Whether `length` bytes from `address` are in memory, faulting if not
+    pub fn check_memory(&mut self, address: usize, length: usize) -> bool {
+        let in_range = address + length <= self.memory.len();
+        if !in_range {
+            self.fault(Fault::MemoryOutOfRange);
+        }
+        in_range
+    }
+
+    /// Drops every cached instruction, needed after writing `memory` directly
+    pub fn invalidate_decoded(&mut self) {
+        for ins in self.decoded.iter_mut() {
+            *ins = None;
+        }
+    }
+
+    /// Runs one instruction; does nothing once the machine has faulted
     pub fn emulate_cycle(&mut self) {
+        if self.fault.is_some() {
+            return;
+        }
+        self.apply_key_events();
+
         // fetch opcode
         let counter: usize = self.pc.into();
+        if counter + 1 >= self.memory.len() {
+            self.fault(Fault::PcOutOfRange);
+            return;
+        }
         let high_byte = self.memory[counter];
         let low_byte = self.memory[counter + 1];
         self.opcode = (high_byte as u16) << 8 | low_byte as u16;
+        let (pc, opcode) = (self.pc, self.opcode);
+        self.notify(|observer| observer.instruction_fetched(pc, opcode));
 
-        // decode opcode
-        let opcode_nibbles = opcode_to_variables(&self.opcode).nibbles;
-
-        // match opcodes to instructions
-        match opcode_nibbles {
-            [0x0, 0x0, 0xE, 0x0] => cls(self),                // 0x00E0
-            [0x0, 0x0, 0xE, 0xE] => ret(self),                // 0x00EE
-            [0x0, _, _, _] => sys_addr(self),                 // 0x0nnn
-            [0x1, _, _, _] => jp_addr(self),                  // 0x1nnn
-            [0x2, _, _, _] => call_addr(self),                // 0x2nnn
-            [0x3, _, _, _] => se_vx_byte(self),               // 0x3xkk
-            [0x4, _, _, _] => sne_vx_byte(self),              // 0x4xkk
-            [0x5, _, _, 0x0] => se_vx_vy(self),               // 0x5xy0
-            [0x6, _, _, _] => ld_vx_byte(self),               // 0x6xkk
-            [0x7, _, _, _] => add_vx_byte(self),              // 0x7xkk
-            [0x8, _, _, 0] => ld_vx_vy(self),                 // 0x8xy0
-            [0x8, _, _, 1] => or_vx_vy(self),                 // 0x8xy1
-            [0x8, _, _, 2] => and_vx_vy(self),                // 0x8xy2
-            [0x8, _, _, 3] => xor_vx_vy(self),                // 0x8xy3
-            [0x8, _, _, 4] => add_vx_vy(self),                // 0x8xy4
-            [0x8, _, _, 5] => sub_vx_vy(self),                // 0x8xy5
-            [0x8, _, _, 6] => shr_vx_vy(self),                // 0x8xy6
-            [0x8, _, _, 7] => subn_vx_vy(self),               // 0x8xy7
-            [0x8, _, _, 0xE] => shl_vx_vy(self),              // 0x8xyE
-            [0x9, _, _, 0x0] => sne_vx_vy(self),              // 0x9xy0
-            [0xA, _, _, _] => ld_i_addr(self),                // 0xAnnn
-            [0xB, _, _, _] => jp_v0_addr(self),               // 0xBnnn
-            [0xC, _, _, _] => rnd_vx_byte(self, gen_rand_u8), // 0xCnnn
-            [0xD, _, _, _] => drw_vx_vy_nibble(self),         // 0xDxyn
-            [0xE, _, 0x9, 0xE] => skp_vx(self),               // 0xEx9E
-            [0xE, _, 0xA, 0x1] => sknp_vx(self),              // 0xExA1
-            [0xF, _, 0x0, 0x7] => ld_vx_dt(self),             // 0xFx07
-            [0xF, _, 0x0, 0xA] => ld_vx_k(self),              // 0xFx0A
-            [0xF, _, 0x1, 0x5] => ld_dt_vx(self),             // Fx15
-            [0xF, _, 0x1, 0x8] => ld_st_vx(self),             // Fx18
-            [0xF, _, 0x1, 0xE] => add_i_vx(self),             // Fx1E
-            [0xF, _, 0x2, 0x9] => ld_f_vx(self),              // Fx29
-            [0xF, _, 0x3, 0x3] => ld_b_vx(self),              // Fx33
-            [0xF, _, 0x5, 0x5] => ld_i_vx(self),              // Fx55
-            [0xF, _, 0x6, 0x5] => ld_vx_i(self),              // Fx65
-            _ => panic!("Not a valid opcode: {:#X?}", self.opcode),
-        }
-
-        // update timers
+        // decode opcode, reusing the cached instruction when there is one
+        let ins = match self.decoded[counter] {
+            Some(ins) => ins,
+            None => {
+                let ins = Instruction::decode(self.opcode);
+                self.decoded[counter] = Some(ins);
+                ins
+            }
+        };
+
+        execute(self, ins);
+        if self.fault.is_none() {
+            self.cycles += 1;
+        }
+    }
+
+    /// Counts the timers down, called once per 60Hz frame
+    pub fn update_timers(&mut self) {
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
+            if self.delay_timer == 0 {
+                self.notify(|observer| observer.timer_expired(Timer::Delay));
+            }
         }
 
-        // 'beep' if sound timer is greater than 0
+        // the frontend beeps while the sound timer is greater than 0
         if self.sound_timer > 0 {
-            println!("*Beep*");
             self.sound_timer -= 1;
+            if self.sound_timer == 0 {
+                self.notify(|observer| observer.timer_expired(Timer::Sound));
+            }
         }
     }
 
+    /// Display width in pixels, the length of each row of `gfx`
+    pub fn width(&self) -> usize {
+        self.platform.display().0
+    }
+
+    /// Resets the CPU and display, leaving memory as it is
+    pub fn reset(&mut self) {
+        self.opcode = 0;
+        self.v = [0; 16];
+        self.i = 0;
+        self.pc = self.platform.start();
+        self.gfx.fill(0);
+        self.stack = [0; 16];
+        self.sp = 0;
+        self.key = [false; 32];
+        self.key_events.clear();
+        self.key_latch = [false; 32];
+        self.key_wait = None;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.fault = None;
+        self.trap = None;
+        self.colors = [CHIP8X_FOREGROUND; 8 * 32];
+        self.background = 0;
+        self.should_draw = true;
+    }
+
+    /// A byte for `Cxkk`, from the seeded generator if there is one
+    pub fn random_byte(&mut self) -> u8 {
+        let state = match &mut self.random {
+            Some(state) => state,
+            None => return gen_rand_u8(),
+        };
+        // SplitMix64, which turns any seed into a good sequence
+        *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) as u8
+    }
+
+    /// Queues a key press or release for the next cycle
     pub fn set_key(&mut self, key: usize, value: bool) {
-        self.key[key] = value;
+        self.key_events.push_back(KeyEvent {
+            key,
+            pressed: value,
+            cycle: self.cycles,
+        });
+    }
+
+    /// Applies queued key events in order
+    ///
+    /// A press also sets the key's latch, so a tap that goes down and up between
+    /// two cycles is still seen once by `Ex9E`, `ExA1` or `Fx0A`.
+    fn apply_key_events(&mut self) {
+        while let Some(event) = self.key_events.pop_front() {
+            self.key[event.key] = event.pressed;
+            if event.pressed {
+                self.key_latch[event.key] = true;
+            }
+        }
+    }
+
+    /// Whether a key is down or was tapped since last checked, clearing the latch
+    pub fn take_key(&mut self, key: usize) -> bool {
+        let pressed = self.key[key] || self.key_latch[key];
+        self.key_latch[key] = false;
+        pressed
     }
 }
 
-const FONTS: [u8; 80] = [
+/// The color every CHIP-8X zone starts out, red in `palette::CHIP8X_COLORS`
+pub const CHIP8X_FOREGROUND: u8 = 1;
+
+pub const FONTS: [u8; 80] = [
     0b11110000, 0b10010000, 0b10010000, 0b10010000, 0b11110000, // "0"
     0b00100000, 0b01100000, 0b00100000, 0b00100000, 0b01110000, // "1"
     0b11110000, 0b00010000, 0b11110000, 0b10000000, 0b11110000, // "2"
@@ -145,8 +385,17 @@ const FONTS: [u8; 80] = [
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs::File;
     use std::io::Read;
 
+    fn load_program(chip8: &mut Chip8, program: &[u16]) {
+        for (i, word) in program.iter().enumerate() {
+            chip8.memory[0x200 + i * 2] = (word >> 8) as u8;
+            chip8.memory[0x200 + i * 2 + 1] = *word as u8;
+        }
+        chip8.invalidate_decoded();
+    }
+
     #[test]
     fn read_fonts_into_memory() {
         let chip8 = Chip8::initialize();
@@ -165,5 +414,211 @@ mod tests {
         };
         rom.read_to_end(&mut data).unwrap();
         assert_eq!(chip8.memory[0x200..(0x200 + data.len())].to_vec(), data);
+
+        let mut loaded = Chip8::initialize();
+        loaded.load_bytes(&data);
+        assert_eq!(chip8.memory.to_vec(), loaded.memory.to_vec());
+    }
+
+    #[test]
+    fn platforms_lay_out_memory_and_display() {
+        let mut chip8 = Chip8::for_platform(Platform::Eti660);
+        chip8.load_rom("test/test-rom.ch8");
+        let mut data: Vec<u8> = Vec::new();
+        File::open("test/test-rom.ch8")
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(chip8.memory[0x600..(0x600 + data.len())].to_vec(), data);
+        assert_eq!((0x600, 64 * 48), (chip8.pc, chip8.gfx.len()));
+
+        let mut chip8 = Chip8::for_platform(Platform::Hires);
+        assert_eq!((0x2C0, 64 * 64), (chip8.pc, chip8.gfx.len()));
+        chip8.pc = 0x400;
+        chip8.reset();
+        assert_eq!(0x2C0, chip8.pc, "should start past the patch");
+    }
+
+    #[test]
+    fn emulate_cycle_caches_decoded_instructions() {
+        let mut chip8 = Chip8::initialize();
+        load_program(&mut chip8, &[0x6005, 0x1200]);
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        assert_eq!(Some(Instruction::decode(0x6005)), chip8.decoded[0x200]);
+        assert_eq!(Some(Instruction::decode(0x1200)), chip8.decoded[0x202]);
+        assert_eq!(5, chip8.v[0]);
+    }
+
+    #[test]
+    fn write_memory_invalidates_decoded_instructions() {
+        let mut chip8 = Chip8::initialize();
+        // store v0 (0x70) over the low byte of the `6101` at 0x204, then run it
+        load_program(&mut chip8, &[0x6070, 0xA205, 0x6101, 0xF055, 0x1204]);
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        assert_eq!(Some(Instruction::decode(0x6101)), chip8.decoded[0x204]);
+        chip8.emulate_cycle();
+        assert_eq!(
+            None, chip8.decoded[0x204],
+            "should drop the overwritten instruction"
+        );
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        assert_eq!(0x70, chip8.v[1], "should run the self-modified instruction");
+    }
+
+    #[test]
+    fn faults_stop_the_machine() {
+        let mut chip8 = Chip8::initialize();
+        load_program(&mut chip8, &[0x6005, 0xE0FF, 0x6106]);
+        for _ in 0..3 {
+            chip8.emulate_cycle();
+        }
+        assert_eq!(Some(Fault::InvalidOpcode), chip8.fault);
+        assert_eq!(0x202, chip8.pc, "should stop at the faulting instruction");
+        assert_eq!(0xE0FF, chip8.opcode);
+        assert_eq!(1, chip8.cycles);
+        assert_eq!(0, chip8.v[1]);
+
+        chip8.reset();
+        assert_eq!(None, chip8.fault);
+    }
+
+    #[test]
+    fn stack_and_memory_faults() {
+        let mut chip8 = Chip8::initialize();
+        load_program(&mut chip8, &[0x2200]);
+        for _ in 0..16 {
+            chip8.emulate_cycle();
+        }
+        assert_eq!(Some(Fault::StackOverflow), chip8.fault);
+        assert_eq!(15, chip8.sp);
+
+        let mut chip8 = Chip8::initialize();
+        load_program(&mut chip8, &[0x00EE]);
+        chip8.emulate_cycle();
+        assert_eq!(Some(Fault::StackUnderflow), chip8.fault);
+
+        let mut chip8 = Chip8::initialize();
+        load_program(&mut chip8, &[0xAFFE, 0xF255]);
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        assert_eq!(Some(Fault::MemoryOutOfRange), chip8.fault);
+        assert_eq!(0, chip8.memory[0xFFE], "should not write part of it");
+
+        let mut chip8 = Chip8::initialize();
+        load_program(&mut chip8, &[0x1FFF]);
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        assert_eq!(Some(Fault::PcOutOfRange), chip8.fault);
+    }
+
+    #[test]
+    fn seeded_random_repeats() {
+        let run = |seed: u64| {
+            let mut chip8 = Chip8::initialize();
+            chip8.random = Some(seed);
+            load_program(&mut chip8, &[0xC0FF, 0xC1FF, 0xC2FF]);
+            for _ in 0..3 {
+                chip8.emulate_cycle();
+            }
+            chip8.v
+        };
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+
+    #[test]
+    fn update_timers_counts_down_to_zero() {
+        let mut chip8 = Chip8::initialize();
+        chip8.delay_timer = 2;
+        chip8.sound_timer = 1;
+        chip8.update_timers();
+        chip8.update_timers();
+        assert_eq!(0, chip8.delay_timer);
+        assert_eq!(0, chip8.sound_timer);
+    }
+
+    #[test]
+    fn reset_keeps_memory() {
+        let mut chip8 = Chip8::initialize();
+        load_program(&mut chip8, &[0x6005, 0x2300]);
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        chip8.gfx[0] = 1;
+        chip8.reset();
+
+        assert_eq!(0x200, chip8.pc);
+        assert_eq!(0, chip8.sp);
+        assert_eq!(0, chip8.v[0]);
+        assert_eq!(0, chip8.gfx[0]);
+        assert_eq!(0x60, chip8.memory[0x200], "should keep memory");
+    }
+
+    #[test]
+    fn set_key_queues_events_until_the_next_cycle() {
+        let mut chip8 = Chip8::initialize();
+        load_program(&mut chip8, &[0x1200]);
+        chip8.emulate_cycle();
+        chip8.set_key(0x5, true);
+
+        assert_eq!(
+            Some(&KeyEvent {
+                key: 0x5,
+                pressed: true,
+                cycle: 1
+            }),
+            chip8.key_events.front()
+        );
+        assert!(!chip8.key[0x5]);
+        chip8.emulate_cycle();
+        assert!(chip8.key[0x5]);
+        assert!(chip8.key_events.is_empty());
+    }
+
+    #[test]
+    fn short_press_between_cycles_is_seen_by_ex9e() {
+        let mut chip8 = Chip8::initialize();
+        // v0 = 5, skip the `6101` if key 5 is down
+        load_program(&mut chip8, &[0x6005, 0xE09E, 0x6101, 0x6202]);
+        chip8.emulate_cycle();
+        chip8.set_key(0x5, true);
+        chip8.set_key(0x5, false);
+        chip8.emulate_cycle();
+
+        assert_eq!(0x206, chip8.pc, "should skip for a tap");
+        assert!(!chip8.key[0x5]);
+        assert!(!chip8.key_latch[0x5], "should only see the tap once");
+    }
+
+    #[test]
+    fn fx0a_waits_for_press_and_release() {
+        let mut chip8 = Chip8::initialize();
+        load_program(&mut chip8, &[0xF30A, 0x1202]);
+        chip8.emulate_cycle();
+        assert_eq!(0x200, chip8.pc, "should wait for a key");
+
+        chip8.set_key(0xA, true);
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        assert_eq!(0x200, chip8.pc, "should wait for the key to be released");
+
+        chip8.set_key(0xA, false);
+        chip8.emulate_cycle();
+        assert_eq!(0x202, chip8.pc);
+        assert_eq!(0xA, chip8.v[3]);
+    }
+
+    #[test]
+    fn fx0a_fires_on_press_with_quirk() {
+        let mut chip8 = Chip8::initialize();
+        chip8.quirks.key_wait_on_press = true;
+        load_program(&mut chip8, &[0xF30A, 0x1202]);
+        chip8.set_key(0xA, true);
+        chip8.emulate_cycle();
+        assert_eq!(0x202, chip8.pc);
+        assert_eq!(0xA, chip8.v[3]);
     }
 }
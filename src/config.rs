@@ -0,0 +1,546 @@
+use crate::crash::CrashDump;
+use crate::database::{self, Database, Detection};
+use crate::debugger::{Condition, Watchpoint};
+use crate::keypad::Keymap;
+use crate::movie::Movie;
+use crate::palette::{Palette, Rgb};
+use crate::platform::Platform;
+use crate::quirks::{InvalidOpcode, MachineCode, Quirks};
+use crate::symbols::SymbolMap;
+use crate::timing::Timing;
+use std::fs;
+use std::time::Duration;
+
+/// Which frontend displays the emulator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrontendKind {
+    Sdl,
+    Terminal,
+    Headless,
+}
+
+/// Emulator settings, read from the command line and an optional config file
+///
+/// Every option can be given as `--key value` on the command line or as a
+/// `key = value` line in the file passed with `--config`. Options apply in the
+/// order they appear, so a `--foreground` after a `--palette` tweaks the preset,
+/// and labels in `--break` or `--watch` need `--symbols` to come first.
+///
+/// When running a ROM, unless `--detect 0` is given, it is looked up in the
+/// program database or its code scanned, and what that finds is applied
+/// first, for the options given to override.
+pub struct Config {
+    pub rom_path: String,
+    pub platform: Platform,
+    /// The VIP's CHIP-8 interpreter, for the `vip` platform
+    pub interpreter: Option<Vec<u8>>,
+    pub scale: u32,
+    pub palette: Palette,
+    pub phosphor_frames: u32,
+    pub frontend: FrontendKind,
+    pub truecolor: bool,
+    /// How long the terminal frontend holds a key pressed once, in case it is
+    /// still down
+    ///
+    /// Terminals send no key-ups, only repeats after a 300-600ms delay, so
+    /// this is longer than that delay to keep held keys from chattering. Once
+    /// a key repeats it is released as soon as the repeats stop.
+    pub key_timeout: Duration,
+    /// Extra host keys for the keypad, e.g. the arrows for a game's controls
+    pub keymap: Keymap,
+    pub instructions_per_frame: u32,
+    pub timing: Timing,
+    pub quirks: Quirks,
+    pub max_frames: Option<u64>,
+    pub breakpoints: Vec<u16>,
+    pub watchpoints: Vec<Watchpoint>,
+    pub conditions: Vec<Condition>,
+    pub gdb_port: Option<u16>,
+    pub symbols: SymbolMap,
+    pub trace: Option<String>,
+    pub crash_dump: Option<String>,
+    pub post_mortem: Option<CrashDump>,
+    pub sprite_sheet: Option<String>,
+    pub sprite_range: Option<(u16, u16)>,
+    pub screenshot: Option<String>,
+    pub gif: Option<String>,
+    pub seed: Option<u64>,
+    pub movie: Option<Movie>,
+    pub save_movie: Option<String>,
+    pub record: Option<String>,
+    pub detect: bool,
+    /// Programs to look the ROM up in before the bundled database
+    pub database: Option<Database>,
+    /// What detection found out about the ROM
+    pub detection: Option<Detection>,
+    /// The options set so far, which detection leaves alone
+    pub given: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            rom_path: String::new(),
+            platform: Platform::Chip8,
+            interpreter: None,
+            scale: 12,
+            palette: Palette::default(),
+            phosphor_frames: 0,
+            frontend: FrontendKind::Sdl,
+            truecolor: false,
+            key_timeout: Duration::from_millis(650),
+            keymap: Keymap::default(),
+            instructions_per_frame: 10,
+            timing: Timing::Ipf,
+            quirks: Quirks::default(),
+            max_frames: None,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            conditions: Vec::new(),
+            gdb_port: None,
+            symbols: SymbolMap::default(),
+            trace: None,
+            crash_dump: None,
+            post_mortem: None,
+            sprite_sheet: None,
+            sprite_range: None,
+            screenshot: None,
+            gif: None,
+            seed: None,
+            movie: None,
+            save_movie: None,
+            record: None,
+            detect: true,
+            database: None,
+            detection: None,
+            given: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Builds a config from command line arguments, not including the program name
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut config = Config::default();
+        config.apply_args(args)?;
+
+        if config.rom_path.is_empty() {
+            // a crash dump remembers which ROM it came from
+            match &config.post_mortem {
+                Some(dump) if !dump.rom.is_empty() => config.rom_path = dump.rom.clone(),
+                _ => return Err(String::from("No ROM given")),
+            }
+        }
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Builds a config for running the ROM, with what detection finds out
+    /// about it filling in the options not given
+    ///
+    /// A crash dump already knows its platform, so post-mortems skip detection.
+    pub fn for_run(args: &[String]) -> Result<Self, String> {
+        let mut config = Config::from_args(args)?;
+        if config.post_mortem.is_some() {
+            return Ok(config);
+        }
+        if let Some(detection) = config.detect() {
+            let given = config.given.clone();
+            for (key, value) in &detection.settings {
+                if !overridden(key, &given) {
+                    config
+                        .set(key, value)
+                        .map_err(|e| format!("{} ({})", e, detection.summary))?;
+                }
+            }
+            config.detection = Some(detection);
+            config.check()?;
+        }
+        Ok(config)
+    }
+
+    /// Refuses settings the emulator can't run with
+    fn check(&self) -> Result<(), String> {
+        if self.platform == Platform::Vip && self.interpreter.is_none() {
+            return Err(String::from(
+                "The vip platform needs --interpreter FILE, a dump of the VIP's CHIP-8 interpreter",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Identifies the ROM, if detection is on and the ROM can be read
+    fn detect(&mut self) -> Option<Detection> {
+        if !self.detect {
+            return None;
+        }
+        let rom = fs::read(&self.rom_path).ok()?;
+        let mut database = Database::bundled();
+        if let Some(extra) = self.database.take() {
+            database.extend(extra);
+        }
+        database::identify(&rom, &database)
+    }
+
+    /// Applies options and the ROM path from command line arguments
+    fn apply_args(&mut self, args: &[String]) -> Result<(), String> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(key) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("Missing value for --{}", key))?;
+                    if key == "config" {
+                        self.load_file(value)?;
+                    } else {
+                        self.set(key, value)?;
+                    }
+                }
+                None => self.rom_path = arg.clone(),
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies every `key = value` line of a config file, skipping blanks and `#` comments
+    pub fn load_file(&mut self, path: &str) -> Result<(), String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading config {}: {:?}", path, e))?;
+        self.load_str(&contents)
+    }
+
+    fn load_str(&mut self, contents: &str) -> Result<(), String> {
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = parts
+                .next()
+                .ok_or_else(|| format!("Expected `key = value`: {:?}", line))?
+                .trim();
+            self.set(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Sets a single option
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        self.given.push(key.to_string());
+        match key {
+            "scale" => self.scale = parse_number(key, value)?,
+            "palette" => {
+                self.palette =
+                    Palette::preset(value).ok_or_else(|| format!("Unknown palette: {:?}", value))?
+            }
+            "foreground" => self.palette.foreground = Rgb::from_hex(value)?,
+            "background" => self.palette.background = Rgb::from_hex(value)?,
+            "ipf" => self.instructions_per_frame = parse_number(key, value)?,
+            "platform" => {
+                self.platform = Platform::from_name(value)
+                    .ok_or_else(|| format!("Unknown platform: {:?}", value))?
+            }
+            "interpreter" => {
+                self.interpreter =
+                    Some(fs::read(value).map_err(|e| format!("Error reading {}: {:?}", value, e))?)
+            }
+            "timing" => {
+                self.timing = Timing::from_name(value)
+                    .ok_or_else(|| format!("Unknown timing: {:?}", value))?
+            }
+            "quirk-key-wait-on-press" => self.quirks.key_wait_on_press = parse_bool(key, value)?,
+            "invalid-opcode" => {
+                self.quirks.invalid_opcode = InvalidOpcode::from_name(value)
+                    .ok_or_else(|| format!("Unknown invalid opcode policy: {:?}", value))?
+            }
+            "machine-code" => {
+                self.quirks.machine_code = MachineCode::from_name(value)
+                    .ok_or_else(|| format!("Unknown machine code policy: {:?}", value))?
+            }
+            "frames" => {
+                self.max_frames = match parse_number(key, value)? {
+                    0 => None,
+                    frames => Some(frames),
+                }
+            }
+            "phosphor" => self.phosphor_frames = parse_number(key, value)?,
+            "frontend" => {
+                self.frontend = match value {
+                    "sdl" => FrontendKind::Sdl,
+                    "terminal" => FrontendKind::Terminal,
+                    "headless" => FrontendKind::Headless,
+                    _ => return Err(format!("Unknown frontend: {:?}", value)),
+                }
+            }
+            "truecolor" => self.truecolor = parse_bool(key, value)?,
+            "key-timeout" => self.key_timeout = Duration::from_millis(parse_number(key, value)?),
+            "keymap" => self.keymap = Keymap::parse(value)?,
+            "symbols" => self.symbols = SymbolMap::load(value)?,
+            "break" => self.breakpoints.push(self.symbols.resolve(value)?),
+            "watch" => self
+                .watchpoints
+                .push(Watchpoint::parse(value, &self.symbols)?),
+            "break-if" => self
+                .conditions
+                .push(Condition::parse(value, &self.symbols)?),
+            "trace" => self.trace = Some(value.to_string()),
+            "gdb" => self.gdb_port = Some(parse_number(key, value)?),
+            "crash-dump" => self.crash_dump = Some(value.to_string()),
+            "post-mortem" => self.post_mortem = Some(CrashDump::load(value)?),
+            "sprite-sheet" => self.sprite_sheet = Some(value.to_string()),
+            "sprite-range" => {
+                let (start, end) = value
+                    .split_once('-')
+                    .ok_or_else(|| format!("Expected START-END: {:?}", value))?;
+                let (start, end) = (self.symbols.resolve(start)?, self.symbols.resolve(end)?);
+                if end < start {
+                    return Err(format!("Range ends before it starts: {:?}", value));
+                }
+                self.sprite_range = Some((start, end));
+            }
+            "screenshot" => self.screenshot = Some(value.to_string()),
+            "gif" => self.gif = Some(value.to_string()),
+            "seed" => self.seed = Some(parse_number(key, value)?),
+            "movie" => self.movie = Some(Movie::load(value)?),
+            "save-movie" => self.save_movie = Some(value.to_string()),
+            "record" => self.record = Some(value.to_string()),
+            "detect" => self.detect = parse_bool(key, value)?,
+            "database" => self.database = Some(Database::load(value)?),
+            _ => return Err(format!("Unknown option: {:?}", key)),
+        }
+        Ok(())
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {:?}", key, value))
+}
+
+/// Whether options already `given` decide `key`
+///
+/// The palette and its two colors count as one option, so a detected preset
+/// never replaces colors the user picked, nor a detected color theirs.
+fn overridden(key: &str, given: &[String]) -> bool {
+    const COLORS: [&str; 3] = ["palette", "foreground", "background"];
+    given
+        .iter()
+        .any(|option| option == key || (COLORS.contains(&key) && COLORS.contains(&option.as_str())))
+}
+
+/// A switch, as `true`/`false`, `1`/`0`, `on`/`off` or `yes`/`no`
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "on" | "yes" => Ok(true),
+        "false" | "0" | "off" | "no" => Ok(false),
+        _ => Err(format!("Invalid value for {}: {:?}", key, value)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keypad::HostKey;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_args() {
+        let config = Config::from_args(&args(&[
+            "--palette",
+            "amber",
+            "--background",
+            "#102030",
+            "--phosphor",
+            "3",
+            "game.ch8",
+        ]))
+        .unwrap();
+
+        assert_eq!("game.ch8", config.rom_path);
+        assert_eq!(Rgb(0x10, 0x20, 0x30), config.palette.background);
+        assert_eq!(
+            Palette::preset("amber").unwrap().foreground,
+            config.palette.foreground
+        );
+        assert_eq!(3, config.phosphor_frames);
+    }
+
+    #[test]
+    fn test_from_args_errors() {
+        assert!(Config::from_args(&args(&[])).is_err());
+        assert!(Config::from_args(&args(&["game.ch8", "--scale"])).is_err());
+        assert!(Config::from_args(&args(&["--palette", "sepia", "game.ch8"])).is_err());
+        assert!(Config::from_args(&args(&["--volume", "3", "game.ch8"])).is_err());
+        assert!(Config::from_args(&args(&["--post-mortem", "/no/such.json"])).is_err());
+        assert!(Config::from_args(&args(&["--platform", "vip", "game.ch8"])).is_err());
+    }
+
+    #[test]
+    fn test_debugger_options() {
+        let config = Config::from_args(&args(&[
+            "--break",
+            "0x200",
+            "--break",
+            "0x2a4",
+            "--watch",
+            "0x300-0x30f:w",
+            "--break-if",
+            "v3 == 0x10 && i > 0x300",
+            "--sprite-range",
+            "0x300-0x3ff",
+            "--gif",
+            "bug.gif",
+            "game.ch8",
+        ]))
+        .unwrap();
+        assert_eq!(vec![0x200, 0x2A4], config.breakpoints);
+        assert_eq!(0x30F, config.watchpoints[0].end);
+        assert_eq!("v3 == 0x10 && i > 0x300", config.conditions[0].source);
+        assert_eq!(Some((0x300, 0x3FF)), config.sprite_range);
+        assert_eq!(Some("bug.gif"), config.gif.as_deref());
+        assert!(Config::from_args(&args(&["--break-if", "v3 ==", "game.ch8"])).is_err());
+        assert!(Config::from_args(&args(&["--sprite-range", "0x300", "game.ch8"])).is_err());
+    }
+
+    #[test]
+    fn test_symbols() {
+        let config = Config::from_args(&args(&[
+            "--symbols",
+            "test/test.sym",
+            "--break",
+            "draw_player",
+            "--watch",
+            "main-draw_player:r",
+            "--break-if",
+            "pc == draw_player",
+            "game.ch8",
+        ]))
+        .unwrap();
+        assert_eq!(vec![0x2A4], config.breakpoints);
+        assert_eq!(0x200, config.watchpoints[0].start);
+        assert!(Config::from_args(&args(&["--break", "draw_player", "game.ch8"])).is_err());
+    }
+
+    #[test]
+    fn test_load_str() {
+        let mut config = Config::default();
+        config
+            .load_str("# colors\npalette = green\n\nforeground = #ABCDEF\nscale=8\nipf = 15\n")
+            .unwrap();
+
+        assert_eq!(Rgb(0xAB, 0xCD, 0xEF), config.palette.foreground);
+        assert_eq!(8, config.scale);
+        assert_eq!(15, config.instructions_per_frame);
+
+        config
+            .load_str("frontend = terminal\ntruecolor = true\nkey-timeout = 100")
+            .unwrap();
+        assert_eq!(FrontendKind::Terminal, config.frontend);
+        assert!(config.truecolor);
+        assert_eq!(Duration::from_millis(100), config.key_timeout);
+
+        config
+            .load_str("quirk-key-wait-on-press = true\nframes = 600")
+            .unwrap();
+        assert_eq!(Some(600), config.max_frames);
+        assert!(config.quirks.key_wait_on_press);
+        config
+            .load_str("quirk-key-wait-on-press = 0\ntruecolor = off")
+            .unwrap();
+        assert!(!config.quirks.key_wait_on_press);
+        assert!(!config.truecolor);
+        assert!(config.load_str("truecolor = maybe").is_err());
+        config.load_str("keymap = up:5 space:6").unwrap();
+        assert_eq!(Some(0x6), config.keymap.key(HostKey::Char(' ')));
+        assert!(config.load_str("keymap = up").is_err());
+        assert!(config.load_str("palette green").is_err());
+
+        config
+            .load_str("seed = 42\nrecord = run.y4m\ntiming = vip")
+            .unwrap();
+        assert_eq!(Timing::Vip, config.timing);
+        assert_eq!(Some(42), config.seed);
+        assert_eq!(Some("run.y4m"), config.record.as_deref());
+        assert!(config.load_str("movie = /no/such.movie").is_err());
+
+        config
+            .load_str("invalid-opcode = trap\nmachine-code = vip")
+            .unwrap();
+        assert_eq!(InvalidOpcode::Trap, config.quirks.invalid_opcode);
+        assert_eq!(MachineCode::Vip, config.quirks.machine_code);
+        assert!(config.load_str("machine-code = jump").is_err());
+    }
+
+    #[test]
+    fn test_detection() {
+        let rom = fs::read("test/test-rom.ch8").unwrap();
+        let path = std::env::temp_dir().join(format!("chip8-programs-{}.txt", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        fs::write(
+            &path,
+            format!(
+                "[{}]\ntitle = Test\nplatform = hires\nipf = 15\nforeground = #FF0000\n",
+                crate::utils::to_hex(&database::sha1(&rom))
+            ),
+        )
+        .unwrap();
+
+        let config = Config::for_run(&args(&[
+            "--database",
+            &path,
+            "--ipf",
+            "20",
+            "test/test-rom.ch8",
+        ]))
+        .unwrap();
+        assert_eq!(Platform::Hires, config.platform);
+        assert_eq!(20, config.instructions_per_frame, "should keep the user's");
+        assert_eq!(Rgb(255, 0, 0), config.palette.foreground);
+        assert_eq!("Test", config.detection.unwrap().summary);
+
+        let config = Config::for_run(&args(&[
+            "--database",
+            &path,
+            "--palette",
+            "amber",
+            "test/test-rom.ch8",
+        ]))
+        .unwrap();
+        assert_eq!(Platform::Hires, config.platform);
+        assert_eq!(
+            Palette::preset("amber").unwrap(),
+            config.palette,
+            "should not mix the detected color into the user's palette"
+        );
+
+        // only running a ROM detects, not the other commands
+        let config = Config::from_args(&args(&["--database", &path, "test/test-rom.ch8"])).unwrap();
+        assert_eq!((Platform::Chip8, None), (config.platform, config.detection));
+
+        let config = Config::for_run(&args(&[
+            "--database",
+            &path,
+            "--detect",
+            "0",
+            "test/test-rom.ch8",
+        ]))
+        .unwrap();
+        assert_eq!(Platform::Chip8, config.platform);
+        fs::remove_file(&path).unwrap();
+
+        // every bundled entry should be options that apply
+        for program in Database::bundled().programs.values() {
+            let mut config = Config::default();
+            for (key, value) in &program.settings {
+                assert!(config.set(key, value).is_ok(), "{}: {}", program.title, key);
+            }
+        }
+    }
+}
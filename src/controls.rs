@@ -0,0 +1,163 @@
+use crate::timing::Timing;
+use std::time::Duration;
+
+/// Frames per second of the CHIP-8 display and timers
+pub const FRAME_RATE: u32 = 60;
+
+/// Speed multiplier while fast-forward is held
+const FAST_FORWARD_SPEED: f32 = 8.0;
+
+/// Speed multiplier in slow motion
+const SLOW_MOTION_SPEED: f32 = 0.25;
+
+/// Upper limit for instructions per frame
+const MAX_INSTRUCTIONS_PER_FRAME: u32 = 1000;
+
+/// Runtime emulation controls driven by hotkeys
+pub struct Controls {
+    pub paused: bool,
+    pub fast_forward: bool,
+    pub slow_motion: bool,
+    pub instructions_per_frame: u32,
+    pub timing: Timing,
+    frame_advance: bool,
+}
+
+impl Controls {
+    pub fn new(instructions_per_frame: u32) -> Self {
+        Controls {
+            paused: false,
+            fast_forward: false,
+            slow_motion: false,
+            instructions_per_frame,
+            timing: Timing::Ipf,
+            frame_advance: false,
+        }
+    }
+
+    /// Pauses or resumes
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.frame_advance = false;
+    }
+
+    /// Lets one frame run while paused
+    pub fn advance_frame(&mut self) {
+        if self.paused {
+            self.frame_advance = true;
+        }
+    }
+
+    /// Whether the next frame should run, consuming a pending frame advance
+    pub fn take_frame(&mut self) -> bool {
+        if !self.paused {
+            return true;
+        }
+        let advance = self.frame_advance;
+        self.frame_advance = false;
+        advance
+    }
+
+    /// Changes instructions per frame by `delta`, staying within 1 and a sane maximum
+    pub fn adjust_speed(&mut self, delta: i32) {
+        let ipf = self.instructions_per_frame as i32 + delta;
+        self.instructions_per_frame = ipf.max(1).min(MAX_INSTRUCTIONS_PER_FRAME as i32) as u32;
+    }
+
+    /// Emulation speed relative to real time
+    pub fn speed(&self) -> f32 {
+        if self.fast_forward {
+            FAST_FORWARD_SPEED
+        } else if self.slow_motion {
+            SLOW_MOTION_SPEED
+        } else {
+            1.0
+        }
+    }
+
+    /// Host time an emulated frame takes at the current speed
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs_f32(1.0 / (FRAME_RATE as f32 * self.speed()))
+    }
+
+    /// Instructions per second at full speed
+    pub fn nominal_ips(&self) -> f32 {
+        (self.instructions_per_frame * FRAME_RATE) as f32
+    }
+
+    /// Window title describing the current state
+    pub fn title(&self) -> String {
+        let mut title = match self.timing {
+            Timing::Ipf => format!("rfc chip8 - {} IPF", self.instructions_per_frame),
+            Timing::Vip => String::from("rfc chip8 - VIP timing"),
+        };
+        if self.paused {
+            title.push_str(" - paused");
+        }
+        if self.fast_forward {
+            title.push_str(" - fast forward");
+        } else if self.slow_motion {
+            title.push_str(" - slow motion");
+        }
+        title
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_advance() {
+        let mut controls = Controls::new(10);
+        assert!(controls.take_frame(), "should run frames while not paused");
+
+        controls.toggle_pause();
+        assert!(!controls.take_frame(), "should not run frames while paused");
+        controls.advance_frame();
+        assert!(controls.take_frame(), "should run one frame on advance");
+        assert!(!controls.take_frame(), "should only run one frame");
+    }
+
+    #[test]
+    fn test_adjust_speed() {
+        let mut controls = Controls::new(2);
+        controls.adjust_speed(-5);
+        assert_eq!(1, controls.instructions_per_frame);
+        controls.adjust_speed(5000);
+        assert_eq!(MAX_INSTRUCTIONS_PER_FRAME, controls.instructions_per_frame);
+    }
+
+    #[test]
+    fn test_frame_duration() {
+        let mut controls = Controls::new(10);
+        assert_eq!(
+            Duration::from_secs_f32(1.0 / 60.0),
+            controls.frame_duration()
+        );
+        controls.slow_motion = true;
+        assert_eq!(
+            Duration::from_secs_f32(1.0 / 15.0),
+            controls.frame_duration()
+        );
+        controls.fast_forward = true;
+        assert_eq!(
+            Duration::from_secs_f32(1.0 / 480.0),
+            controls.frame_duration()
+        );
+    }
+
+    #[test]
+    fn test_title() {
+        let mut controls = Controls::new(10);
+        assert_eq!("rfc chip8 - 10 IPF", controls.title());
+        controls.paused = true;
+        controls.slow_motion = true;
+        assert_eq!(
+            "rfc chip8 - 10 IPF - paused - slow motion",
+            controls.title()
+        );
+        controls.timing = Timing::Vip;
+        assert!(controls.title().starts_with("rfc chip8 - VIP timing"));
+    }
+}
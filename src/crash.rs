@@ -0,0 +1,371 @@
+use crate::chip8::{Chip8, Fault};
+use crate::disasm::mnemonic;
+use crate::observer::Observer;
+use crate::platform::Platform;
+use crate::symbols::SymbolMap;
+use crate::utils::{from_hex, to_hex};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+/// How many executed instructions a crash dump keeps
+pub const HISTORY_LENGTH: usize = 64;
+
+/// Observer remembering the last `HISTORY_LENGTH` instructions fetched, as (pc, opcode)
+///
+/// Clones share the same buffer, so one can be installed on the machine and
+/// another kept to read it back.
+#[derive(Clone, Default)]
+pub struct History(Rc<RefCell<VecDeque<(u16, u16)>>>);
+
+impl History {
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    /// A history that starts out with `entries`, e.g. from a crash dump
+    pub fn from_entries(entries: &[(u16, u16)]) -> Self {
+        let history = History::new();
+        for &(pc, opcode) in entries {
+            history.push(pc, opcode);
+        }
+        history
+    }
+
+    /// The remembered instructions, oldest first
+    pub fn entries(&self) -> Vec<(u16, u16)> {
+        self.0.borrow().iter().copied().collect()
+    }
+
+    fn push(&self, pc: u16, opcode: u16) {
+        let mut entries = self.0.borrow_mut();
+        if entries.len() == HISTORY_LENGTH {
+            entries.pop_front();
+        }
+        entries.push_back((pc, opcode));
+    }
+}
+
+impl Observer for History {
+    fn instruction_fetched(&mut self, pc: u16, opcode: u16) {
+        self.push(pc, opcode);
+    }
+}
+
+/// A machine that stopped on a fault, with the instructions that led up to it
+///
+/// Saved as JSON so it can be read by people as well as reopened with
+/// `--post-mortem` or a debugger. `machine` has the CPU, timers, memory and
+/// display; input state and observers are not kept.
+pub struct CrashDump {
+    pub rom: String,
+    pub machine: Chip8,
+    pub history: Vec<(u16, u16)>,
+}
+
+impl CrashDump {
+    /// Copies the state of `chip8`, which is usually stopped on a fault
+    pub fn capture(chip8: &Chip8, rom: &str, history: &History) -> Self {
+        CrashDump {
+            rom: rom.to_string(),
+            machine: copy_state(chip8),
+            history: history.entries(),
+        }
+    }
+
+    /// A fresh machine in the dumped state, still stopped on its fault
+    pub fn restore(&self) -> Chip8 {
+        copy_state(&self.machine)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading crash dump {}: {:?}", path, e))?;
+        let value = serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid crash dump {}: {}", path, e))?;
+        Self::from_json(&value)
+    }
+
+    pub fn save(&self, path: &str, symbols: &SymbolMap) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(&self.to_json(symbols)).unwrap_or_default();
+        fs::write(path, text + "\n").map_err(|e| format!("Error writing {}: {:?}", path, e))
+    }
+
+    /// The dump as JSON, with the report and disassembled history alongside the
+    /// raw state for anyone reading the file
+    pub fn to_json(&self, symbols: &SymbolMap) -> Value {
+        let chip8 = &self.machine;
+        let history: Vec<Value> = self
+            .history
+            .iter()
+            .map(|&(pc, opcode)| {
+                json!({
+                    "pc": pc,
+                    "opcode": opcode,
+                    "instruction": format!("{}  {}", symbols.describe(pc), mnemonic(opcode, symbols)),
+                })
+            })
+            .collect();
+        json!({
+            "rom": self.rom,
+            "platform": chip8.platform.name(),
+            "report": report(chip8, symbols),
+            "fault": chip8.fault.map(|fault| fault.name()),
+            "pc": chip8.pc,
+            "opcode": chip8.opcode,
+            "v": chip8.v.to_vec(),
+            "i": chip8.i,
+            "sp": chip8.sp,
+            "stack": chip8.stack.to_vec(),
+            "delay_timer": chip8.delay_timer,
+            "sound_timer": chip8.sound_timer,
+            "cycles": chip8.cycles,
+            "history": history,
+            "memory": to_hex(&chip8.memory),
+            "display": to_hex(&chip8.gfx),
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        // dumps from before there were platforms are all plain CHIP-8
+        let platform = match &value["platform"] {
+            Value::Null => Platform::Chip8,
+            platform => platform
+                .as_str()
+                .and_then(Platform::from_name)
+                .ok_or_else(|| format!("Unknown platform: {}", platform))?,
+        };
+        let mut machine = Chip8::for_platform(platform);
+        machine.fault = match &value["fault"] {
+            Value::Null => None,
+            fault => Some(
+                fault
+                    .as_str()
+                    .and_then(Fault::from_name)
+                    .ok_or_else(|| format!("Unknown fault: {}", fault))?,
+            ),
+        };
+        machine.pc = number(value, "pc")? as u16;
+        machine.opcode = number(value, "opcode")? as u16;
+        machine.i = number(value, "i")? as u16;
+        machine.sp = number(value, "sp")? as u16;
+        if machine.sp as usize >= machine.stack.len() {
+            return Err(format!("Stack pointer out of range: {}", machine.sp));
+        }
+        machine.delay_timer = number(value, "delay_timer")? as u8;
+        machine.sound_timer = number(value, "sound_timer")? as u8;
+        machine.cycles = number(value, "cycles")?;
+        for (i, register) in numbers(value, "v", machine.v.len())?
+            .into_iter()
+            .enumerate()
+        {
+            machine.v[i] = register as u8;
+        }
+        for (i, entry) in numbers(value, "stack", machine.stack.len())?
+            .into_iter()
+            .enumerate()
+        {
+            machine.stack[i] = entry as u16;
+        }
+        let memory = bytes(value, "memory", machine.memory.len())?;
+        machine.memory.copy_from_slice(&memory);
+        let display = bytes(value, "display", machine.gfx.len())?;
+        machine.gfx.copy_from_slice(&display);
+
+        let history = value["history"]
+            .as_array()
+            .ok_or_else(|| String::from("Missing history"))?
+            .iter()
+            .map(|entry| Ok((number(entry, "pc")? as u16, number(entry, "opcode")? as u16)))
+            .collect::<Result<_, String>>()?;
+        Ok(CrashDump {
+            rom: value["rom"].as_str().unwrap_or("").to_string(),
+            machine,
+            history,
+        })
+    }
+}
+
+fn copy_state(chip8: &Chip8) -> Chip8 {
+    let mut machine = Chip8::for_platform(chip8.platform);
+    machine.opcode = chip8.opcode;
+    machine.memory = chip8.memory;
+    machine.v = chip8.v;
+    machine.i = chip8.i;
+    machine.pc = chip8.pc;
+    machine.gfx = chip8.gfx.clone();
+    machine.colors = chip8.colors;
+    machine.background = chip8.background;
+    machine.stack = chip8.stack;
+    machine.sp = chip8.sp;
+    machine.delay_timer = chip8.delay_timer;
+    machine.sound_timer = chip8.sound_timer;
+    machine.cycles = chip8.cycles;
+    machine.quirks = chip8.quirks;
+    machine.fault = chip8.fault;
+    machine.should_draw = true;
+    machine
+}
+
+/// Where to write the dump for a ROM when no path is configured: next to it,
+/// as `game.crash.json` for `game.ch8`
+pub fn default_path(rom: &str) -> String {
+    Path::new(rom)
+        .with_extension("crash.json")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Lines describing a stopped machine: the fault, the instruction and the
+/// registers and stack, short enough for the OSD
+pub fn report(chip8: &Chip8, symbols: &SymbolMap) -> Vec<String> {
+    let fault = chip8.fault.map_or("Stopped", |fault| fault.describe());
+    let place = symbols.describe(chip8.pc);
+    let mut lines = vec![if place == format!("{:03X}", chip8.pc) {
+        format!("{} at {}", fault, place)
+    } else {
+        format!("{} at {:03X} ({})", fault, chip8.pc, place)
+    }];
+    lines.push(format!(
+        "{:04X}  {}",
+        chip8.opcode,
+        mnemonic(chip8.opcode, symbols)
+    ));
+    for (half, registers) in chip8.v.chunks(8).enumerate() {
+        let values: Vec<String> = registers.iter().map(|v| format!("{:02X}", v)).collect();
+        lines.push(format!(
+            "V{:X}-{:X} {}",
+            half * 8,
+            half * 8 + 7,
+            values.join(" ")
+        ));
+    }
+    lines.push(format!(
+        "I {:03X}  SP {:X}  DT {:02X}  ST {:02X}",
+        chip8.i, chip8.sp, chip8.delay_timer, chip8.sound_timer
+    ));
+    let stack: Vec<String> = chip8.stack[1..=(chip8.sp as usize).min(chip8.stack.len() - 1)]
+        .iter()
+        .map(|address| format!("{:03X}", address))
+        .collect();
+    lines.push(if stack.is_empty() {
+        String::from("Stack empty")
+    } else {
+        format!("Stack {}", stack.join(" "))
+    });
+    lines
+}
+
+fn number(value: &Value, key: &str) -> Result<u64, String> {
+    value[key]
+        .as_u64()
+        .ok_or_else(|| format!("Missing or invalid {}", key))
+}
+
+fn numbers(value: &Value, key: &str, length: usize) -> Result<Vec<u64>, String> {
+    let numbers: Option<Vec<u64>> = value[key]
+        .as_array()
+        .map(|array| array.iter().map(Value::as_u64).collect())
+        .unwrap_or(None);
+    match numbers {
+        Some(numbers) if numbers.len() == length => Ok(numbers),
+        _ => Err(format!("Expected {} numbers for {}", length, key)),
+    }
+}
+
+fn bytes(value: &Value, key: &str, length: usize) -> Result<Vec<u8>, String> {
+    let bytes = from_hex(value[key].as_str().unwrap_or(""))?;
+    if bytes.len() == length {
+        Ok(bytes)
+    } else {
+        Err(format!("Expected {} bytes for {}", length, key))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Runs `program` until it faults, keeping a history
+    fn crashed(program: &[u16]) -> (Chip8, History) {
+        let mut chip8 = Chip8::initialize();
+        for (i, word) in program.iter().enumerate() {
+            chip8.write_memory(0x200 + i * 2, (word >> 8) as u8);
+            chip8.write_memory(0x200 + i * 2 + 1, *word as u8);
+        }
+        let history = History::new();
+        chip8.add_observer(Box::new(history.clone()));
+        while chip8.fault.is_none() {
+            chip8.emulate_cycle();
+        }
+        (chip8, history)
+    }
+
+    #[test]
+    fn test_history_keeps_the_latest() {
+        let (_, history) = crashed(&[0x7001, 0x3080, 0x1200, 0xE0FF]);
+        let entries = history.entries();
+        assert_eq!(HISTORY_LENGTH, entries.len());
+        assert_eq!(
+            &[(0x202, 0x3080), (0x206, 0xE0FF)],
+            &entries[HISTORY_LENGTH - 2..]
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        // 200: call 204; 202: -; 204: v3 = 0x42; 206: invalid
+        let (chip8, _) = crashed(&[0x2204, 0x0000, 0x6342, 0xE0FF]);
+        let symbols = SymbolMap::parse("0x200 main\n0x204 blink").unwrap();
+        assert_eq!(
+            vec![
+                "Invalid opcode at 206 (blink+2)",
+                "E0FF  DW 0xE0FF",
+                "V0-7 00 00 00 42 00 00 00 00",
+                "V8-F 00 00 00 00 00 00 00 00",
+                "I 000  SP 1  DT 00  ST 00",
+                "Stack 200",
+            ],
+            report(&chip8, &symbols)
+        );
+        assert_eq!(
+            "Invalid opcode at 206",
+            report(&chip8, &SymbolMap::default())[0]
+        );
+    }
+
+    #[test]
+    fn test_dump_round_trip() {
+        let (chip8, history) = crashed(&[0x2204, 0x0000, 0x6342, 0xA000, 0xD015, 0xE0FF]);
+        let dump = CrashDump::capture(&chip8, "game.ch8", &history);
+        let json = dump.to_json(&SymbolMap::default());
+        assert_eq!(json!("invalid-opcode"), json["fault"]);
+        assert_eq!(json!("204  LD V3, 0x42"), json["history"][1]["instruction"]);
+
+        let loaded = CrashDump::from_json(&json).unwrap();
+        assert_eq!("game.ch8", loaded.rom);
+        assert_eq!(history.entries(), loaded.history);
+        let machine = loaded.machine;
+        assert_eq!(Some(Fault::InvalidOpcode), machine.fault);
+        assert_eq!((0x20A, 0xE0FF), (machine.pc, machine.opcode));
+        assert_eq!(chip8.v, machine.v);
+        assert_eq!(chip8.stack, machine.stack);
+        assert_eq!(chip8.cycles, machine.cycles);
+        assert_eq!(chip8.memory.to_vec(), machine.memory.to_vec());
+        assert_eq!(chip8.gfx, machine.gfx);
+        assert_eq!(Platform::Chip8, machine.platform);
+
+        assert!(CrashDump::from_json(&json!({ "pc": 512 })).is_err());
+        let mut corrupt = json.clone();
+        corrupt["sp"] = json!(16);
+        assert!(CrashDump::from_json(&corrupt).is_err());
+    }
+
+    #[test]
+    fn test_default_path() {
+        assert_eq!("roms/game.crash.json", default_path("roms/game.ch8"));
+    }
+}
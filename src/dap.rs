@@ -0,0 +1,766 @@
+use crate::chip8::Chip8;
+use crate::controls::FRAME_RATE;
+use crate::crash::{self, CrashDump, History};
+use crate::disasm::mnemonic;
+use crate::expr::Expr;
+use crate::quirks::Quirks;
+use crate::symbols::SymbolMap;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+/// The only thread a CHIP-8 has
+const THREAD_ID: i64 = 1;
+
+/// Variable references for the scopes of the stopped machine
+const REGISTERS: i64 = 1;
+const TIMERS: i64 = 2;
+const STACK: i64 = 3;
+const HISTORY: i64 = 4;
+
+/// How a resumed machine decides to stop again, besides breakpoints
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Run {
+    Stopped,
+    Continue,
+    /// Runs until `pc` comes back at or above the given stack depth
+    Over {
+        sp: u16,
+        pc: u16,
+    },
+    /// Runs until the stack is shallower than the given depth
+    Out {
+        sp: u16,
+    },
+}
+
+/// Debug Adapter Protocol session for one ROM
+///
+/// `handle` answers a request with responses and events, and `run_frame` runs
+/// the machine while it is resumed; `serve` connects both to stdio. Execution
+/// only ever stops between instructions. A fault stops it as an exception, and
+/// launching with a `crashDump` instead of a `program` opens a saved crash for
+/// post-mortem inspection.
+pub struct DapSession {
+    pub chip8: Option<Chip8>,
+    symbols: SymbolMap,
+    history: History,
+    quirks: Quirks,
+    instructions_per_frame: u32,
+    /// Breakpoint addresses, by the source path the editor set them for
+    breakpoints: HashMap<String, Vec<u16>>,
+    run: Run,
+    stop_on_entry: bool,
+    resumed_at: Option<u16>,
+    seq: i64,
+}
+
+impl DapSession {
+    pub fn new(quirks: Quirks, instructions_per_frame: u32) -> Self {
+        DapSession {
+            chip8: None,
+            symbols: SymbolMap::default(),
+            history: History::new(),
+            quirks,
+            instructions_per_frame,
+            breakpoints: HashMap::new(),
+            run: Run::Stopped,
+            stop_on_entry: false,
+            resumed_at: None,
+            seq: 0,
+        }
+    }
+
+    /// Whether the machine is running and `run_frame` should be called
+    pub fn is_running(&self) -> bool {
+        self.run != Run::Stopped && self.chip8.is_some()
+    }
+
+    /// Answers one request; the returned messages are sent in order
+    pub fn handle(&mut self, request: &Value) -> Vec<Value> {
+        let command = request["command"].as_str().unwrap_or("");
+        let args = &request["arguments"];
+        let mut after = Vec::new();
+        let result = match command {
+            "initialize" => {
+                after.push(self.event("initialized", json!({})));
+                Ok(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsEvaluateForHovers": true,
+                    "supportsExceptionInfoRequest": true,
+                }))
+            }
+            "launch" => self.launch(args),
+            "setBreakpoints" => Ok(self.set_breakpoints(args)),
+            "configurationDone" => {
+                if self
+                    .chip8
+                    .as_ref()
+                    .is_some_and(|chip8| chip8.fault.is_some())
+                {
+                    after.push(self.stopped("exception"));
+                } else if self.stop_on_entry {
+                    after.push(self.stopped("entry"));
+                } else {
+                    self.resume(Run::Continue);
+                }
+                Ok(json!({}))
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+            "stackTrace" => self.with_machine(|session, chip8| session.stack_trace(chip8)),
+            "scopes" => Ok(json!({ "scopes": [
+                { "name": "Registers", "variablesReference": REGISTERS, "expensive": false },
+                { "name": "Timers", "variablesReference": TIMERS, "expensive": false },
+                { "name": "Stack", "variablesReference": STACK, "expensive": false },
+                { "name": "History", "variablesReference": HISTORY, "expensive": false },
+            ]})),
+            "variables" => {
+                let reference = args["variablesReference"].as_i64().unwrap_or(0);
+                self.with_machine(|session, chip8| {
+                    let variables = match reference {
+                        HISTORY => session.history_variables(),
+                        _ => variables(chip8, reference),
+                    };
+                    Ok(json!({ "variables": variables }))
+                })
+            }
+            "exceptionInfo" => self.with_machine(|session, chip8| match chip8.stop_cause() {
+                Some(fault) => Ok(json!({
+                    "exceptionId": fault.name(),
+                    "description": fault.describe(),
+                    "breakMode": "always",
+                    "details": {
+                        "message": crash::report(chip8, &session.symbols).join("\n"),
+                    },
+                })),
+                None => Err(String::from("No exception")),
+            }),
+            "evaluate" => self.with_machine(|session, chip8| {
+                let expression = args["expression"].as_str().unwrap_or("");
+                let value = Expr::parse(expression, &session.symbols)?.eval(chip8);
+                Ok(json!({ "result": format!("{:#X}", value), "variablesReference": 0 }))
+            }),
+            "continue" => {
+                self.resume(Run::Continue);
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => match self.chip8.as_ref() {
+                Some(chip8) if chip8.memory.get(chip8.pc as usize).map(|b| b >> 4) == Some(0x2) => {
+                    // run the whole call, stopping once it returns to the next instruction
+                    let over = Run::Over {
+                        sp: chip8.sp,
+                        pc: chip8.pc + 2,
+                    };
+                    self.resume(over);
+                    Ok(json!({}))
+                }
+                _ => {
+                    after.extend(self.step());
+                    Ok(json!({}))
+                }
+            },
+            "stepIn" => {
+                after.extend(self.step());
+                Ok(json!({}))
+            }
+            "stepOut" => match self.chip8.as_ref().map(|chip8| chip8.sp) {
+                Some(sp) if sp > 0 => {
+                    self.resume(Run::Out { sp });
+                    Ok(json!({}))
+                }
+                // nothing to return from; behave like a single step
+                _ => {
+                    after.extend(self.step());
+                    Ok(json!({}))
+                }
+            },
+            "pause" => {
+                if self.is_running() {
+                    self.run = Run::Stopped;
+                    after.push(self.stopped("pause"));
+                }
+                Ok(json!({}))
+            }
+            "disconnect" => {
+                self.run = Run::Stopped;
+                self.chip8 = None;
+                Ok(json!({}))
+            }
+            _ => Err(format!("Unsupported request: {}", command)),
+        };
+
+        let mut messages = vec![self.response(request, result)];
+        messages.extend(after);
+        messages
+    }
+
+    /// Runs one frame of instructions, returning a `stopped` event if the machine stopped
+    pub fn run_frame(&mut self) -> Vec<Value> {
+        if !self.is_running() {
+            return Vec::new();
+        }
+        for _ in 0..self.instructions_per_frame {
+            if let Some(reason) = self.stop_reason() {
+                self.run = Run::Stopped;
+                return vec![self.stopped(reason)];
+            }
+            if let Some(chip8) = self.chip8.as_mut() {
+                chip8.emulate_cycle();
+            }
+            self.resumed_at = None;
+        }
+        if let Some(chip8) = self.chip8.as_mut() {
+            chip8.update_timers();
+        }
+        Vec::new()
+    }
+
+    fn stop_reason(&self) -> Option<&'static str> {
+        let chip8 = self.chip8.as_ref()?;
+        let trapped = chip8.trap == Some(chip8.pc) && self.resumed_at != Some(chip8.pc);
+        if chip8.fault.is_some() || trapped {
+            return Some("exception");
+        }
+        let finished = match self.run {
+            Run::Stopped => return None,
+            Run::Continue => false,
+            Run::Over { sp, pc } => chip8.sp <= sp && chip8.pc == pc,
+            Run::Out { sp } => chip8.sp < sp,
+        };
+        if finished {
+            return Some("step");
+        }
+        // the instruction execution resumed from runs even if it has a breakpoint
+        let breakpoint = self.resumed_at != Some(chip8.pc)
+            && self
+                .breakpoints
+                .values()
+                .any(|addresses| addresses.contains(&chip8.pc));
+        if breakpoint {
+            Some("breakpoint")
+        } else {
+            None
+        }
+    }
+
+    fn resume(&mut self, run: Run) {
+        self.run = run;
+        self.resumed_at = self.chip8.as_ref().map(|chip8| chip8.pc);
+    }
+
+    fn step(&mut self) -> Vec<Value> {
+        match self.chip8.as_mut() {
+            Some(chip8) => {
+                chip8.emulate_cycle();
+                let reason = if chip8.stop_cause().is_some() {
+                    "exception"
+                } else {
+                    "step"
+                };
+                self.run = Run::Stopped;
+                vec![self.stopped(reason)]
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        let dump = match args["crashDump"].as_str() {
+            Some(path) => Some(CrashDump::load(path)?),
+            None => None,
+        };
+        let program = match (args["program"].as_str(), &dump) {
+            (Some(program), _) => program,
+            (None, Some(dump)) => dump.rom.as_str(),
+            (None, None) => return Err(String::from("Missing program")),
+        };
+        if dump.is_none() && !Path::new(program).is_file() {
+            return Err(format!("No such ROM: {}", program));
+        }
+        self.symbols = match args["symbols"].as_str() {
+            Some(path) => SymbolMap::load(path)?,
+            None => SymbolMap::default(),
+        };
+        if let Some(ipf) = args["instructionsPerFrame"].as_u64() {
+            self.instructions_per_frame = ipf.max(1) as u32;
+        }
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+
+        let mut chip8 = match &dump {
+            Some(dump) => dump.restore(),
+            None => {
+                let mut chip8 = Chip8::initialize();
+                chip8.quirks = self.quirks;
+                chip8.load_rom(program);
+                chip8
+            }
+        };
+        self.history = match &dump {
+            Some(dump) => History::from_entries(&dump.history),
+            None => History::new(),
+        };
+        chip8.add_observer(Box::new(self.history.clone()));
+        self.chip8 = Some(chip8);
+        Ok(json!({}))
+    }
+
+    /// Replaces the breakpoints of one source file, mapping lines through the symbols
+    fn set_breakpoints(&mut self, args: &Value) -> Value {
+        let path = args["source"]["path"].as_str().unwrap_or("").to_string();
+        let mut addresses = Vec::new();
+        let mut breakpoints = Vec::new();
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = breakpoint["line"].as_u64().unwrap_or(0) as u32;
+            match self.symbols.address_for(&path, line) {
+                Some(address) => {
+                    addresses.push(address);
+                    breakpoints.push(json!({ "verified": true, "line": line }));
+                }
+                None => breakpoints.push(json!({
+                    "verified": false,
+                    "line": line,
+                    "message": "No code at this line",
+                })),
+            }
+        }
+        self.breakpoints.insert(path, addresses);
+        json!({ "breakpoints": breakpoints })
+    }
+
+    /// Frames from `pc` down through the return addresses on `Chip8::stack`
+    fn stack_trace(&self, chip8: &Chip8) -> Result<Value, String> {
+        let mut addresses = vec![chip8.pc];
+        addresses.extend(
+            (1..=chip8.sp as usize)
+                .rev()
+                .map(|level| chip8.stack[level]),
+        );
+        let frames: Vec<Value> = addresses
+            .iter()
+            .enumerate()
+            .map(|(id, &address)| {
+                let mut frame = json!({
+                    "id": id,
+                    "name": self.symbols.describe(address),
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": format!("{:#05X}", address),
+                });
+                if let Some(source) = self.symbols.line_for(address) {
+                    frame["source"] = json!({ "path": source.file });
+                    frame["line"] = json!(source.line);
+                    frame["column"] = json!(1);
+                }
+                frame
+            })
+            .collect();
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    /// The last instructions run, most recent first
+    fn history_variables(&self) -> Vec<Value> {
+        self.history
+            .entries()
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(age, &(pc, opcode))| {
+                json!({
+                    "name": format!("-{}", age),
+                    "value": format!("{}  {}", self.symbols.describe(pc), mnemonic(opcode, &self.symbols)),
+                    "variablesReference": 0,
+                })
+            })
+            .collect()
+    }
+
+    fn with_machine<F>(&self, f: F) -> Result<Value, String>
+    where
+        F: FnOnce(&Self, &Chip8) -> Result<Value, String>,
+    {
+        match self.chip8.as_ref() {
+            Some(chip8) => f(self, chip8),
+            None => Err(String::from("No ROM launched")),
+        }
+    }
+
+    fn next_seq(&mut self) -> i64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    fn response(&mut self, request: &Value, result: Result<Value, String>) -> Value {
+        let mut response = json!({
+            "seq": self.next_seq(),
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        response
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Value {
+        json!({ "seq": self.next_seq(), "type": "event", "event": event, "body": body })
+    }
+
+    fn stopped(&mut self, reason: &str) -> Value {
+        let mut body =
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+        if let Some(fault) = self.chip8.as_ref().and_then(Chip8::stop_cause) {
+            body["description"] = json!(fault.describe());
+            body["text"] = json!(fault.name());
+        }
+        self.event("stopped", body)
+    }
+}
+
+fn variables(chip8: &Chip8, reference: i64) -> Vec<Value> {
+    let variable = |name: String, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+    match reference {
+        REGISTERS => {
+            let mut variables: Vec<Value> = chip8
+                .v
+                .iter()
+                .enumerate()
+                .map(|(x, v)| variable(format!("V{:X}", x), format!("{:#04X}", v)))
+                .collect();
+            variables.push(variable(String::from("I"), format!("{:#05X}", chip8.i)));
+            variables.push(variable(String::from("PC"), format!("{:#05X}", chip8.pc)));
+            variables.push(variable(String::from("SP"), chip8.sp.to_string()));
+            variables
+        }
+        TIMERS => vec![
+            variable(String::from("DT"), chip8.delay_timer.to_string()),
+            variable(String::from("ST"), chip8.sound_timer.to_string()),
+        ],
+        STACK => (1..=chip8.sp as usize)
+            .map(|level| {
+                variable(
+                    format!("[{}]", level),
+                    format!("{:#05X}", chip8.stack[level]),
+                )
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Reads one `Content-Length` framed message, or None at the end of input
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// Reads requests on a thread, so a running machine can still be paused
+fn spawn_reader<R: Read + Send + 'static>(input: R) -> Receiver<Value> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut input = BufReader::new(input);
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Serves a debug session until the editor disconnects
+///
+/// While running, a frame of instructions runs every 1/60th of a second
+/// between checks for new requests.
+pub fn serve<R, W>(
+    input: R,
+    mut output: W,
+    quirks: Quirks,
+    instructions_per_frame: u32,
+) -> io::Result<()>
+where
+    R: Read + Send + 'static,
+    W: Write,
+{
+    let requests = spawn_reader(input);
+    let mut session = DapSession::new(quirks, instructions_per_frame);
+    let frame = Duration::from_secs(1) / FRAME_RATE;
+    loop {
+        let request = if session.is_running() {
+            match requests.try_recv() {
+                Ok(request) => Some(request),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        } else {
+            match requests.recv() {
+                Ok(request) => Some(request),
+                Err(_) => return Ok(()),
+            }
+        };
+
+        if let Some(request) = request {
+            for message in session.handle(&request) {
+                write_message(&mut output, &message)?;
+            }
+            if request["command"] == "disconnect" {
+                return Ok(());
+            }
+        } else {
+            for message in session.run_frame() {
+                write_message(&mut output, &message)?;
+            }
+            thread::sleep(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+
+    fn request(seq: i64, command: &str, arguments: Value) -> Value {
+        json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments })
+    }
+
+    /// Writes a ROM and line map to a temporary directory and launches them
+    fn launch(name: &str, program: &[u16], symbols: &str) -> DapSession {
+        let dir = std::env::temp_dir().join(format!("chip8-dap-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let rom: Vec<u8> = program
+            .iter()
+            .flat_map(|word| word.to_be_bytes().to_vec())
+            .collect();
+        fs::write(dir.join("game.ch8"), rom).unwrap();
+        fs::write(dir.join("game.sym"), symbols).unwrap();
+
+        let mut session = DapSession::new(Quirks::default(), 10);
+        session.handle(&request(1, "initialize", json!({})));
+        let messages = session.handle(&request(
+            2,
+            "launch",
+            json!({
+                "program": dir.join("game.ch8").to_str().unwrap(),
+                "symbols": dir.join("game.sym").to_str().unwrap(),
+                "stopOnEntry": true,
+            }),
+        ));
+        assert_eq!(json!(true), messages[0]["success"], "{:?}", messages);
+        session
+    }
+
+    /// Runs frames until the machine stops, returning the stop reason
+    fn run_until_stopped(session: &mut DapSession) -> String {
+        for _ in 0..100 {
+            if let Some(event) = session.run_frame().pop() {
+                return event["body"]["reason"].as_str().unwrap().to_string();
+            }
+        }
+        panic!("should have stopped");
+    }
+
+    fn pc(session: &DapSession) -> u16 {
+        session.chip8.as_ref().unwrap().pc
+    }
+
+    const SYMBOLS: &str =
+        "0x200 main game.8o:1\n0x202 game.8o:2\n0x204 game.8o:3\n0x206 blink game.8o:5\n0x208 game.8o:6\n";
+
+    // 200: V0 = 5; 202: call 206; 204: jump 204; 206: V1 = 1; 208: return
+    const PROGRAM: [u16; 5] = [0x6005, 0x2206, 0x1204, 0x6101, 0x00EE];
+
+    #[test]
+    fn test_breakpoints_by_line() {
+        let mut session = launch("breakpoints", &PROGRAM, SYMBOLS);
+        let messages = session.handle(&request(
+            3,
+            "setBreakpoints",
+            json!({ "source": { "path": "/work/game.8o" }, "breakpoints": [{ "line": 5 }, { "line": 4 }] }),
+        ));
+        let breakpoints = &messages[0]["body"]["breakpoints"];
+        assert_eq!(json!(true), breakpoints[0]["verified"]);
+        assert_eq!(
+            json!(false),
+            breakpoints[1]["verified"],
+            "line 4 has no code"
+        );
+
+        let messages = session.handle(&request(4, "configurationDone", json!({})));
+        assert_eq!(json!("entry"), messages[1]["body"]["reason"]);
+        session.handle(&request(5, "continue", json!({ "threadId": 1 })));
+        assert_eq!("breakpoint", run_until_stopped(&mut session));
+        assert_eq!(0x206, pc(&session));
+
+        let messages = session.handle(&request(6, "stackTrace", json!({ "threadId": 1 })));
+        let frames = &messages[0]["body"]["stackFrames"];
+        assert_eq!(json!(5), frames[0]["line"]);
+        assert_eq!(json!("game.8o"), frames[0]["source"]["path"]);
+        assert_eq!(json!(2), frames[1]["line"], "should show the call site");
+        assert_eq!(json!("blink"), frames[0]["name"]);
+        assert_eq!(json!("main+2"), frames[1]["name"]);
+    }
+
+    #[test]
+    fn test_stepping() {
+        let mut session = launch("stepping", &PROGRAM, SYMBOLS);
+        session.handle(&request(3, "configurationDone", json!({})));
+
+        let messages = session.handle(&request(4, "next", json!({ "threadId": 1 })));
+        assert_eq!(json!("step"), messages[1]["body"]["reason"]);
+        assert_eq!(0x202, pc(&session));
+
+        // step over the call
+        session.handle(&request(5, "next", json!({ "threadId": 1 })));
+        assert_eq!("step", run_until_stopped(&mut session));
+        assert_eq!(0x204, pc(&session));
+        assert_eq!(1, session.chip8.as_ref().unwrap().v[1]);
+    }
+
+    #[test]
+    fn test_step_in_and_out() {
+        let mut session = launch("step-out", &PROGRAM, SYMBOLS);
+        session.handle(&request(3, "configurationDone", json!({})));
+        session.handle(&request(4, "stepIn", json!({ "threadId": 1 })));
+        session.handle(&request(5, "stepIn", json!({ "threadId": 1 })));
+        assert_eq!(0x206, pc(&session));
+
+        session.handle(&request(6, "stepOut", json!({ "threadId": 1 })));
+        assert_eq!("step", run_until_stopped(&mut session));
+        assert_eq!(0x204, pc(&session));
+    }
+
+    #[test]
+    fn test_variables_and_evaluate() {
+        let mut session = launch("variables", &PROGRAM, SYMBOLS);
+        session.handle(&request(3, "configurationDone", json!({})));
+        session.handle(&request(4, "stepIn", json!({ "threadId": 1 })));
+        session.handle(&request(5, "stepIn", json!({ "threadId": 1 })));
+
+        let messages = session.handle(&request(
+            6,
+            "variables",
+            json!({ "variablesReference": REGISTERS }),
+        ));
+        let variables = &messages[0]["body"]["variables"];
+        assert_eq!(json!("V0"), variables[0]["name"]);
+        assert_eq!(json!("0x05"), variables[0]["value"]);
+        assert_eq!(json!("0x206"), variables[17]["value"]);
+
+        let messages = session.handle(&request(
+            7,
+            "variables",
+            json!({ "variablesReference": STACK }),
+        ));
+        assert_eq!(json!("0x202"), messages[0]["body"]["variables"][0]["value"]);
+
+        let messages = session.handle(&request(8, "evaluate", json!({ "expression": "v0 + 1" })));
+        assert_eq!(json!("0x6"), messages[0]["body"]["result"]);
+        let messages = session.handle(&request(9, "evaluate", json!({ "expression": "v0 +" })));
+        assert_eq!(json!(false), messages[0]["success"]);
+    }
+
+    #[test]
+    fn test_pause_and_errors() {
+        let mut session = DapSession::new(Quirks::default(), 10);
+        let messages = session.handle(&request(1, "launch", json!({ "program": "/no/such.ch8" })));
+        assert_eq!(json!(false), messages[0]["success"]);
+        let messages = session.handle(&request(2, "stackTrace", json!({})));
+        assert_eq!(json!(false), messages[0]["success"]);
+
+        let mut session = launch("pause", &[0x1200], "");
+        session.handle(&request(3, "configurationDone", json!({})));
+        session.handle(&request(4, "continue", json!({})));
+        assert!(session.run_frame().is_empty());
+        let messages = session.handle(&request(5, "pause", json!({})));
+        assert_eq!(json!("pause"), messages[1]["body"]["reason"]);
+        assert!(!session.is_running());
+    }
+
+    #[test]
+    fn test_faults_and_crash_dumps() {
+        // 200: V0 = 5; 202: call 206; 204: -; 206: invalid
+        let mut session = launch("fault", &[0x6005, 0x2206, 0x0000, 0xE0FF], SYMBOLS);
+        session.handle(&request(3, "configurationDone", json!({})));
+        session.handle(&request(4, "continue", json!({})));
+        assert_eq!("exception", run_until_stopped(&mut session));
+        let messages = session.handle(&request(5, "exceptionInfo", json!({ "threadId": 1 })));
+        assert_eq!(json!("invalid-opcode"), messages[0]["body"]["exceptionId"]);
+        let messages = session.handle(&request(
+            6,
+            "variables",
+            json!({ "variablesReference": HISTORY }),
+        ));
+        let history = &messages[0]["body"]["variables"];
+        assert_eq!(json!("blink  DW 0xE0FF"), history[0]["value"]);
+        assert_eq!(json!("main  LD V0, 0x05"), history[2]["value"]);
+
+        let path = std::env::temp_dir().join(format!("chip8-dap-{}.json", std::process::id()));
+        let chip8 = session.chip8.as_ref().unwrap();
+        CrashDump::capture(chip8, "game.ch8", &session.history)
+            .save(path.to_str().unwrap(), &SymbolMap::default())
+            .unwrap();
+        let mut session = DapSession::new(Quirks::default(), 10);
+        let messages = session.handle(&request(
+            1,
+            "launch",
+            json!({ "crashDump": path.to_str().unwrap() }),
+        ));
+        assert_eq!(json!(true), messages[0]["success"], "{:?}", messages);
+        let messages = session.handle(&request(2, "configurationDone", json!({})));
+        assert_eq!(json!("exception"), messages[1]["body"]["reason"]);
+        assert_eq!(0x206, pc(&session));
+        assert_eq!(3, session.history.entries().len());
+        std::fs::remove_file(path).unwrap();
+
+        // 200: V0 = FF; 202: jump past the end of memory
+        let mut session = launch("jump-out", &[0x60FF, 0xBFFF], SYMBOLS);
+        session.handle(&request(3, "stepIn", json!({ "threadId": 1 })));
+        session.handle(&request(4, "stepIn", json!({ "threadId": 1 })));
+        assert_eq!(0x10FE, pc(&session));
+        let messages = session.handle(&request(5, "next", json!({ "threadId": 1 })));
+        assert_eq!(json!("exception"), messages[1]["body"]["reason"]);
+    }
+
+    #[test]
+    fn test_framing() {
+        let mut output = Vec::new();
+        write_message(&mut output, &json!({ "seq": 1 })).unwrap();
+        assert_eq!(b"Content-Length: 9\r\n\r\n{\"seq\":1}".to_vec(), output);
+
+        let mut input = Cursor::new(output);
+        assert_eq!(Some(json!({ "seq": 1 })), read_message(&mut input).unwrap());
+        assert_eq!(None, read_message(&mut input).unwrap());
+    }
+}
@@ -0,0 +1,308 @@
+use crate::analyze::{platform_opcode, Extension, Traversal};
+use crate::platform::Platform;
+use crate::utils::to_hex;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+
+/// The database that comes with the emulator
+const BUNDLED: &str = include_str!("programs.txt");
+
+/// What the database knows about a ROM
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Program {
+    pub title: String,
+    pub author: Option<String>,
+    /// Config options the ROM wants, e.g. its platform, quirks, speed, keymap and colors
+    pub settings: Vec<(String, String)>,
+}
+
+/// Known ROMs by the SHA-1 of their contents
+///
+/// The file has a `[sha1]` line for each ROM, followed by `key = value`
+/// lines: `title` and `author` describe it, and any other key is a config
+/// option. Blank lines and `#` comments are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct Database {
+    /// By lowercase hex SHA-1
+    pub programs: HashMap<String, Program>,
+}
+
+impl Database {
+    pub fn bundled() -> Self {
+        Database::parse(BUNDLED).expect("bundled database should parse")
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading database {}: {:?}", path, e))?;
+        Database::parse(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut database = Database::default();
+        let mut current: Option<String> = None;
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(hash) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let hash = hash.trim().to_ascii_lowercase();
+                if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(format!("line {}: expected a SHA-1: {:?}", number + 1, line));
+                }
+                database.programs.insert(hash.clone(), Program::default());
+                current = Some(hash);
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim().to_string()))
+                .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+            let program = current
+                .as_ref()
+                .and_then(|hash| database.programs.get_mut(hash))
+                .ok_or_else(|| format!("line {}: expected a `[sha1]` line first", number + 1))?;
+            match key {
+                "title" => program.title = value,
+                "author" => program.author = Some(value),
+                _ => program.settings.push((key.to_string(), value)),
+            }
+        }
+        Ok(database)
+    }
+
+    /// Adds another database's programs, replacing any for the same ROM
+    pub fn extend(&mut self, other: Database) {
+        self.programs.extend(other.programs);
+    }
+
+    pub fn get(&self, rom: &[u8]) -> Option<&Program> {
+        self.programs.get(&to_hex(&sha1(rom)))
+    }
+}
+
+/// What detection decided about a ROM
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Detection {
+    /// Config options to run it with, which the user's own options override
+    pub settings: Vec<(String, String)>,
+    /// One line for the OSD saying what was found
+    pub summary: String,
+}
+
+/// Identifies a ROM by its hash, or failing that guesses from what it runs
+pub fn identify(rom: &[u8], database: &Database) -> Option<Detection> {
+    let program = match database.get(rom) {
+        Some(program) => program,
+        None => return guess(rom),
+    };
+    let summary = match &program.author {
+        Some(author) => format!("{} by {}", program.title, author),
+        None => program.title.clone(),
+    };
+    Some(Detection {
+        settings: program.settings.clone(),
+        summary,
+    })
+}
+
+/// Settings for an unknown ROM from the opcodes its reachable code uses
+///
+/// A ROM starting with a jump to `0x260` carries the HIRES patch. SUPER-CHIP
+/// and XO-CHIP opcodes are beyond the core, so they are logged and skipped
+/// rather than halting, and machine code calls are run as the VIP would.
+fn guess(rom: &[u8]) -> Option<Detection> {
+    if rom.starts_with(&[0x12, 0x60]) {
+        return Some(Detection {
+            settings: vec![(String::from("platform"), String::from("hires"))],
+            summary: String::from("Looks like a HIRES program"),
+        });
+    }
+
+    let traversal = Traversal::run(rom, Platform::Chip8);
+    let extensions: BTreeSet<Extension> = traversal
+        .code
+        .values()
+        .filter_map(|&word| platform_opcode(word))
+        .map(|(extension, _)| extension)
+        .collect();
+    let mut settings = Vec::new();
+    let mut findings = Vec::new();
+    for extension in extensions {
+        match extension {
+            Extension::Schip | Extension::XoChip => {
+                let name = match extension {
+                    Extension::Schip => "SUPER-CHIP",
+                    _ => "XO-CHIP",
+                };
+                let option = (String::from("invalid-opcode"), String::from("log"));
+                if !settings.contains(&option) {
+                    settings.push(option);
+                }
+                findings.push(format!("uses {} opcodes, which are skipped", name));
+            }
+            Extension::MachineCode => {
+                settings.push((String::from("machine-code"), String::from("vip")));
+                findings.push(String::from("calls machine code, run as on the VIP"));
+            }
+        }
+    }
+    if findings.is_empty() {
+        return None;
+    }
+    Some(Detection {
+        settings,
+        summary: format!("Unknown ROM: {}", findings.join(", ")),
+    })
+}
+
+/// The SHA-1 digest of `data`, which the database identifies ROMs by
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    // a 1 bit, zeros up to 8 bytes short of a block, then the length in bits
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (t, word) in block.chunks(4).enumerate() {
+            w[t] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for t in 16..80 {
+            w[t] = (w[t - 3] ^ w[t - 8] ^ w[t - 14] ^ w[t - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (t, &word) in w.iter().enumerate() {
+            let (f, k) = match t {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, state) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&state.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rom(program: &[u16]) -> Vec<u8> {
+        program.iter().flat_map(|word| word.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn test_sha1() {
+        assert_eq!(
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+            to_hex(&sha1(b""))
+        );
+        assert_eq!(
+            "a9993e364706816aba3e25717850c26c9cd0d89d",
+            to_hex(&sha1(b"abc"))
+        );
+        // two blocks once padded
+        assert_eq!(
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1",
+            to_hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_lookup() {
+        let game = rom(&[0x6005, 0x1202]);
+        let database = Database::parse(&format!(
+            "# a test entry\n[{}]\ntitle = Blink\nauthor = Someone\nkeymap = space:5\nipf = 15\n",
+            to_hex(&sha1(&game)).to_uppercase()
+        ))
+        .unwrap();
+        let detection = identify(&game, &database).unwrap();
+        assert_eq!("Blink by Someone", detection.summary);
+        assert_eq!(
+            vec![
+                (String::from("keymap"), String::from("space:5")),
+                (String::from("ipf"), String::from("15"))
+            ],
+            detection.settings
+        );
+        assert_eq!(None, identify(&rom(&[0x6005, 0x1204]), &database));
+
+        assert!(
+            Database::parse("ipf = 15").is_err(),
+            "should need a hash first"
+        );
+        assert!(Database::parse("[1234]").is_err());
+        assert!(Database::bundled()
+            .programs
+            .values()
+            .all(|p| !p.title.is_empty()));
+    }
+
+    #[test]
+    fn test_bundled() {
+        // David Winter's Maze, all 34 bytes of it
+        let maze = rom(&[
+            0xA21E, 0xC201, 0x3201, 0xA21A, 0xD014, 0x7004, 0x3040, 0x1200, 0x6000, 0x7104, 0x3120,
+            0x1200, 0x1218, 0x8040, 0x2010, 0x2040, 0x8010,
+        ]);
+        let detection = identify(&maze, &Database::bundled()).unwrap();
+        assert_eq!("Maze by David Winter", detection.summary);
+        assert_eq!(
+            vec![(String::from("platform"), String::from("chip8"))],
+            detection.settings
+        );
+    }
+
+    #[test]
+    fn test_guess() {
+        let database = Database::default();
+        let hires = identify(&rom(&[0x1260, 0x0000]), &database).unwrap();
+        assert_eq!(
+            vec![(String::from("platform"), String::from("hires"))],
+            hires.settings
+        );
+
+        // 00FF is reached, the F075 after the jump is not
+        let schip = identify(&rom(&[0x00FF, 0x0300, 0x1204, 0xF075]), &database).unwrap();
+        assert_eq!(
+            vec![
+                (String::from("invalid-opcode"), String::from("log")),
+                (String::from("machine-code"), String::from("vip"))
+            ],
+            schip.settings
+        );
+        assert_eq!(
+            "Unknown ROM: uses SUPER-CHIP opcodes, which are skipped, calls machine code, run as on the VIP",
+            schip.summary
+        );
+
+        assert_eq!(None, identify(&rom(&[0x6005, 0x1202, 0xF075]), &database));
+    }
+}
@@ -0,0 +1,400 @@
+use crate::chip8::Chip8;
+use crate::expr::Expr;
+use crate::observer::{Observer, Register};
+use crate::symbols::SymbolMap;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Which accesses a watchpoint breaks on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Any,
+}
+
+/// Breaks when an instruction touches an address range, inclusive
+///
+/// Reads cover `Fx65` and the sprite rows `Dxyn` draws, writes cover `Fx33`
+/// and `Fx55`, and setting `I` to point into the range also counts as a read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub access: Access,
+}
+
+impl Watchpoint {
+    /// Parses `addr`, or `start-end`, optionally followed by `:r`, `:w` or `:rw`;
+    /// addresses can be labels
+    pub fn parse(spec: &str, symbols: &SymbolMap) -> Result<Self, String> {
+        let mut parts = spec.splitn(2, ':');
+        let range = parts.next().unwrap_or("");
+        let access = match parts.next() {
+            None | Some("rw") => Access::Any,
+            Some("r") => Access::Read,
+            Some("w") => Access::Write,
+            Some(other) => return Err(format!("Unknown watchpoint access: {:?}", other)),
+        };
+        let mut bounds = range.splitn(2, '-');
+        let start = symbols.resolve(bounds.next().unwrap_or(""))?;
+        let end = match bounds.next() {
+            Some(end) => symbols.resolve(end)?,
+            None => start,
+        };
+        if end < start {
+            return Err(format!(
+                "Watchpoint range ends before it starts: {:?}",
+                spec
+            ));
+        }
+        Ok(Watchpoint { start, end, access })
+    }
+
+    fn covers(&self, address: u16, write: bool) -> bool {
+        let access = match self.access {
+            Access::Any => true,
+            Access::Read => !write,
+            Access::Write => write,
+        };
+        access && (self.start..=self.end).contains(&address)
+    }
+}
+
+/// Breaks when an expression becomes true, e.g. `v3 == 0x10 && i > 0x300` or `dt == 0`
+#[derive(Clone, Debug)]
+pub struct Condition {
+    pub source: String,
+    expr: Expr,
+    was_true: Option<bool>,
+}
+
+impl Condition {
+    pub fn parse(source: &str, symbols: &SymbolMap) -> Result<Self, String> {
+        Ok(Condition {
+            source: source.trim().to_string(),
+            expr: Expr::parse(source, symbols)?,
+            was_true: None,
+        })
+    }
+
+    /// Whether the expression went from false to true since it was last checked
+    ///
+    /// The first check only takes a baseline, so a condition that already holds
+    /// when the debugger starts does not break straight away.
+    fn became_true(&mut self, chip8: &Chip8) -> bool {
+        let is_true = self.expr.eval(chip8) != 0;
+        let became_true = self.was_true == Some(false) && is_true;
+        self.was_true = Some(is_true);
+        became_true
+    }
+}
+
+/// Why execution stopped
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Break {
+    Breakpoint(u16),
+    Read { address: u16, value: u8 },
+    Write { address: u16, value: u8 },
+    Index(u16),
+    Condition(String),
+}
+
+impl Break {
+    /// Says why execution stopped, naming addresses by label
+    pub fn describe(&self, symbols: &SymbolMap) -> String {
+        match self {
+            Break::Breakpoint(pc) => format!("Breakpoint at {}", symbols.describe(*pc)),
+            Break::Read { address, value } => {
+                format!("Read {:02X} from {}", value, symbols.describe(*address))
+            }
+            Break::Write { address, value } => {
+                format!("Wrote {:02X} to {}", value, symbols.describe(*address))
+            }
+            Break::Index(address) => format!("I set to {}", symbols.describe(*address)),
+            Break::Condition(source) => format!("Break: {}", source),
+        }
+    }
+}
+
+/// Watchpoints and the first access that hit one, shared with the observer
+struct Watches {
+    watchpoints: Vec<Watchpoint>,
+    hit: Option<Break>,
+}
+
+impl Watches {
+    fn access(&mut self, address: u16, write: bool, hit: Break) {
+        if self.hit.is_none() && self.watchpoints.iter().any(|w| w.covers(address, write)) {
+            self.hit = Some(hit);
+        }
+    }
+}
+
+/// Observer feeding memory accesses to the debugger's watchpoints
+struct WatchObserver(Rc<RefCell<Watches>>);
+
+impl Observer for WatchObserver {
+    fn memory_read(&mut self, address: u16, value: u8) {
+        self.0
+            .borrow_mut()
+            .access(address, false, Break::Read { address, value });
+    }
+
+    fn memory_write(&mut self, address: u16, value: u8) {
+        self.0
+            .borrow_mut()
+            .access(address, true, Break::Write { address, value });
+    }
+
+    fn register_write(&mut self, register: Register, value: u16) {
+        if register == Register::I {
+            self.0
+                .borrow_mut()
+                .access(value, false, Break::Index(value));
+        }
+    }
+}
+
+/// Program counter breakpoints, memory watchpoints and conditional breaks
+///
+/// The emulator asks `before_cycle` and `after_cycle` around every instruction
+/// and pauses on the first break either reports.
+pub struct Debugger {
+    pub breakpoints: Vec<u16>,
+    pub conditions: Vec<Condition>,
+    watches: Rc<RefCell<Watches>>,
+    resuming: bool,
+}
+
+impl Debugger {
+    pub fn new(
+        breakpoints: Vec<u16>,
+        watchpoints: Vec<Watchpoint>,
+        conditions: Vec<Condition>,
+    ) -> Self {
+        Debugger {
+            breakpoints,
+            conditions,
+            watches: Rc::new(RefCell::new(Watches {
+                watchpoints,
+                hit: None,
+            })),
+            resuming: false,
+        }
+    }
+
+    /// Installs the observer watchpoints need; call again after replacing the machine
+    pub fn attach(&self, chip8: &mut Chip8) {
+        chip8.add_observer(Box::new(WatchObserver(self.watches.clone())));
+    }
+
+    /// Checks for a breakpoint on the instruction about to run
+    ///
+    /// Right after breaking the same instruction is let through once, so
+    /// resuming does not stop on the breakpoint again.
+    pub fn before_cycle(&mut self, chip8: &Chip8) -> Option<Break> {
+        if self.resuming {
+            self.resuming = false;
+            return None;
+        }
+        if self.breakpoints.contains(&chip8.pc) {
+            self.resuming = true;
+            return Some(Break::Breakpoint(chip8.pc));
+        }
+        None
+    }
+
+    /// Checks watchpoints hit by the instruction that just ran, then conditions
+    pub fn after_cycle(&mut self, chip8: &Chip8) -> Option<Break> {
+        let hit = self.watches.borrow_mut().hit.take();
+        // conditions are checked even after a hit, to keep their baselines current
+        let mut condition = None;
+        for c in self.conditions.iter_mut() {
+            if c.became_true(chip8) && condition.is_none() {
+                condition = Some(Break::Condition(c.source.clone()));
+            }
+        }
+        hit.or(condition)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn load(chip8: &mut Chip8, program: &[u16]) {
+        for (i, word) in program.iter().enumerate() {
+            chip8.write_memory(0x200 + i * 2, (word >> 8) as u8);
+            chip8.write_memory(0x200 + i * 2 + 1, *word as u8);
+        }
+    }
+
+    /// Runs until the debugger breaks, returning the break
+    fn run(chip8: &mut Chip8, debugger: &mut Debugger, cycles: usize) -> Option<Break> {
+        for _ in 0..cycles {
+            if let Some(hit) = debugger.before_cycle(chip8) {
+                return Some(hit);
+            }
+            chip8.emulate_cycle();
+            if let Some(hit) = debugger.after_cycle(chip8) {
+                return Some(hit);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_parse_watchpoint() {
+        assert_eq!(
+            Ok(Watchpoint {
+                start: 0x300,
+                end: 0x30F,
+                access: Access::Write
+            }),
+            Watchpoint::parse("0x300-0x30f:w", &SymbolMap::default())
+        );
+        assert_eq!(
+            Ok(Watchpoint {
+                start: 0x200,
+                end: 0x200,
+                access: Access::Any
+            }),
+            Watchpoint::parse("0x200", &SymbolMap::default())
+        );
+        assert!(Watchpoint::parse("0x300-0x200", &SymbolMap::default()).is_err());
+        assert!(Watchpoint::parse("0x300:x", &SymbolMap::default()).is_err());
+        assert!(Watchpoint::parse("0x1000", &SymbolMap::default()).is_err());
+    }
+
+    #[test]
+    fn test_breakpoint_resumes() {
+        let mut chip8 = Chip8::initialize();
+        load(&mut chip8, &[0x7001, 0x1200]);
+        let mut debugger = Debugger::new(vec![0x202], vec![], vec![]);
+        assert_eq!(
+            Some(Break::Breakpoint(0x202)),
+            run(&mut chip8, &mut debugger, 10)
+        );
+        assert_eq!(1, chip8.v[0], "should stop before running the jump");
+        assert_eq!(
+            Some(Break::Breakpoint(0x202)),
+            run(&mut chip8, &mut debugger, 10),
+            "should run past the breakpoint once and hit it on the next loop"
+        );
+        assert_eq!(2, chip8.v[0]);
+    }
+
+    #[test]
+    fn test_write_watchpoint() {
+        let mut chip8 = Chip8::initialize();
+        // V0 = 0x99, I = 0x300, BCD of V0 to 0x300-0x302
+        load(&mut chip8, &[0x6099, 0xA300, 0xF033, 0x1206]);
+        let mut debugger = Debugger::new(
+            vec![],
+            vec![Watchpoint::parse("0x302:w", &SymbolMap::default()).unwrap()],
+            vec![],
+        );
+        debugger.attach(&mut chip8);
+        assert_eq!(
+            Some(Break::Write {
+                address: 0x302,
+                value: 3
+            }),
+            run(&mut chip8, &mut debugger, 10)
+        );
+        assert_eq!(0x206, chip8.pc);
+    }
+
+    #[test]
+    fn test_read_watchpoint_on_sprites_and_index() {
+        let mut chip8 = Chip8::initialize();
+        // I = 0x000 (the "0" glyph), draw it
+        load(&mut chip8, &[0xA000, 0xD005]);
+        let mut debugger = Debugger::new(
+            vec![],
+            vec![Watchpoint::parse("0x004:r", &SymbolMap::default()).unwrap()],
+            vec![],
+        );
+        debugger.attach(&mut chip8);
+        assert_eq!(
+            Some(Break::Read {
+                address: 4,
+                value: 0xF0
+            }),
+            run(&mut chip8, &mut debugger, 10)
+        );
+
+        let mut chip8 = Chip8::initialize();
+        load(&mut chip8, &[0xA304]);
+        let mut debugger = Debugger::new(
+            vec![],
+            vec![Watchpoint::parse("0x300-0x30F", &SymbolMap::default()).unwrap()],
+            vec![],
+        );
+        debugger.attach(&mut chip8);
+        assert_eq!(Some(Break::Index(0x304)), run(&mut chip8, &mut debugger, 1));
+
+        // V0 = 2, I = 0x300, I = the "2" glyph, I += V0
+        let mut chip8 = Chip8::initialize();
+        load(&mut chip8, &[0x6002, 0xA300, 0xF029, 0xF01E]);
+        let mut debugger = Debugger::new(
+            vec![],
+            vec![Watchpoint::parse("0x00A", &SymbolMap::default()).unwrap()],
+            vec![],
+        );
+        debugger.attach(&mut chip8);
+        assert_eq!(
+            Some(Break::Index(0x00A)),
+            run(&mut chip8, &mut debugger, 10)
+        );
+        let mut debugger = Debugger::new(
+            vec![],
+            vec![Watchpoint::parse("0x00C", &SymbolMap::default()).unwrap()],
+            vec![],
+        );
+        debugger.attach(&mut chip8);
+        assert_eq!(
+            Some(Break::Index(0x00C)),
+            run(&mut chip8, &mut debugger, 10)
+        );
+    }
+
+    #[test]
+    fn test_condition() {
+        let mut chip8 = Chip8::initialize();
+        // I = 0x301, then count V3 up
+        load(&mut chip8, &[0xA301, 0x7301, 0x1202]);
+        let condition = Condition::parse("v3 == 0x10 && i > 0x300", &SymbolMap::default()).unwrap();
+        let mut debugger = Debugger::new(vec![], vec![], vec![condition]);
+        assert_eq!(
+            Some(Break::Condition(String::from("v3 == 0x10 && i > 0x300"))),
+            run(&mut chip8, &mut debugger, 100)
+        );
+        assert_eq!(0x10, chip8.v[3]);
+        assert_eq!(
+            None,
+            run(&mut chip8, &mut debugger, 2),
+            "should only break once"
+        );
+    }
+
+    #[test]
+    fn test_timer_condition() {
+        let mut chip8 = Chip8::initialize();
+        chip8.delay_timer = 2;
+        let mut debugger = Debugger::new(
+            vec![],
+            vec![],
+            vec![Condition::parse("dt == 0", &SymbolMap::default()).unwrap()],
+        );
+        assert_eq!(None, debugger.after_cycle(&chip8));
+        chip8.update_timers();
+        assert_eq!(None, debugger.after_cycle(&chip8));
+        chip8.update_timers();
+        assert_eq!(
+            Some(Break::Condition(String::from("dt == 0"))),
+            debugger.after_cycle(&chip8)
+        );
+    }
+}
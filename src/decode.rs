@@ -0,0 +1,187 @@
+use crate::chip8::Chip8;
+use crate::instructions::*;
+use crate::utils::*;
+
+/// Instruction kinds, in the same order as `HANDLERS`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Op {
+    Sys,
+    Cls,
+    Ret,
+    Jp,
+    Call,
+    SeVxByte,
+    SneVxByte,
+    SeVxVy,
+    LdVxByte,
+    AddVxByte,
+    LdVxVy,
+    OrVxVy,
+    AndVxVy,
+    XorVxVy,
+    AddVxVy,
+    SubVxVy,
+    ShrVxVy,
+    SubnVxVy,
+    ShlVxVy,
+    SneVxVy,
+    LdIAddr,
+    JpV0Addr,
+    RndVxByte,
+    Drw,
+    SkpVx,
+    SknpVx,
+    LdVxDt,
+    LdVxK,
+    LdDtVx,
+    LdStVx,
+    AddIVx,
+    LdFVx,
+    LdBVx,
+    LdIVx,
+    LdVxI,
+    Invalid,
+}
+
+/// A pre-decoded instruction
+/// - op - The instruction kind
+/// - x, y - Register operands
+/// - n - The lowest nibble (sprite height for `Dxyn`)
+/// - kk - The lowest byte
+/// - nnn - The lowest 12 bits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub op: Op,
+    pub x: u8,
+    pub y: u8,
+    pub n: u8,
+    pub kk: u8,
+    pub nnn: u16,
+}
+
+impl Instruction {
+    /// Decodes a 16-bit word into an instruction
+    pub fn decode(word: u16) -> Self {
+        let vars = opcode_to_variables(&word);
+        Instruction {
+            op: decode_op(vars.nibbles),
+            x: vars.x as u8,
+            y: vars.y as u8,
+            n: vars.nibbles[3] as u8,
+            kk: vars.kk,
+            nnn: vars.nnn,
+        }
+    }
+}
+
+/// Matches opcode nibbles to an instruction kind
+fn decode_op(nibbles: [usize; 4]) -> Op {
+    match nibbles {
+        [0x0, 0x0, 0xE, 0x0] => Op::Cls,  // 0x00E0
+        [0x0, 0x0, 0xE, 0xE] => Op::Ret,  // 0x00EE
+        [0x0, _, _, _] => Op::Sys,        // 0x0nnn
+        [0x1, _, _, _] => Op::Jp,         // 0x1nnn
+        [0x2, _, _, _] => Op::Call,       // 0x2nnn
+        [0x3, _, _, _] => Op::SeVxByte,   // 0x3xkk
+        [0x4, _, _, _] => Op::SneVxByte,  // 0x4xkk
+        [0x5, _, _, 0x0] => Op::SeVxVy,   // 0x5xy0
+        [0x6, _, _, _] => Op::LdVxByte,   // 0x6xkk
+        [0x7, _, _, _] => Op::AddVxByte,  // 0x7xkk
+        [0x8, _, _, 0] => Op::LdVxVy,     // 0x8xy0
+        [0x8, _, _, 1] => Op::OrVxVy,     // 0x8xy1
+        [0x8, _, _, 2] => Op::AndVxVy,    // 0x8xy2
+        [0x8, _, _, 3] => Op::XorVxVy,    // 0x8xy3
+        [0x8, _, _, 4] => Op::AddVxVy,    // 0x8xy4
+        [0x8, _, _, 5] => Op::SubVxVy,    // 0x8xy5
+        [0x8, _, _, 6] => Op::ShrVxVy,    // 0x8xy6
+        [0x8, _, _, 7] => Op::SubnVxVy,   // 0x8xy7
+        [0x8, _, _, 0xE] => Op::ShlVxVy,  // 0x8xyE
+        [0x9, _, _, 0x0] => Op::SneVxVy,  // 0x9xy0
+        [0xA, _, _, _] => Op::LdIAddr,    // 0xAnnn
+        [0xB, _, _, _] => Op::JpV0Addr,   // 0xBnnn
+        [0xC, _, _, _] => Op::RndVxByte,  // 0xCxkk
+        [0xD, _, _, _] => Op::Drw,        // 0xDxyn
+        [0xE, _, 0x9, 0xE] => Op::SkpVx,  // 0xEx9E
+        [0xE, _, 0xA, 0x1] => Op::SknpVx, // 0xExA1
+        [0xF, _, 0x0, 0x7] => Op::LdVxDt, // 0xFx07
+        [0xF, _, 0x0, 0xA] => Op::LdVxK,  // 0xFx0A
+        [0xF, _, 0x1, 0x5] => Op::LdDtVx, // 0xFx15
+        [0xF, _, 0x1, 0x8] => Op::LdStVx, // 0xFx18
+        [0xF, _, 0x1, 0xE] => Op::AddIVx, // 0xFx1E
+        [0xF, _, 0x2, 0x9] => Op::LdFVx,  // 0xFx29
+        [0xF, _, 0x3, 0x3] => Op::LdBVx,  // 0xFx33
+        [0xF, _, 0x5, 0x5] => Op::LdIVx,  // 0xFx55
+        [0xF, _, 0x6, 0x5] => Op::LdVxI,  // 0xFx65
+        _ => Op::Invalid,
+    }
+}
+
+/// Instruction handler, indexed by `Op`
+pub type Handler = fn(&mut Chip8, Instruction);
+
+pub const HANDLERS: [Handler; 36] = [
+    sys_addr,
+    cls,
+    ret,
+    jp_addr,
+    call_addr,
+    se_vx_byte,
+    sne_vx_byte,
+    se_vx_vy,
+    ld_vx_byte,
+    add_vx_byte,
+    ld_vx_vy,
+    or_vx_vy,
+    and_vx_vy,
+    xor_vx_vy,
+    add_vx_vy,
+    sub_vx_vy,
+    shr_vx_vy,
+    subn_vx_vy,
+    shl_vx_vy,
+    sne_vx_vy,
+    ld_i_addr,
+    jp_v0_addr,
+    |chip8, ins| rnd_vx_byte(chip8, ins, Chip8::random_byte),
+    drw_vx_vy_nibble,
+    skp_vx,
+    sknp_vx,
+    ld_vx_dt,
+    ld_vx_k,
+    ld_dt_vx,
+    ld_st_vx,
+    add_i_vx,
+    ld_f_vx,
+    ld_b_vx,
+    ld_i_vx,
+    ld_vx_i,
+    invalid,
+];
+
+/// Runs a decoded instruction against the CPU
+pub fn execute(chip8: &mut Chip8, ins: Instruction) {
+    HANDLERS[ins.op as usize](chip8, ins);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let ins = Instruction::decode(0xD8B4);
+        assert_eq!(Op::Drw, ins.op, "should decode `Dxyn`");
+        assert_eq!(0x8, ins.x, "`x` should be correct");
+        assert_eq!(0xB, ins.y, "`y` should be correct");
+        assert_eq!(0x4, ins.n, "`n` should be correct");
+        assert_eq!(0xB4, ins.kk, "`kk` should be correct");
+        assert_eq!(0x8B4, ins.nnn, "`nnn` should be correct");
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert_eq!(Op::Invalid, Instruction::decode(0x5121).op);
+        assert_eq!(Op::Invalid, Instruction::decode(0xF0FF).op);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::decode::{Instruction, Op};
+use crate::symbols::SymbolMap;
+
+/// Assembly for one instruction word, in Cowgod's syntax
+///
+/// Jump, call and `LD I` targets are shown by label when the symbols have one.
+/// Words that are not instructions come out as `DW` data.
+pub fn mnemonic(word: u16, symbols: &SymbolMap) -> String {
+    let ins = Instruction::decode(word);
+    let (x, y) = (ins.x, ins.y);
+    let target = || match symbols.label_at(ins.nnn) {
+        Some(label) => label.to_string(),
+        None => format!("{:#05X}", ins.nnn),
+    };
+    match ins.op {
+        Op::Sys => format!("SYS {}", target()),
+        Op::Cls => String::from("CLS"),
+        Op::Ret => String::from("RET"),
+        Op::Jp => format!("JP {}", target()),
+        Op::Call => format!("CALL {}", target()),
+        Op::SeVxByte => format!("SE V{:X}, {:#04X}", x, ins.kk),
+        Op::SneVxByte => format!("SNE V{:X}, {:#04X}", x, ins.kk),
+        Op::SeVxVy => format!("SE V{:X}, V{:X}", x, y),
+        Op::LdVxByte => format!("LD V{:X}, {:#04X}", x, ins.kk),
+        Op::AddVxByte => format!("ADD V{:X}, {:#04X}", x, ins.kk),
+        Op::LdVxVy => format!("LD V{:X}, V{:X}", x, y),
+        Op::OrVxVy => format!("OR V{:X}, V{:X}", x, y),
+        Op::AndVxVy => format!("AND V{:X}, V{:X}", x, y),
+        Op::XorVxVy => format!("XOR V{:X}, V{:X}", x, y),
+        Op::AddVxVy => format!("ADD V{:X}, V{:X}", x, y),
+        Op::SubVxVy => format!("SUB V{:X}, V{:X}", x, y),
+        Op::ShrVxVy => format!("SHR V{:X}, V{:X}", x, y),
+        Op::SubnVxVy => format!("SUBN V{:X}, V{:X}", x, y),
+        Op::ShlVxVy => format!("SHL V{:X}, V{:X}", x, y),
+        Op::SneVxVy => format!("SNE V{:X}, V{:X}", x, y),
+        Op::LdIAddr => format!("LD I, {}", target()),
+        Op::JpV0Addr => format!("JP V0, {}", target()),
+        Op::RndVxByte => format!("RND V{:X}, {:#04X}", x, ins.kk),
+        Op::Drw => format!("DRW V{:X}, V{:X}, {}", x, y, ins.n),
+        Op::SkpVx => format!("SKP V{:X}", x),
+        Op::SknpVx => format!("SKNP V{:X}", x),
+        Op::LdVxDt => format!("LD V{:X}, DT", x),
+        Op::LdVxK => format!("LD V{:X}, K", x),
+        Op::LdDtVx => format!("LD DT, V{:X}", x),
+        Op::LdStVx => format!("LD ST, V{:X}", x),
+        Op::AddIVx => format!("ADD I, V{:X}", x),
+        Op::LdFVx => format!("LD F, V{:X}", x),
+        Op::LdBVx => format!("LD B, V{:X}", x),
+        Op::LdIVx => format!("LD [I], V{:X}", x),
+        Op::LdVxI => format!("LD V{:X}, [I]", x),
+        Op::Invalid => format!("DW {:#06X}", word),
+    }
+}
+
+/// Lists `bytes`, loaded at `start`, as one instruction per two bytes
+///
+/// Each line has the address, the raw word and its assembly, with a line for
+/// each label before the instruction it names. An odd byte at the end is
+/// listed as `DB`.
+pub fn listing(bytes: &[u8], start: u16, symbols: &SymbolMap) -> String {
+    let mut out = String::new();
+    for (i, pair) in bytes.chunks(2).enumerate() {
+        let address = start + i as u16 * 2;
+        if let Some(label) = symbols.label_at(address) {
+            out.push_str(&format!("{}:\n", label));
+        }
+        let line = match *pair {
+            [high, low] => {
+                let word = (high as u16) << 8 | low as u16;
+                format!("{:03X}  {:04X}  {}", address, word, mnemonic(word, symbols))
+            }
+            [byte] => format!("{:03X}  {:02X}    DB {:#04X}", address, byte, byte),
+            _ => unreachable!(),
+        };
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mnemonic() {
+        let symbols = SymbolMap::default();
+        assert_eq!("CLS", mnemonic(0x00E0, &symbols));
+        assert_eq!("JP 0x2A4", mnemonic(0x12A4, &symbols));
+        assert_eq!("LD V3, 0x10", mnemonic(0x6310, &symbols));
+        assert_eq!("DRW V0, V1, 5", mnemonic(0xD015, &symbols));
+        assert_eq!("LD VA, [I]", mnemonic(0xFA65, &symbols));
+        assert_eq!("DW 0xE0FF", mnemonic(0xE0FF, &symbols));
+    }
+
+    #[test]
+    fn test_listing_uses_labels() {
+        let symbols = SymbolMap::parse("0x200 main\n0x206 draw_player\n0x300 sprite").unwrap();
+        let listing = listing(
+            &[0x22, 0x06, 0x12, 0x00, 0xA3, 0x00, 0x00, 0xEE, 0xFF],
+            0x200,
+            &symbols,
+        );
+        assert_eq!(
+            "main:\n\
+             200  2206  CALL draw_player\n\
+             202  1200  JP main\n\
+             204  A300  LD I, sprite\n\
+             draw_player:\n\
+             206  00EE  RET\n\
+             208  FF    DB 0xFF\n",
+            listing
+        );
+    }
+}
@@ -0,0 +1,1036 @@
+use crate::capture::{self, GifRecorder};
+use crate::chip8::Chip8;
+use crate::config::Config;
+use crate::controls::{Controls, FRAME_RATE};
+use crate::crash::{self, CrashDump, History};
+use crate::debugger::{Break, Debugger};
+use crate::decode::Op;
+use crate::frontend::{Frontend, Hotkey, InputEvent};
+use crate::image::Image;
+use crate::memview::MemoryView;
+use crate::movie::Movie;
+use crate::osd::Osd;
+use crate::palette::Palette;
+use crate::platform::Platform;
+use crate::quirks::Quirks;
+use crate::record::Recorder;
+use crate::sprites::{self, SpriteTracker, Sprites};
+use crate::symbols::SymbolMap;
+use crate::timing::{self, Timing};
+use crate::trace::Tracer;
+use crate::vip::Vip;
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::Duration;
+
+/// What the frontend's debug window shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugView {
+    Sprites,
+    Memory,
+}
+
+/// Runs the core against a frontend, owning frame pacing and hotkeys
+pub struct Emulator<F: Frontend> {
+    pub chip8: Chip8,
+    pub frontend: F,
+    pub controls: Controls,
+    pub osd: Osd,
+    pub frame: u64,
+    pub debugger: Option<Debugger>,
+    symbols: SymbolMap,
+    history: History,
+    crash_path: String,
+    sprites: SpriteTracker,
+    memory_view: MemoryView,
+    debug_view: Option<DebugView>,
+    sprite_sheet: Option<String>,
+    screenshot: Option<String>,
+    /// The GIF being recorded, and where to
+    gif: Option<(GifRecorder<BufWriter<File>>, String)>,
+    recorder: Option<Recorder>,
+    /// Input being played back
+    movie: Option<Movie>,
+    /// Input being recorded, and where to save it
+    movie_out: Option<(Movie, String)>,
+    /// Seeds the random generator on every hard reset, if set
+    seed: Option<u64>,
+    /// Machine cycles the last frame ran over its budget, with VIP timing
+    cycle_debt: u32,
+    /// The VIP interpreter, on the `vip` platform
+    interpreter: Option<Vec<u8>>,
+    /// The whole VIP, which runs instead of the core on the `vip` platform
+    vip: Option<Vip>,
+    platform: Platform,
+    rom_path: String,
+    quirks: Quirks,
+    palette: Palette,
+    scale: u32,
+    max_frames: Option<u64>,
+    next_frame: Duration,
+    last_present: Option<Duration>,
+    buzzer: bool,
+    title: String,
+}
+
+impl<F: Frontend> Emulator<F> {
+    /// Creates a machine with the configured ROM loaded
+    pub fn new(config: &Config, frontend: F) -> Self {
+        let mut controls = Controls::new(config.instructions_per_frame);
+        controls.timing = config.timing;
+        let mut seed = config
+            .seed
+            .or_else(|| config.movie.as_ref().and_then(|movie| movie.seed));
+        if seed.is_none() && config.save_movie.is_some() {
+            // a movie only plays back the same with the same random numbers
+            seed = Some(rand::random());
+        }
+        let mut emulator = Emulator {
+            chip8: Chip8::initialize(),
+            frontend,
+            osd: Osd::new(controls.nominal_ips()),
+            controls,
+            frame: 0,
+            debugger: None,
+            symbols: config.symbols.clone(),
+            history: History::new(),
+            crash_path: config
+                .crash_dump
+                .clone()
+                .unwrap_or_else(|| crash::default_path(&config.rom_path)),
+            sprites: SpriteTracker::new(),
+            memory_view: MemoryView::new(),
+            debug_view: None,
+            sprite_sheet: config.sprite_sheet.clone(),
+            screenshot: config.screenshot.clone(),
+            gif: None,
+            recorder: None,
+            movie: config.movie.clone(),
+            movie_out: config
+                .save_movie
+                .as_ref()
+                .map(|path| (Movie::new(seed), path.clone())),
+            seed,
+            cycle_debt: 0,
+            interpreter: match config.platform {
+                Platform::Vip => config.interpreter.clone(),
+                _ => None,
+            },
+            vip: None,
+            platform: config.platform,
+            rom_path: config.rom_path.clone(),
+            quirks: config.quirks,
+            palette: config.palette,
+            scale: config.scale,
+            max_frames: config.max_frames,
+            next_frame: Duration::from_secs(0),
+            last_present: None,
+            buzzer: false,
+            title: String::new(),
+        };
+        emulator.hard_reset();
+        if let Some(detection) = &config.detection {
+            emulator.osd.show_message(&detection.summary);
+        }
+        if let Some(dump) = &config.post_mortem {
+            emulator.chip8 = dump.restore();
+            emulator.history = History::from_entries(&dump.history);
+            emulator.stop(crash::report(&emulator.chip8, &emulator.symbols));
+        }
+        let history = emulator.history.clone();
+        emulator.chip8.add_observer(Box::new(history));
+        let sprites = emulator.sprites.clone();
+        emulator.chip8.add_observer(Box::new(sprites));
+        let writes = emulator.memory_view.writes.clone();
+        emulator.chip8.add_observer(Box::new(writes));
+        if !config.breakpoints.is_empty()
+            || !config.watchpoints.is_empty()
+            || !config.conditions.is_empty()
+        {
+            let debugger = Debugger::new(
+                config.breakpoints.clone(),
+                config.watchpoints.clone(),
+                config.conditions.clone(),
+            );
+            debugger.attach(&mut emulator.chip8);
+            emulator.debugger = Some(debugger);
+        }
+        if let Some(path) = &config.gif {
+            if let Err(e) = emulator.start_recording(path) {
+                eprintln!("{}", e);
+            }
+        }
+        if let Some(path) = &config.record {
+            match Recorder::create(
+                path,
+                config.platform.display(),
+                capture::colors(config.platform, config.palette),
+                config.scale,
+            ) {
+                Ok(recorder) => emulator.recorder = Some(recorder),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        if let Some(path) = &config.trace {
+            match Tracer::create(path, config.symbols.clone()) {
+                Ok(tracer) => emulator.chip8.add_observer(Box::new(tracer)),
+                Err(e) => eprintln!("Error opening trace {}: {:?}", path, e),
+            }
+        }
+        emulator
+    }
+
+    /// Runs until the frontend quits or the frame limit is reached, then
+    /// exports the sprite sheet and final screenshot if they were asked for
+    /// and finishes any recordings
+    pub fn run(&mut self) {
+        while self.step() {}
+        if let Some(path) = &self.sprite_sheet {
+            if let Err(e) = self.sprite_sheet().save_png(path) {
+                eprintln!("{}", e);
+            }
+        }
+        if let Some(path) = &self.screenshot {
+            if let Err(e) = self.take_screenshot().save_png(path) {
+                eprintln!("{}", e);
+            }
+        }
+        if let Some(message) = self.stop_recording() {
+            eprintln!("{}", message);
+        }
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(e) = recorder.finish() {
+                eprintln!("{}", e);
+            }
+        }
+        if let Some((movie, path)) = &self.movie_out {
+            if let Err(e) = movie.save(path) {
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    /// The display at the configured palette and scale
+    pub fn take_screenshot(&self) -> Image {
+        capture::screenshot(
+            &capture::pixels(&self.chip8),
+            self.chip8.width(),
+            &capture::colors(self.platform, self.palette),
+            self.scale,
+        )
+    }
+
+    /// Starts recording a GIF from the next frame
+    fn start_recording(&mut self, path: &str) -> Result<(), String> {
+        let recorder = GifRecorder::create(
+            path,
+            self.chip8.platform.display(),
+            &capture::colors(self.platform, self.palette),
+            self.scale,
+        )?;
+        self.gif = Some((recorder, path.to_string()));
+        Ok(())
+    }
+
+    /// Finishes the GIF being recorded, returning what happened
+    fn stop_recording(&mut self) -> Option<String> {
+        let (recorder, path) = self.gif.take()?;
+        Some(match recorder.finish(self.frame) {
+            Ok(_) => format!("Saved {}", path),
+            Err(e) => format!("Error writing {}: {:?}", path, e),
+        })
+    }
+
+    /// The sprites in memory, with the heights the game has drawn them at so far
+    pub fn sprites(&self) -> Sprites {
+        let rom = &self.chip8.memory[self.platform.load_address() as usize..];
+        let scanned = sprites::scan(rom, self.platform);
+        sprites::merge(scanned, &self.sprites.sprites())
+    }
+
+    /// A sheet of `sprites`, in the palette
+    pub fn sprite_sheet(&self) -> Image {
+        sprites::sheet(&self.chip8.memory, &self.sprites(), self.palette)
+    }
+
+    /// Handles input, then runs and presents a frame if one is due; returns false to stop
+    pub fn step(&mut self) -> bool {
+        for event in self.frontend.poll_events() {
+            match event {
+                InputEvent::Quit => return false,
+                InputEvent::KeyDown(key) => self.press_key(key, true),
+                InputEvent::KeyUp(key) => self.press_key(key, false),
+                InputEvent::Hotkey(hotkey) => self.handle_hotkey(hotkey),
+                InputEvent::ViewKey(key) if self.debug_view == Some(DebugView::Memory) => {
+                    if self.memory_view.handle(key, &mut self.chip8) {
+                        // poking a running machine would race it
+                        self.controls.paused = true;
+                        self.osd.paused = true;
+                    }
+                    self.refresh_debug_window();
+                }
+                InputEvent::ViewKey(_) => {}
+            }
+        }
+
+        let title = self.controls.title();
+        if title != self.title {
+            self.frontend.set_title(&title);
+            self.title = title;
+        }
+
+        let now = self.frontend.now();
+        if now < self.next_frame {
+            self.frontend.sleep(self.next_frame - now);
+            return true;
+        }
+        self.next_frame += self.controls.frame_duration();
+        if self.next_frame < now {
+            // fell behind, e.g. after a stall; don't try to catch up
+            self.next_frame = now + self.controls.frame_duration();
+        }
+
+        if self.controls.take_frame() {
+            self.run_frame();
+        }
+
+        let buzzer = self.chip8.sound_timer > 0 && !self.controls.paused;
+        if buzzer != self.buzzer {
+            self.frontend.set_buzzer(buzzer);
+            self.buzzer = buzzer;
+        }
+
+        self.present(now);
+
+        if self.chip8.fault.is_some() && !self.frontend.is_interactive() {
+            return false;
+        }
+        match self.max_frames {
+            Some(max_frames) => self.frame < max_frames,
+            None => true,
+        }
+    }
+
+    /// Passes a keypad key on, recording it into any movie being saved
+    fn press_key(&mut self, key: usize, pressed: bool) {
+        self.set_key(key, pressed);
+        if let Some((movie, _)) = &mut self.movie_out {
+            movie.record(self.frame, key, pressed);
+        }
+    }
+
+    /// Passes a keypad key to whichever machine is running
+    fn set_key(&mut self, key: usize, pressed: bool) {
+        match &mut self.vip {
+            Some(vip) => vip.set_key(key, pressed),
+            None => self.chip8.set_key(key, pressed),
+        }
+    }
+
+    /// Runs one frame's worth of instructions and ticks the timers
+    ///
+    /// With VIP timing a frame runs until the interpreter's share of the frame's
+    /// machine cycles is spent, carrying any overrun into the next, and a draw
+    /// waits for the next frame, as the VIP's did for the vertical blank
+    /// interrupt, unless it is the first instruction.
+    ///
+    /// A debugger break or a trapped invalid opcode pauses and cuts the frame's
+    /// instructions short. A fault stops the machine for good, until it is reset.
+    pub fn run_frame(&mut self) {
+        if self.chip8.fault.is_some() {
+            return;
+        }
+        let keys = match &mut self.movie {
+            Some(movie) => movie.take(self.frame).to_vec(),
+            None => Vec::new(),
+        };
+        for (_, key, pressed) in keys {
+            self.set_key(key, pressed);
+        }
+        let count = match &mut self.vip {
+            Some(vip) => {
+                vip.write_back(&self.chip8);
+                vip.run_frame();
+                vip.sync(&mut self.chip8);
+                0
+            }
+            None => match self.run_instructions() {
+                Some(count) => count,
+                None => return,
+            },
+        };
+        self.memory_view.writes.tick();
+        let pixels = capture::pixels(&self.chip8);
+        if let Some((recorder, path)) = &mut self.gif {
+            if let Err(e) = recorder.frame(&pixels, self.frame) {
+                let message = format!("Error writing {}: {:?}", path, e);
+                self.warn(&message);
+                self.gif = None;
+            }
+        }
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.frame(&pixels, self.chip8.sound_timer > 0) {
+                self.warn(&e);
+                self.recorder = None;
+            }
+        }
+        self.check_debugger(Debugger::after_cycle);
+        self.osd.record_cycles(count);
+        self.frame += 1;
+    }
+
+    /// Runs the core's instructions for a frame and ticks its timers, returning
+    /// how many ran, or `None` if the machine crashed
+    fn run_instructions(&mut self) -> Option<u32> {
+        let (mut count, mut spent) = (0, self.cycle_debt);
+        loop {
+            let next = match self.controls.timing {
+                Timing::Ipf if count < self.controls.instructions_per_frame => None,
+                Timing::Vip if spent < timing::BUDGET => timing::next_instruction(&self.chip8),
+                _ => break,
+            };
+            if count > 0 && next.is_some_and(|ins| ins.op == Op::Drw) {
+                // the rest of the frame is spent idle, waiting for the interrupt
+                spent = timing::BUDGET;
+                break;
+            }
+            if self.check_debugger(Debugger::before_cycle) {
+                break;
+            }
+            let (pc, v) = (self.chip8.pc, self.chip8.v);
+            self.chip8.emulate_cycle();
+            count += 1;
+            if let Some(ins) = next {
+                let skipped = self.chip8.pc == pc.wrapping_add(4);
+                spent += timing::vip_cycles(ins, &v, skipped);
+            }
+            if self.chip8.fault.is_some() {
+                self.crash();
+                return None;
+            }
+            if let Some(address) = self.chip8.trap {
+                self.controls.paused = true;
+                self.osd.paused = true;
+                self.osd.show_message(&format!(
+                    "Trap: invalid opcode {:04X} at {}",
+                    self.chip8.opcode,
+                    self.symbols.describe(address)
+                ));
+                break;
+            }
+            if self.check_debugger(Debugger::after_cycle) {
+                break;
+            }
+        }
+        self.chip8.update_timers();
+        self.cycle_debt = spent.saturating_sub(timing::BUDGET);
+        Some(count)
+    }
+
+    /// Runs one of the debugger's checks, pausing and saying why on the OSD if it breaks
+    fn check_debugger(&mut self, check: fn(&mut Debugger, &Chip8) -> Option<Break>) -> bool {
+        let hit = match &mut self.debugger {
+            Some(debugger) => check(debugger, &self.chip8),
+            None => None,
+        };
+        match hit {
+            Some(hit) => {
+                self.controls.paused = true;
+                self.osd.paused = true;
+                self.osd.show_message(&hit.describe(&self.symbols));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Pauses on a fault, saves a crash dump and shows the report
+    fn crash(&mut self) {
+        let dump = CrashDump::capture(&self.chip8, &self.rom_path, &self.history);
+        let mut report = crash::report(&self.chip8, &self.symbols);
+        report.push(match dump.save(&self.crash_path, &self.symbols) {
+            Ok(()) => format!("Dump saved to {}", self.crash_path),
+            Err(e) => e,
+        });
+        if !self.frontend.is_interactive() {
+            for line in &report {
+                eprintln!("{}", line);
+            }
+        }
+        self.stop(report);
+    }
+
+    /// Shows a problem on the OSD, or on stderr when nobody is watching
+    fn warn(&mut self, message: &str) {
+        if !self.frontend.is_interactive() {
+            eprintln!("{}", message);
+        }
+        self.osd.show_message(message);
+    }
+
+    /// Pauses with lines that stay on the OSD until a reset
+    fn stop(&mut self, report: Vec<String>) {
+        self.controls.paused = true;
+        self.osd.paused = true;
+        self.osd.show_report(report);
+    }
+
+    /// Redraws the debug window, if one is open
+    fn refresh_debug_window(&mut self) {
+        let image = match self.debug_view {
+            Some(DebugView::Sprites) => self.sprite_sheet(),
+            Some(DebugView::Memory) => self.memory_view.render(&self.chip8, self.palette),
+            None => return,
+        };
+        self.frontend.show_debug_window(Some(&image));
+    }
+
+    /// Opens the debug window on a view, or closes it if it already shows that view
+    fn toggle_debug_view(&mut self, view: DebugView) {
+        if self.debug_view == Some(view) {
+            self.debug_view = None;
+            self.frontend.show_debug_window(None);
+        } else {
+            self.debug_view = Some(view);
+            self.refresh_debug_window();
+        }
+    }
+
+    /// Presents the display if it changed, at most at the display's refresh rate
+    ///
+    /// Fast-forward can run frames faster than that, and the OSD changes without
+    /// the game drawing, so it keeps refreshing while active. So does the memory
+    /// view, which changes whenever the game runs.
+    fn present(&mut self, now: Duration) {
+        let redraw = self.chip8.should_draw
+            || self.osd.is_active()
+            || self.frontend.needs_redraw(&self.chip8)
+            || (self.debug_view == Some(DebugView::Memory) && !self.controls.paused);
+        let refresh = Duration::from_secs(1) / FRAME_RATE;
+        let due = match self.last_present {
+            Some(last_present) => now >= last_present + refresh,
+            None => true,
+        };
+        if redraw && due {
+            self.refresh_debug_window();
+            self.frontend.present(&self.chip8, &self.osd.lines());
+            self.chip8.should_draw = false;
+            self.osd.record_frame();
+            self.last_present = Some(now);
+        }
+    }
+
+    fn handle_hotkey(&mut self, hotkey: Hotkey) {
+        match hotkey {
+            Hotkey::ToggleOsd => self.osd.visible = !self.osd.visible,
+            Hotkey::TogglePause => {
+                self.controls.toggle_pause();
+                self.osd.paused = self.controls.paused;
+            }
+            Hotkey::AdvanceFrame => self.controls.advance_frame(),
+            Hotkey::SoftReset => {
+                self.soft_reset();
+                self.osd.show_message("Soft reset");
+            }
+            Hotkey::HardReset => {
+                self.hard_reset();
+                self.osd.show_message("Hard reset");
+            }
+            Hotkey::FastForward(held) => self.controls.fast_forward = held,
+            Hotkey::ToggleSlowMotion => self.controls.slow_motion = !self.controls.slow_motion,
+            Hotkey::SpeedUp | Hotkey::SpeedDown if self.controls.timing == Timing::Vip => {
+                self.osd.show_message("VIP timing sets the speed")
+            }
+            Hotkey::SpeedUp | Hotkey::SpeedDown => {
+                self.controls
+                    .adjust_speed(if hotkey == Hotkey::SpeedUp { 1 } else { -1 });
+                self.osd.nominal_ips = self.controls.nominal_ips();
+                self.osd.show_message(&format!(
+                    "{} instructions per frame",
+                    self.controls.instructions_per_frame
+                ));
+            }
+            Hotkey::ToggleSprites => self.toggle_debug_view(DebugView::Sprites),
+            Hotkey::ToggleMemory => self.toggle_debug_view(DebugView::Memory),
+            Hotkey::CloseDebugWindow => {
+                self.debug_view = None;
+                self.frontend.show_debug_window(None);
+            }
+            Hotkey::Screenshot => {
+                let path = capture::next_path(&self.rom_path, "png");
+                match self.take_screenshot().save_png(&path) {
+                    Ok(()) => self.osd.show_message(&format!("Saved {}", path)),
+                    Err(e) => self.osd.show_message(&e),
+                }
+            }
+            Hotkey::ToggleRecording => match self.stop_recording() {
+                Some(message) => self.osd.show_message(&message),
+                None => {
+                    let path = capture::next_path(&self.rom_path, "gif");
+                    match self.start_recording(&path) {
+                        Ok(()) => self.osd.show_message(&format!("Recording to {}", path)),
+                        Err(e) => self.osd.show_message(&e),
+                    }
+                }
+            },
+        }
+    }
+
+    /// Resets the CPU and reloads the ROM, keeping the rest of memory
+    pub fn soft_reset(&mut self) {
+        self.osd.clear_report();
+        self.chip8.reset();
+        self.chip8.load_rom(&self.rom_path);
+        self.start_vip();
+    }
+
+    /// Starts over from a fresh machine, keeping any observers installed on the old one
+    pub fn hard_reset(&mut self) {
+        let observers = self.chip8.take_observers();
+        self.chip8 = Chip8::for_platform(self.platform);
+        for observer in observers {
+            self.chip8.add_observer(observer);
+        }
+        self.chip8.quirks = self.quirks;
+        self.chip8.random = self.seed;
+        self.chip8.load_rom(&self.rom_path);
+        self.start_vip();
+        self.osd.clear_report();
+    }
+
+    /// Starts the VIP over on the core's memory, on the `vip` platform
+    fn start_vip(&mut self) {
+        self.vip = self
+            .interpreter
+            .as_ref()
+            .map(|interpreter| Vip::new(interpreter, &self.chip8.memory));
+        if let Some(vip) = &self.vip {
+            vip.sync(&mut self.chip8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::debugger::Condition;
+    use crate::frontend::{Headless, TestFrontend};
+    use crate::memview::ViewKey;
+    use crate::quirks::InvalidOpcode;
+
+    /// Writes a program over the test ROM, which is empty
+    fn emulator(program: &[u16], script: Vec<Vec<InputEvent>>) -> Emulator<TestFrontend> {
+        let config = Config {
+            rom_path: String::from("test/test-rom.ch8"),
+            instructions_per_frame: 1,
+            ..Config::default()
+        };
+        let mut emulator = Emulator::new(&config, TestFrontend::new(script));
+        for (i, word) in program.iter().enumerate() {
+            emulator
+                .chip8
+                .write_memory(0x200 + i * 2, (word >> 8) as u8);
+            emulator.chip8.write_memory(0x200 + i * 2 + 1, *word as u8);
+        }
+        emulator
+    }
+
+    #[test]
+    fn test_paces_frames_with_host_time() {
+        let mut emulator = emulator(&[0x1200], vec![]);
+        assert!(emulator.step());
+        assert_eq!(1, emulator.frame);
+        assert!(emulator.step(), "should sleep until the next frame");
+        assert_eq!(1, emulator.frame);
+        assert_eq!(emulator.controls.frame_duration(), emulator.frontend.time);
+        emulator.step();
+        assert_eq!(2, emulator.frame);
+    }
+
+    #[test]
+    fn test_presents_drawn_frames() {
+        // v0 = 0, draw the "0" glyph, loop
+        let mut emulator = emulator(&[0xD005, 0x1202], vec![]);
+        emulator.step();
+        assert_eq!(1, emulator.frontend.frames.len());
+        assert_eq!(1, emulator.frontend.frames[0][0]);
+        emulator.step();
+        emulator.step();
+        assert_eq!(1, emulator.frontend.frames.len(), "should not redraw");
+    }
+
+    #[test]
+    fn test_keys_and_quit() {
+        let mut emulator = emulator(
+            &[0xF30A, 0x1202],
+            vec![
+                vec![InputEvent::KeyDown(0x4), InputEvent::KeyUp(0x4)],
+                vec![InputEvent::Quit],
+            ],
+        );
+        assert!(emulator.step());
+        assert_eq!(0x4, emulator.chip8.v[3]);
+        assert!(!emulator.step(), "should stop on quit");
+    }
+
+    #[test]
+    fn test_pause_and_advance() {
+        let mut emulator = emulator(
+            &[0x7001, 0x1200],
+            vec![
+                vec![InputEvent::Hotkey(Hotkey::TogglePause)],
+                vec![],
+                vec![InputEvent::Hotkey(Hotkey::AdvanceFrame)],
+            ],
+        );
+        emulator.step();
+        assert_eq!(0, emulator.frame, "should not run while paused");
+        assert!(emulator.frontend.title.ends_with("paused"));
+        emulator.step();
+        emulator.step();
+        assert_eq!(1, emulator.frame, "should advance one frame");
+    }
+
+    #[test]
+    fn test_buzzer() {
+        let mut emulator = emulator(&[0x1200], vec![]);
+        emulator.chip8.sound_timer = 2;
+        for _ in 0..12 {
+            emulator.step();
+        }
+        assert_eq!(vec![true, false], emulator.frontend.buzzer);
+    }
+
+    #[test]
+    fn test_debugger_pauses() {
+        let mut emulator = emulator(&[0x7001, 0x1200], vec![]);
+        let debugger = Debugger::new(
+            vec![],
+            vec![],
+            vec![Condition::parse("v0 == 3", &SymbolMap::default()).unwrap()],
+        );
+        debugger.attach(&mut emulator.chip8);
+        emulator.debugger = Some(debugger);
+        for _ in 0..20 {
+            emulator.step();
+        }
+        assert!(emulator.controls.paused);
+        assert_eq!(3, emulator.chip8.v[0]);
+        assert!(emulator
+            .osd
+            .lines()
+            .contains(&String::from("Break: v0 == 3")));
+    }
+
+    #[test]
+    fn test_crash_saves_a_dump() {
+        let path = std::env::temp_dir().join(format!("chip8-crash-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let mut emulator = emulator(&[0x6005, 0xE0FF], vec![]);
+        emulator.crash_path = path.clone();
+        for _ in 0..4 {
+            emulator.step();
+        }
+        assert!(emulator.controls.paused);
+        let lines = emulator.osd.lines();
+        assert_eq!("Invalid opcode at 202", lines[1]);
+        assert_eq!(&format!("Dump saved to {}", path), lines.last().unwrap());
+
+        let config = Config {
+            rom_path: String::from("test/test-rom.ch8"),
+            post_mortem: Some(CrashDump::load(&path).unwrap()),
+            ..Config::default()
+        };
+        let mut emulator = Emulator::new(&config, TestFrontend::new(vec![]));
+        assert!(emulator.controls.paused);
+        assert_eq!(5, emulator.chip8.v[0]);
+        assert_eq!(
+            vec![(0x200, 0x6005), (0x202, 0xE0FF)],
+            emulator.history.entries()
+        );
+        assert_eq!("Invalid opcode at 202", emulator.osd.lines()[1]);
+
+        emulator.hard_reset();
+        assert_eq!(None, emulator.chip8.fault);
+        assert_eq!(
+            vec!["PAUSED"],
+            emulator.osd.lines(),
+            "should clear the report"
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_trap_pauses_then_skips() {
+        let mut emulator = emulator(&[0x6005, 0xE0FF, 0x6106, 0x1206], vec![]);
+        emulator.chip8.quirks.invalid_opcode = InvalidOpcode::Trap;
+        for _ in 0..4 {
+            emulator.step();
+        }
+        assert!(emulator.controls.paused);
+        assert_eq!(0x202, emulator.chip8.pc);
+        assert!(emulator
+            .osd
+            .lines()
+            .contains(&String::from("Trap: invalid opcode E0FF at 202")));
+
+        emulator.handle_hotkey(Hotkey::TogglePause);
+        for _ in 0..6 {
+            emulator.step();
+        }
+        assert_eq!(6, emulator.chip8.v[1], "should skip the word when resumed");
+        assert_eq!(None, emulator.chip8.fault);
+    }
+
+    #[test]
+    fn test_sprite_view_and_sheet() {
+        // v0 = 0, draw the "0" glyph, loop
+        let script = vec![vec![InputEvent::Hotkey(Hotkey::ToggleSprites)]];
+        let mut emulator = emulator(&[0xD005, 0x1202], script);
+        emulator.step();
+        let sheet = emulator.frontend.debug_window.clone().unwrap();
+        assert_eq!(
+            emulator.sprite_sheet(),
+            sheet,
+            "should update once the glyph is drawn"
+        );
+        assert_eq!(sprites::SHEET_SCALE * 10, sheet.width);
+
+        let path = std::env::temp_dir().join(format!("chip8-sprites-{}.png", std::process::id()));
+        emulator.sprite_sheet = Some(path.to_str().unwrap().to_string());
+        emulator.max_frames = Some(1);
+        emulator.run();
+        assert_eq!(sheet.to_png(), std::fs::read(&path).unwrap());
+        std::fs::remove_file(path).unwrap();
+
+        emulator.handle_hotkey(Hotkey::ToggleSprites);
+        assert_eq!(None, emulator.frontend.debug_window);
+    }
+
+    #[test]
+    fn test_memory_view_edits() {
+        // 200: V0 = 0; 202: loop
+        let script = vec![
+            vec![InputEvent::Hotkey(Hotkey::ToggleMemory)],
+            vec![
+                InputEvent::ViewKey(ViewKey::Right),
+                InputEvent::ViewKey(ViewKey::Digit(0xA)),
+                InputEvent::ViewKey(ViewKey::Digit(0xB)),
+            ],
+        ];
+        let mut emulator = emulator(&[0x6000, 0x1202], script);
+        emulator.step();
+        assert!(emulator.frontend.debug_window.is_some());
+        emulator.step();
+        assert!(emulator.controls.paused, "should pause to edit");
+        assert_eq!(0xAB, emulator.chip8.memory[0x201]);
+        assert_eq!(
+            emulator
+                .memory_view
+                .render(&emulator.chip8, emulator.palette),
+            emulator.frontend.debug_window.clone().unwrap()
+        );
+
+        emulator.handle_hotkey(Hotkey::ToggleSprites);
+        assert_eq!(Some(DebugView::Sprites), emulator.debug_view);
+        emulator.handle_hotkey(Hotkey::CloseDebugWindow);
+        assert_eq!(None, emulator.frontend.debug_window);
+    }
+
+    #[test]
+    fn test_screenshot_and_gif() {
+        let temp = |name: &str| {
+            let path = std::env::temp_dir().join(format!("chip8-{}-{}", std::process::id(), name));
+            path.to_str().unwrap().to_string()
+        };
+        let (png, gif) = (temp("screenshot.png"), temp("recording.gif"));
+        let config = Config {
+            rom_path: String::from("test/test-rom.ch8"),
+            scale: 2,
+            max_frames: Some(3),
+            screenshot: Some(png.clone()),
+            gif: Some(gif.clone()),
+            ..Config::default()
+        };
+        let mut emulator = Emulator::new(&config, Headless::new());
+        // v0 = 0, draw the "0" glyph, loop
+        for (i, &byte) in [0xD0, 0x05, 0x12, 0x02].iter().enumerate() {
+            emulator.chip8.write_memory(0x200 + i, byte);
+        }
+        emulator.run();
+
+        let screenshot = emulator.take_screenshot();
+        assert_eq!((128, 64), (screenshot.width, screenshot.height));
+        assert_eq!(screenshot.to_png(), std::fs::read(&png).unwrap());
+        let recording = std::fs::read(&gif).unwrap();
+        assert_eq!([128, 0, 64, 0], recording[6..10]);
+        assert_eq!(Some(&0x3B), recording.last());
+        std::fs::remove_file(png).unwrap();
+        std::fs::remove_file(gif).unwrap();
+
+        // hotkey screenshots go next to the ROM, numbered
+        emulator.rom_path = temp("game.ch8");
+        emulator.handle_hotkey(Hotkey::Screenshot);
+        let first = temp("game-1.png");
+        assert!(std::path::Path::new(&first).exists());
+        assert_eq!(
+            temp("game-2.png"),
+            capture::next_path(&emulator.rom_path, "png"),
+            "should not overwrite the first"
+        );
+        std::fs::remove_file(first).unwrap();
+    }
+
+    #[test]
+    fn test_recordings_repeat() {
+        let path = std::env::temp_dir().join(format!("chip8-record-{}.y4m", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let record = || {
+            let config = Config {
+                rom_path: String::from("test/test-rom.ch8"),
+                instructions_per_frame: 2,
+                scale: 1,
+                max_frames: Some(6),
+                movie: Some(Movie::parse("seed 3\n2 +1").unwrap()),
+                record: Some(path.clone()),
+                ..Config::default()
+            };
+            let mut emulator = Emulator::new(&config, Headless::new());
+            // v0 = random, skip unless key 1 is down, draw "0" at (v0, v0), loop
+            for (i, &byte) in [0xC0, 0x3F, 0xE0, 0x9E, 0xD0, 0x05, 0x12, 0x00]
+                .iter()
+                .enumerate()
+            {
+                emulator.chip8.write_memory(0x200 + i, byte);
+            }
+            emulator.run();
+            let video = std::fs::read(&path).unwrap();
+            let audio = std::fs::read(path.replace(".y4m", ".wav")).unwrap();
+            (video, audio)
+        };
+        let (video, audio) = record();
+        assert_eq!((video.clone(), audio.clone()), record());
+        let header = "YUV4MPEG2 W64 H32 F60:1 Ip A1:1 C444\n".len();
+        assert_eq!(header + 6 * (6 + 3 * 64 * 32), video.len());
+        assert_eq!(44 + 6 * 735 * 2, audio.len());
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.replace(".y4m", ".wav")).unwrap();
+    }
+
+    #[test]
+    fn test_recording_sound() {
+        let path = std::env::temp_dir().join(format!("chip8-sound-{}.y4m", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let config = Config {
+            rom_path: String::from("test/test-rom.ch8"),
+            scale: 1,
+            max_frames: Some(8),
+            record: Some(path.clone()),
+            ..Config::default()
+        };
+        let mut emulator = Emulator::new(&config, Headless::new());
+        // v0 = 3, sound timer = v0, loop
+        for (i, &byte) in [0x60, 0x03, 0xF0, 0x18, 0x12, 0x04].iter().enumerate() {
+            emulator.chip8.write_memory(0x200 + i, byte);
+        }
+        emulator.run();
+
+        let audio = std::fs::read(path.replace(".y4m", ".wav")).unwrap();
+        let frames: Vec<bool> = audio[44..]
+            .chunks(735 * 2)
+            .map(|frame| frame.iter().any(|&byte| byte != 0))
+            .collect();
+        assert_eq!(8, frames.len());
+        assert!(frames[0], "Fx18 should sound the buzzer");
+        assert!(!frames[7], "the sound timer should run out");
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.replace(".y4m", ".wav")).unwrap();
+    }
+
+    #[test]
+    fn test_saves_movie() {
+        let script = vec![
+            vec![],
+            vec![InputEvent::KeyDown(5)],
+            vec![InputEvent::KeyUp(5)],
+        ];
+        let mut emulator = emulator(&[0x1200], script);
+        emulator.movie_out = Some((Movie::new(Some(9)), String::new()));
+        for _ in 0..6 {
+            emulator.step();
+        }
+        let (movie, _) = emulator.movie_out.unwrap();
+        // the second poll comes while waiting for frame 1, so both land before it
+        assert_eq!("seed 9\n1 +5\n1 -5\n", movie.to_string());
+    }
+
+    #[test]
+    fn test_vip_timing() {
+        // 200: V0 += 1; 202: loop
+        let mut looping = emulator(&[0x7001, 0x1200], vec![]);
+        looping.controls.timing = Timing::Vip;
+        looping.run_frame();
+        // each pass is 50 cycles for the add and 52 for the jump
+        let passes = timing::BUDGET.div_ceil(102) as u8;
+        assert!((passes - 1..=passes).contains(&looping.chip8.v[0]));
+        assert!(looping.cycle_debt < 52);
+
+        // 200: V0 += 1; 202: draw; 204: loop
+        let mut emulator = emulator(&[0x7001, 0xD005, 0x1200], vec![]);
+        emulator.controls.timing = Timing::Vip;
+        for frame in 1..=3 {
+            emulator.run_frame();
+            assert_eq!(
+                frame, emulator.chip8.v[0],
+                "should wait for the next frame to draw"
+            );
+        }
+        assert_eq!(0x202, emulator.chip8.pc);
+    }
+
+    #[test]
+    fn test_vip_timing_delay_loop() {
+        // 200: V0 = 5; DT = V0; 204: V2 += 1; V1 = DT; skip if V1 == 0; loop; 20C: done
+        let mut emulator = emulator(
+            &[0x6005, 0xF015, 0x7201, 0xF107, 0x3100, 0x1204, 0x120C],
+            vec![],
+        );
+        emulator.controls.timing = Timing::Vip;
+        let mut frames = 0;
+        while emulator.chip8.pc != 0x20C {
+            emulator.run_frame();
+            frames += 1;
+        }
+        assert_eq!(6, frames, "should wait out the delay timer");
+        // each pass is 50 cycles for the add, load and skip and 52 for the jump
+        let passes = (5 * timing::BUDGET / 202) as u8;
+        assert!((passes - 2..=passes + 2).contains(&emulator.chip8.v[2]));
+    }
+
+    #[test]
+    fn test_vip_platform() {
+        let config = Config {
+            rom_path: String::from("test/test-rom.ch8"),
+            platform: Platform::Vip,
+            // LDI 0x02; PHI R5; LDI 0x42; PLO R5; IDL
+            interpreter: Some(vec![0xF8, 0x02, 0xB5, 0xF8, 0x42, 0xA5, 0x00]),
+            ..Config::default()
+        };
+        let script = vec![vec![InputEvent::KeyDown(5)]];
+        let mut emulator = Emulator::new(&config, TestFrontend::new(script));
+        emulator.step();
+        assert_eq!(0x242, emulator.chip8.pc, "should show the interpreter's R5");
+        assert!(emulator.chip8.key[5]);
+        assert!(
+            emulator.chip8.key_events.is_empty(),
+            "should send keys to the VIP"
+        );
+
+        // as the memory view would
+        emulator.chip8.write_memory(0x300, 0xAB);
+        emulator.chip8.set_v(3, 7);
+        emulator.run_frame();
+        assert_eq!(0xAB, emulator.chip8.memory[0x300], "should keep edits");
+        assert_eq!(7, emulator.chip8.v[3]);
+    }
+}
@@ -0,0 +1,351 @@
+use crate::chip8::Chip8;
+use crate::symbols::SymbolMap;
+
+/// A parsed debugger expression, e.g. `v3 == 0x10 && i > 0x300`
+///
+/// Values are integers, and conditions are true when non-zero. Names are the
+/// registers `v0`-`vf`, `i`, `pc` and `sp`, and the timers `dt` and `st`;
+/// `[addr]` reads a byte of memory, and labels from a symbol map stand for
+/// their addresses. Operators, loosest first, are `||`, `&&`,
+/// comparisons, `+ - & |`, and unary `!` and `-`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Name(Name),
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+/// A register or timer an expression can name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Name {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    DelayTimer,
+    SoundTimer,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    BitAnd,
+    BitOr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(Name),
+    Op(&'static str),
+}
+
+/// Operators, longest first so `<=` wins over `<`
+const OPERATORS: [&str; 16] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "&", "|", "!", "(", ")", "[",
+];
+
+impl Expr {
+    /// Parses an expression, looking up any labels in `symbols`
+    pub fn parse(text: &str, symbols: &SymbolMap) -> Result<Self, String> {
+        let tokens = tokenize(text, symbols)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {:?} in {:?}", token, text)),
+        }
+    }
+
+    /// Evaluates against the machine's current state
+    pub fn eval(&self, chip8: &Chip8) -> i64 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Name(name) => match name {
+                Name::V(x) => chip8.v[*x].into(),
+                Name::I => chip8.i.into(),
+                Name::Pc => chip8.pc.into(),
+                Name::Sp => chip8.sp.into(),
+                Name::DelayTimer => chip8.delay_timer.into(),
+                Name::SoundTimer => chip8.sound_timer.into(),
+            },
+            Expr::Memory(address) => {
+                let address = address.eval(chip8) as usize % chip8.memory.len();
+                chip8.memory[address].into()
+            }
+            Expr::Not(expr) => (expr.eval(chip8) == 0) as i64,
+            Expr::Negate(expr) => -expr.eval(chip8),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.eval(chip8);
+                // `&&` and `||` short-circuit
+                match op {
+                    BinaryOp::Or if lhs != 0 => return 1,
+                    BinaryOp::And if lhs == 0 => return 0,
+                    _ => {}
+                }
+                let rhs = rhs.eval(chip8);
+                match op {
+                    BinaryOp::Or | BinaryOp::And => (rhs != 0) as i64,
+                    BinaryOp::Eq => (lhs == rhs) as i64,
+                    BinaryOp::Ne => (lhs != rhs) as i64,
+                    BinaryOp::Lt => (lhs < rhs) as i64,
+                    BinaryOp::Le => (lhs <= rhs) as i64,
+                    BinaryOp::Gt => (lhs > rhs) as i64,
+                    BinaryOp::Ge => (lhs >= rhs) as i64,
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                    BinaryOp::BitAnd => lhs & rhs,
+                    BinaryOp::BitOr => lhs | rhs,
+                }
+            }
+        }
+    }
+}
+
+/// Parses a decimal, `0x` hex or `0b` binary number
+pub fn parse_number(text: &str) -> Result<i64, String> {
+    let lower = text.to_ascii_lowercase();
+    let result = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        lower.parse()
+    };
+    result.map_err(|_| format!("Invalid number: {:?}", text))
+}
+
+fn parse_name(word: &str) -> Option<Name> {
+    let lower = word.to_ascii_lowercase();
+    match lower.as_str() {
+        "i" => Some(Name::I),
+        "pc" => Some(Name::Pc),
+        "sp" => Some(Name::Sp),
+        "dt" => Some(Name::DelayTimer),
+        "st" => Some(Name::SoundTimer),
+        _ => {
+            let register = lower.strip_prefix('v')?;
+            if register.len() != 1 {
+                return None;
+            }
+            usize::from_str_radix(register, 16).ok().map(Name::V)
+        }
+    }
+}
+
+fn tokenize(text: &str, symbols: &SymbolMap) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        if rest.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            let token = if word.starts_with(|c: char| c.is_ascii_digit()) {
+                Token::Number(parse_number(word)?)
+            } else if let Some(name) = parse_name(word) {
+                Token::Name(name)
+            } else if let Some(address) = symbols.address_of(word) {
+                Token::Number(address.into())
+            } else {
+                return Err(format!("Unknown name: {:?}", word));
+            };
+            tokens.push(token);
+            rest = &rest[end..];
+        } else if rest.starts_with(']') {
+            tokens.push(Token::Op("]"));
+            rest = &rest[1..];
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .ok_or_else(|| format!("Unexpected character in {:?}", rest))?;
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens, one method per precedence level
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.next) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.peek_op() == Some(op) {
+            self.next += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected {:?}", op))
+        }
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[(&str, BinaryOp)],
+        operand: fn(&mut Self) -> Result<Expr, String>,
+    ) -> Result<Expr, String> {
+        let mut lhs = operand(self)?;
+        while let Some(&(_, op)) = ops.iter().find(|(text, _)| self.peek_op() == Some(text)) {
+            self.next += 1;
+            let rhs = operand(self)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        self.binary(&[("||", BinaryOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        self.binary(&[("&&", BinaryOp::And)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        self.binary(
+            &[
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+            ],
+            Self::sum,
+        )
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        self.binary(
+            &[
+                ("+", BinaryOp::Add),
+                ("-", BinaryOp::Sub),
+                ("&", BinaryOp::BitAnd),
+                ("|", BinaryOp::BitOr),
+            ],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek_op() {
+            Some("!") => {
+                self.next += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some("-") => {
+                self.next += 1;
+                Ok(Expr::Negate(Box::new(self.unary()?)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .cloned()
+            .ok_or_else(|| String::from("Unexpected end of expression"))?;
+        self.next += 1;
+        match token {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Name(name) => Ok(Expr::Name(name)),
+            Token::Op("(") => {
+                let expr = self.or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Op("[") => {
+                let expr = self.or()?;
+                self.expect("]")?;
+                Ok(Expr::Memory(Box::new(expr)))
+            }
+            Token::Op(op) => Err(format!("Unexpected {:?}", op)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn eval(text: &str, chip8: &Chip8) -> i64 {
+        Expr::parse(text, &SymbolMap::default())
+            .unwrap()
+            .eval(chip8)
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(Ok(0x300), parse_number("0x300"));
+        assert_eq!(Ok(5), parse_number("0b101"));
+        assert_eq!(Ok(42), parse_number("42"));
+        assert!(parse_number("0xZZ").is_err());
+    }
+
+    #[test]
+    fn test_registers_and_precedence() {
+        let mut chip8 = Chip8::initialize();
+        chip8.v[3] = 0x10;
+        chip8.i = 0x301;
+        assert_eq!(1, eval("v3 == 0x10 && i > 0x300", &chip8));
+        assert_eq!(0, eval("V3 == 0x10 && I > 0x301", &chip8));
+        assert_eq!(1, eval("v0 == 1 || v3 - 0x8 == 8", &chip8));
+        assert_eq!(1, eval("!(pc != 0x200)", &chip8));
+        assert_eq!(0x12, eval("v3 | 2", &chip8));
+    }
+
+    #[test]
+    fn test_timers_and_memory() {
+        let mut chip8 = Chip8::initialize();
+        chip8.delay_timer = 3;
+        chip8.memory[0x301] = 0xAB;
+        assert_eq!(1, eval("dt <= 3 && st == 0", &chip8));
+        assert_eq!(0xAB, eval("[0x300 + 1]", &chip8));
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut chip8 = Chip8::initialize();
+        chip8.pc = 0x2A4;
+        let symbols = SymbolMap::parse("0x2A4 draw_player").unwrap();
+        let expr = Expr::parse("pc == draw_player", &symbols).unwrap();
+        assert_eq!(1, expr.eval(&chip8));
+        assert!(Expr::parse("pc == draw_player", &SymbolMap::default()).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Expr::parse("vg == 1", &SymbolMap::default()).is_err());
+        assert!(Expr::parse("v1 ==", &SymbolMap::default()).is_err());
+        assert!(Expr::parse("(v1 == 1", &SymbolMap::default()).is_err());
+        assert!(Expr::parse("v1 = 1", &SymbolMap::default()).is_err());
+        assert!(Expr::parse("v1 1", &SymbolMap::default()).is_err());
+    }
+}
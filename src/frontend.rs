@@ -0,0 +1,170 @@
+use crate::chip8::Chip8;
+use crate::image::Image;
+use crate::memview::ViewKey;
+use std::time::Duration;
+
+/// Emulator controls a frontend can trigger, usually from hotkeys
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    ToggleOsd,
+    TogglePause,
+    AdvanceFrame,
+    SoftReset,
+    HardReset,
+    FastForward(bool),
+    ToggleSlowMotion,
+    SpeedUp,
+    SpeedDown,
+    ToggleSprites,
+    ToggleMemory,
+    CloseDebugWindow,
+    Screenshot,
+    ToggleRecording,
+}
+
+/// Input collected by a frontend since it was last polled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputEvent {
+    Quit,
+    KeyDown(usize),
+    KeyUp(usize),
+    Hotkey(Hotkey),
+    /// A key for the debug window's view, e.g. to edit memory
+    ViewKey(ViewKey),
+}
+
+/// Everything the emulator needs from the host
+pub trait Frontend {
+    /// Shows the display, with lines of OSD text on top if the frontend can draw them
+    fn present(&mut self, chip8: &Chip8, overlay: &[String]);
+
+    /// Turns the buzzer on or off
+    fn set_buzzer(&mut self, on: bool);
+
+    /// Returns the input that arrived since the last poll
+    fn poll_events(&mut self) -> Vec<InputEvent>;
+
+    /// Host time since some fixed starting point
+    fn now(&self) -> Duration;
+
+    /// Waits until `now` has advanced by `duration`
+    fn sleep(&mut self, duration: Duration);
+
+    /// Describes the emulator state, e.g. in a window title
+    fn set_title(&mut self, _title: &str) {}
+
+    /// Whether the display should be presented again even though the game has not drawn
+    fn needs_redraw(&self, _chip8: &Chip8) -> bool {
+        false
+    }
+
+    /// Shows a debug view such as the sprite sheet beside the display, or
+    /// closes it for `None`
+    fn show_debug_window(&mut self, _image: Option<&Image>) {}
+
+    /// Whether someone is watching, so a crash should stay on screen rather than end the run
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+/// Frontend without any output that runs as fast as the host allows
+///
+/// Its clock only moves when the emulator sleeps, so frame pacing never waits.
+pub struct Headless {
+    time: Duration,
+}
+
+impl Headless {
+    pub fn new() -> Self {
+        Headless {
+            time: Duration::from_secs(0),
+        }
+    }
+}
+
+impl Frontend for Headless {
+    fn present(&mut self, _chip8: &Chip8, _overlay: &[String]) {}
+
+    fn set_buzzer(&mut self, _on: bool) {}
+
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        Vec::new()
+    }
+
+    fn now(&self) -> Duration {
+        self.time
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.time += duration;
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Scripted frontend for tests, recording what the emulator asked of it
+#[cfg(test)]
+pub struct TestFrontend {
+    pub time: Duration,
+    /// Events handed out on each poll, front first; polls past the end get nothing
+    pub script: Vec<Vec<InputEvent>>,
+    pub frames: Vec<Vec<u8>>,
+    pub overlays: Vec<Vec<String>>,
+    pub buzzer: Vec<bool>,
+    pub title: String,
+    pub debug_window: Option<Image>,
+}
+
+#[cfg(test)]
+impl TestFrontend {
+    pub fn new(script: Vec<Vec<InputEvent>>) -> Self {
+        TestFrontend {
+            time: Duration::from_secs(0),
+            script,
+            frames: Vec::new(),
+            overlays: Vec::new(),
+            buzzer: Vec::new(),
+            title: String::new(),
+            debug_window: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Frontend for TestFrontend {
+    fn present(&mut self, chip8: &Chip8, overlay: &[String]) {
+        self.frames.push(chip8.gfx.to_vec());
+        self.overlays.push(overlay.to_vec());
+    }
+
+    fn set_buzzer(&mut self, on: bool) {
+        self.buzzer.push(on);
+    }
+
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        if self.script.is_empty() {
+            Vec::new()
+        } else {
+            self.script.remove(0)
+        }
+    }
+
+    fn now(&self) -> Duration {
+        self.time
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.time += duration;
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+    }
+
+    fn show_debug_window(&mut self, image: Option<&Image>) {
+        self.debug_window = image.cloned();
+    }
+}
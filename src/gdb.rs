@@ -0,0 +1,660 @@
+use crate::chip8::{Chip8, Fault};
+use crate::controls::FRAME_RATE;
+use crate::utils::{from_hex, to_hex};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+/// Registers in the order GDB numbers them: `v0`-`vf`, `i`, `pc`, `sp`, `dt`, `st`
+const REGISTER_SIZES: [usize; 21] = [
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1,
+];
+
+/// Target description, so GDB knows the register layout without a built-in architecture
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.rfc.chip8">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="16"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+/// SIGTRAP, reported after a step or a breakpoint
+const SIGTRAP: u8 = 5;
+
+/// SIGINT, reported when GDB interrupts a continue
+const SIGINT: u8 = 2;
+
+/// SIGILL, reported when the machine stops on an invalid opcode
+const SIGILL: u8 = 4;
+
+/// SIGSEGV, reported for the other faults
+const SIGSEGV: u8 = 11;
+
+/// What came in from GDB
+#[derive(Debug, PartialEq, Eq)]
+enum Packet {
+    Command(String),
+    Interrupt,
+}
+
+/// GDB remote serial protocol server for one connection
+///
+/// Memory is the 4K `memory` array at address 0, and writes go through
+/// `write_memory` so the decoded instruction cache stays valid. Continuing runs
+/// `instructions_per_frame` cycles per 60Hz frame and ticks the timers between
+/// them, checking for breakpoints before each instruction. A fault stops the
+/// machine for good and is reported as SIGILL for an invalid opcode, SIGSEGV
+/// for anything else; a trapped invalid opcode stops it as SIGILL until resumed.
+pub struct GdbStub {
+    pub chip8: Chip8,
+    stream: TcpStream,
+    pending: Vec<u8>,
+    breakpoints: Vec<u16>,
+    instructions_per_frame: u32,
+    frame_cycles: u32,
+    no_ack: bool,
+}
+
+impl GdbStub {
+    pub fn new(chip8: Chip8, stream: TcpStream, instructions_per_frame: u32) -> Self {
+        // packets are small and GDB waits on each reply
+        let _ = stream.set_nodelay(true);
+        GdbStub {
+            chip8,
+            stream,
+            pending: Vec::new(),
+            breakpoints: Vec::new(),
+            instructions_per_frame,
+            frame_cycles: 0,
+            no_ack: false,
+        }
+    }
+
+    /// Serves commands until GDB detaches, kills the target or disconnects
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            let command = match self.read_packet()? {
+                Some(Packet::Command(command)) => command,
+                Some(Packet::Interrupt) => {
+                    self.send(&stop_reply(SIGINT))?;
+                    continue;
+                }
+                None => return Ok(()),
+            };
+            match self.handle(&command) {
+                Some(reply) => self.send(&reply)?,
+                None => {
+                    // `k` gets no reply
+                    return Ok(());
+                }
+            }
+            if command == "D" {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Replies to one command, or returns None to end the session
+    fn handle(&mut self, command: &str) -> Option<String> {
+        let (kind, args) = command.split_at(command.chars().next().map_or(0, char::len_utf8));
+        let reply = match kind {
+            "?" => self.stop_reply(),
+            "g" => self.read_registers(),
+            "G" => ok_or_error(self.write_registers(args)),
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(register) if register < REGISTER_SIZES.len() => {
+                    to_hex(&self.register_bytes(register))
+                }
+                _ => error(),
+            },
+            "P" => ok_or_error(self.write_register(args)),
+            "m" => self.read_memory(args).unwrap_or_else(|_| error()),
+            "M" => ok_or_error(self.write_memory(args)),
+            "c" => self.resume(),
+            "s" => {
+                self.step();
+                self.stop_reply()
+            }
+            "Z" | "z" => ok_or_error(self.set_breakpoint(kind == "Z", args)),
+            "H" => String::from("OK"),
+            "D" => String::from("OK"),
+            "k" => return None,
+            "q" | "Q" => self.query(command),
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    fn query(&mut self, command: &str) -> String {
+        if command.starts_with("qSupported") {
+            String::from("PacketSize=1000;qXfer:features:read+;QStartNoAckMode+")
+        } else if command == "QStartNoAckMode" {
+            self.no_ack = true;
+            String::from("OK")
+        } else if command == "qAttached" {
+            String::from("1")
+        } else if let Some(args) = command.strip_prefix("qXfer:features:read:target.xml:") {
+            read_xfer(TARGET_XML.as_bytes(), args).unwrap_or_else(|_| error())
+        } else {
+            String::new()
+        }
+    }
+
+    fn register_bytes(&self, register: usize) -> Vec<u8> {
+        let chip8 = &self.chip8;
+        match register {
+            0..=15 => vec![chip8.v[register]],
+            16 => chip8.i.to_le_bytes().to_vec(),
+            17 => chip8.pc.to_le_bytes().to_vec(),
+            18 => chip8.sp.to_le_bytes().to_vec(),
+            19 => vec![chip8.delay_timer],
+            _ => vec![chip8.sound_timer],
+        }
+    }
+
+    /// Refuses writes the core can't run with, i.e. `sp` past the stack
+    fn check_register(&self, register: usize, bytes: &[u8]) -> Result<(), String> {
+        if register == 18
+            && u16::from_le_bytes([bytes[0], bytes[1]]) as usize >= self.chip8.stack.len()
+        {
+            return Err(String::from("Stack pointer out of range"));
+        }
+        Ok(())
+    }
+
+    /// Sets a register through the core, so observers and watchpoints see it
+    fn set_register(&mut self, register: usize, bytes: &[u8]) {
+        let word = || u16::from_le_bytes([bytes[0], bytes[1]]);
+        let chip8 = &mut self.chip8;
+        match register {
+            0..=15 => chip8.set_v(register, bytes[0]),
+            16 => chip8.set_i(word()),
+            17 => chip8.pc = word(),
+            18 => chip8.sp = word(),
+            19 => chip8.delay_timer = bytes[0],
+            _ => chip8.sound_timer = bytes[0],
+        }
+    }
+
+    fn read_registers(&self) -> String {
+        (0..REGISTER_SIZES.len())
+            .map(|register| to_hex(&self.register_bytes(register)))
+            .collect()
+    }
+
+    fn write_registers(&mut self, args: &str) -> Result<(), String> {
+        let bytes = from_hex(args)?;
+        if bytes.len() != REGISTER_SIZES.iter().sum::<usize>() {
+            return Err(String::from("Wrong register data length"));
+        }
+        let mut values = Vec::new();
+        let mut offset = 0;
+        for &size in REGISTER_SIZES.iter() {
+            values.push(&bytes[offset..offset + size]);
+            offset += size;
+        }
+        // check them all first, so a bad packet changes nothing
+        for (register, value) in values.iter().enumerate() {
+            self.check_register(register, value)?;
+        }
+        for (register, value) in values.iter().enumerate() {
+            self.set_register(register, value);
+        }
+        Ok(())
+    }
+
+    fn write_register(&mut self, args: &str) -> Result<(), String> {
+        let (register, value) = split_pair(args, '=')?;
+        let register = parse_hex(register)?;
+        let bytes = from_hex(value)?;
+        match REGISTER_SIZES.get(register) {
+            Some(&size) if bytes.len() == size => {
+                self.check_register(register, &bytes)?;
+                self.set_register(register, &bytes);
+                Ok(())
+            }
+            _ => Err(format!("Bad register write: {:?}", args)),
+        }
+    }
+
+    fn read_memory(&self, args: &str) -> Result<String, String> {
+        let (address, length) = split_pair(args, ',')?;
+        let range = memory_range(&self.chip8, parse_hex(address)?, parse_hex(length)?)?;
+        Ok(to_hex(&self.chip8.memory[range]))
+    }
+
+    fn write_memory(&mut self, args: &str) -> Result<(), String> {
+        let (range, data) = split_pair(args, ':')?;
+        let (address, length) = split_pair(range, ',')?;
+        let bytes = from_hex(data)?;
+        let range = memory_range(&self.chip8, parse_hex(address)?, parse_hex(length)?)?;
+        if bytes.len() != range.len() {
+            return Err(String::from("Wrong memory data length"));
+        }
+        for (address, &byte) in range.zip(bytes.iter()) {
+            self.chip8.write_memory(address, byte);
+        }
+        Ok(())
+    }
+
+    /// `Z0,addr,kind` / `z0,addr,kind`; only software breakpoints are supported
+    fn set_breakpoint(&mut self, insert: bool, args: &str) -> Result<(), String> {
+        let mut parts = args.split(',');
+        if parts.next() != Some("0") {
+            return Err(String::from("Only software breakpoints are supported"));
+        }
+        let address = parse_hex(parts.next().unwrap_or(""))? as u16;
+        if insert {
+            if !self.breakpoints.contains(&address) {
+                self.breakpoints.push(address);
+            }
+        } else {
+            self.breakpoints.retain(|&breakpoint| breakpoint != address);
+        }
+        Ok(())
+    }
+
+    /// Runs one instruction, ticking the timers as if a frame had passed when one is due
+    fn step(&mut self) {
+        self.chip8.emulate_cycle();
+        self.frame_cycles += 1;
+        if self.frame_cycles >= self.instructions_per_frame {
+            self.chip8.update_timers();
+            self.frame_cycles = 0;
+        }
+    }
+
+    /// Continues until a breakpoint, a fault or an interrupt from GDB
+    ///
+    /// The instruction at the current `pc` always runs, so continuing from a
+    /// breakpoint moves past it.
+    fn resume(&mut self) -> String {
+        let frame = Duration::from_secs(1) / FRAME_RATE;
+        let mut first = true;
+        loop {
+            for _ in 0..self.instructions_per_frame {
+                if !first && self.breakpoints.contains(&self.chip8.pc) {
+                    return stop_reply(SIGTRAP);
+                }
+                first = false;
+                self.step();
+                if self.chip8.stop_cause().is_some() {
+                    return self.stop_reply();
+                }
+            }
+            match self.poll_interrupt() {
+                Ok(false) => thread::sleep(frame),
+                // a dropped connection also stops the run; the next read sees it
+                _ => return stop_reply(SIGINT),
+            }
+        }
+    }
+
+    /// Why the machine is stopped: a fault's signal, or a trap from a step or breakpoint
+    fn stop_reply(&self) -> String {
+        stop_reply(match self.chip8.stop_cause() {
+            Some(Fault::InvalidOpcode) => SIGILL,
+            Some(_) => SIGSEGV,
+            None => SIGTRAP,
+        })
+    }
+
+    /// Checks for the interrupt byte without blocking
+    fn poll_interrupt(&mut self) -> io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let mut buffer = [0; 64];
+        let result = self.stream.read(&mut buffer);
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Ok(0) => Err(io::Error::from(ErrorKind::UnexpectedEof)),
+            Ok(count) => {
+                self.pending.extend_from_slice(&buffer[..count]);
+                Ok(match self.pending.iter().position(|&byte| byte == 0x03) {
+                    Some(position) => {
+                        self.pending.remove(position);
+                        true
+                    }
+                    None => false,
+                })
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        if !self.pending.is_empty() {
+            return Ok(Some(self.pending.remove(0)));
+        }
+        let mut byte = [0];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// Reads the next packet, acknowledging it; returns None once GDB disconnects
+    fn read_packet(&mut self) -> io::Result<Option<Packet>> {
+        loop {
+            match self.next_byte()? {
+                None => return Ok(None),
+                Some(0x03) => return Ok(Some(Packet::Interrupt)),
+                Some(b'$') => {}
+                // acks, and anything else outside a packet
+                Some(_) => continue,
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.next_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+            let mut checksum = [0; 2];
+            for digit in checksum.iter_mut() {
+                *digit = match self.next_byte()? {
+                    Some(byte) => byte,
+                    None => return Ok(None),
+                };
+            }
+
+            let valid = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                == Some(checksum_of(&data));
+            if !self.no_ack {
+                self.stream.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(Some(Packet::Command(
+                    String::from_utf8_lossy(&data).into_owned(),
+                )));
+            }
+        }
+    }
+
+    /// Sends a packet, resending until GDB acknowledges it
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        loop {
+            self.stream.write_all(packet.as_bytes())?;
+            if self.no_ack {
+                return Ok(());
+            }
+            match self.next_byte()? {
+                Some(b'+') | None => return Ok(()),
+                Some(b'-') => continue,
+                Some(byte) => {
+                    // a new packet started without an ack; leave it for the next read
+                    self.pending.insert(0, byte);
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Waits for GDB on a local port and serves it
+pub fn serve(chip8: Chip8, port: u16, instructions_per_frame: u32) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("Waiting for GDB on {}", listener.local_addr()?);
+    let (stream, _) = listener.accept()?;
+    GdbStub::new(chip8, stream, instructions_per_frame).run()
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+fn error() -> String {
+    String::from("E01")
+}
+
+fn ok_or_error(result: Result<(), String>) -> String {
+    match result {
+        Ok(()) => String::from("OK"),
+        Err(_) => error(),
+    }
+}
+
+/// Serves `offset,length` of a `qXfer` object
+fn read_xfer(object: &[u8], args: &str) -> Result<String, String> {
+    let (offset, length) = split_pair(args, ',')?;
+    let offset = parse_hex(offset)?.min(object.len());
+    let end = offset.saturating_add(parse_hex(length)?).min(object.len());
+    let marker = if end == object.len() { 'l' } else { 'm' };
+    Ok(format!(
+        "{}{}",
+        marker,
+        String::from_utf8_lossy(&object[offset..end])
+    ))
+}
+
+fn memory_range(
+    chip8: &Chip8,
+    address: usize,
+    length: usize,
+) -> Result<std::ops::Range<usize>, String> {
+    match address.checked_add(length) {
+        Some(end) if end <= chip8.memory.len() => Ok(address..end),
+        _ => Err(String::from("Address out of range")),
+    }
+}
+
+fn split_pair(text: &str, separator: char) -> Result<(&str, &str), String> {
+    let mut parts = text.splitn(2, separator);
+    match (parts.next(), parts.next()) {
+        (Some(first), Some(second)) => Ok((first, second)),
+        _ => Err(format!("Expected {:?} in {:?}", separator, text)),
+    }
+}
+
+fn parse_hex(text: &str) -> Result<usize, String> {
+    usize::from_str_radix(text, 16).map_err(|_| format!("Invalid hex: {:?}", text))
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread::JoinHandle;
+
+    /// Minimal GDB: sends packets and reads replies, acknowledging them
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn send(&mut self, command: &str) -> String {
+            let packet = format!("${}#{:02x}", command, checksum_of(command.as_bytes()));
+            self.stream.write_all(packet.as_bytes()).unwrap();
+            self.reply()
+        }
+
+        fn reply(&mut self) -> String {
+            let mut reply = Vec::new();
+            let mut byte = [0];
+            // skip the ack up to the start of the reply
+            while byte[0] != b'$' {
+                self.stream.read_exact(&mut byte).unwrap();
+            }
+            loop {
+                self.stream.read_exact(&mut byte).unwrap();
+                if byte[0] == b'#' {
+                    break;
+                }
+                reply.push(byte[0]);
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum).unwrap();
+            self.stream.write_all(b"+").unwrap();
+            String::from_utf8(reply).unwrap()
+        }
+    }
+
+    /// Serves a machine running `program` on a local port, with `session` as GDB
+    fn session<T: Send + 'static>(program: &[u16], session: fn(&mut Client) -> T) -> (Chip8, T) {
+        let mut chip8 = Chip8::initialize();
+        for (i, word) in program.iter().enumerate() {
+            chip8.write_memory(0x200 + i * 2, (word >> 8) as u8);
+            chip8.write_memory(0x200 + i * 2 + 1, *word as u8);
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client: JoinHandle<T> = thread::spawn(move || {
+            let stream = TcpStream::connect(address).unwrap();
+            stream.set_nodelay(true).unwrap();
+            let mut client = Client { stream };
+            session(&mut client)
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut stub = GdbStub::new(chip8, stream, 10);
+        stub.run().unwrap();
+        (stub.chip8, client.join().unwrap())
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let (chip8, replies) = session(&[0x6005], |client| {
+            let replies = vec![
+                client.send("qSupported:multiprocess+"),
+                client.send("?"),
+                client.send("g"),
+                client.send("p11"),
+                client.send("P3=2a"),
+                client.send("m200,2"),
+                client.send("M300,2:abcd"),
+                client.send("m300,2"),
+                client.send("mfff,2"),
+                client.send("mffffffffffffffff,1"),
+                client.send("P12=1000"),
+                client.send("P12=0f00"),
+                client.send("qXfer:features:read:target.xml:0,20"),
+            ];
+            client.send("D");
+            replies
+        });
+
+        assert!(replies[0].contains("qXfer:features:read+"));
+        assert_eq!("S05", replies[1]);
+        assert_eq!(
+            format!(
+                "{}{}{}{}{}",
+                "00".repeat(16),
+                "0000",
+                "0002",
+                "0000",
+                "0000"
+            ),
+            replies[2]
+        );
+        assert_eq!("0002", replies[3], "pc should be little-endian");
+        assert_eq!("OK", replies[4]);
+        assert_eq!("6005", replies[5]);
+        assert_eq!("OK", replies[6]);
+        assert_eq!("abcd", replies[7]);
+        assert_eq!("E01", replies[8]);
+        assert_eq!("E01", replies[9], "should not overflow");
+        assert_eq!("E01", replies[10], "sp past the stack");
+        assert_eq!("OK", replies[11]);
+        assert!(replies[12].starts_with("m<?xml"));
+        assert_eq!(15, chip8.sp);
+        assert_eq!(0x2A, chip8.v[3]);
+        assert_eq!(0xCD, chip8.memory[0x301]);
+    }
+
+    #[test]
+    fn test_breakpoints_and_step() {
+        // v0 = 5, then count v1 up forever
+        let (chip8, replies) = session(&[0x6005, 0x7101, 0x1202], |client| {
+            let replies = vec![
+                client.send("Z0,204,2"),
+                client.send("c"),
+                client.send("p11"),
+                client.send("s"),
+                client.send("p11"),
+                client.send("c"),
+                client.send("p1"),
+                client.send("z0,204,2"),
+                client.send("Z1,204,2"),
+            ];
+            // `k` gets no reply
+            let packet = format!("$k#{:02x}", checksum_of(b"k"));
+            client.stream.write_all(packet.as_bytes()).unwrap();
+            replies
+        });
+
+        assert_eq!("OK", replies[0]);
+        assert_eq!("S05", replies[1]);
+        assert_eq!("0402", replies[2], "should stop before the breakpoint");
+        assert_eq!("S05", replies[3]);
+        assert_eq!("0202", replies[4], "should step over the jump");
+        assert_eq!("S05", replies[5]);
+        assert_eq!("02", replies[6], "should stop on the next loop");
+        assert_eq!("OK", replies[7]);
+        assert_eq!(
+            "E01", replies[8],
+            "should only support software breakpoints"
+        );
+        assert_eq!(5, chip8.v[0]);
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let (chip8, reply) = session(&[0x7101, 0x1200], |client| {
+            let packet = format!("$c#{:02x}", checksum_of(b"c"));
+            client.stream.write_all(packet.as_bytes()).unwrap();
+            thread::sleep(Duration::from_millis(50));
+            client.stream.write_all(&[0x03]).unwrap();
+            let reply = client.reply();
+            client.send("D");
+            reply
+        });
+
+        assert_eq!("S02", reply);
+        assert!(chip8.cycles > 0, "should have run until interrupted");
+    }
+
+    #[test]
+    fn test_faults() {
+        let (chip8, replies) = session(&[0x7101, 0x3105, 0x1200, 0xE0FF], |client| {
+            let replies = vec![client.send("c"), client.send("?"), client.send("s")];
+            client.send("D");
+            replies
+        });
+
+        assert_eq!(vec!["S04", "S04", "S04"], replies);
+        assert_eq!(Some(Fault::InvalidOpcode), chip8.fault);
+        assert_eq!(0x206, chip8.pc);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::osd::text_pixels;
+use crate::palette::Rgb;
+use std::fs;
+
+/// An RGB picture, for debug views and exported files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// Rows top to bottom, each left to right
+    pub pixels: Vec<Rgb>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, background: Rgb) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![background; (width * height) as usize],
+        }
+    }
+
+    /// Sets a pixel, ignoring ones outside the image
+    pub fn set(&mut self, x: u32, y: u32, color: Rgb) {
+        if x < self.width && y < self.height {
+            self.pixels[(y * self.width + x) as usize] = color;
+        }
+    }
+
+    pub fn fill(&mut self, x: u32, y: u32, width: u32, height: u32, color: Rgb) {
+        for row in y..y + height {
+            for column in x..x + width {
+                self.set(column, row, color);
+            }
+        }
+    }
+
+    /// Writes a line of OSD font text with its top left at (`x`, `y`), each font
+    /// pixel `size` pixels square
+    pub fn text(&mut self, x: u32, y: u32, text: &str, color: Rgb, size: u32) {
+        for (column, row) in text_pixels(text) {
+            let (column, row) = (column as u32 * size, row as u32 * size);
+            self.fill(x + column, y + row, size, size, color);
+        }
+    }
+
+    /// The pixels as packed RGB bytes
+    pub fn rgb_bytes(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&Rgb(r, g, b)| [r, g, b])
+            .collect()
+    }
+
+    /// Encodes the image as a PNG
+    ///
+    /// The image data is stored uncompressed, which keeps the encoder small;
+    /// debug pictures are small enough that it does not matter.
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut scanlines = Vec::with_capacity(((self.width * 3 + 1) * self.height) as usize);
+        let row_bytes = self.width as usize * 3;
+        for row in self
+            .rgb_bytes()
+            .chunks(row_bytes.max(1))
+            .take(self.height as usize)
+        {
+            // filter type 0, none
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+
+        let mut header = Vec::new();
+        header.extend(self.width.to_be_bytes());
+        header.extend(self.height.to_be_bytes());
+        // 8 bits per channel, RGB, deflate, standard filters, not interlaced
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut png = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+        chunk(&mut png, b"IHDR", &header);
+        chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_png()).map_err(|e| format!("Error writing {}: {:?}", path, e))
+    }
+}
+
+/// Appends a PNG chunk: length, type, data and the CRC of type and data
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// Wraps data in a zlib stream of uncompressed deflate blocks
+pub fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32K window, no preset dictionary, fastest
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        out.push(last as u8);
+        out.extend(length.to_le_bytes());
+        out.extend((!length).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// The CRC-32 PNG and gzip use
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_drawing() {
+        let mut image = Image::new(12, 8, Rgb(0, 0, 0));
+        image.fill(10, 6, 4, 4, Rgb(1, 2, 3));
+        assert_eq!(Rgb(1, 2, 3), image.pixels[6 * 12 + 10]);
+        assert_eq!(
+            4,
+            image.pixels.iter().filter(|&&p| p == Rgb(1, 2, 3)).count()
+        );
+
+        image.text(0, 0, "1", Rgb(9, 9, 9), 1);
+        // the top row of "1" is ..#.
+        assert_eq!(Rgb(9, 9, 9), image.pixels[2]);
+        assert_eq!(Rgb(0, 0, 0), image.pixels[1]);
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+        assert_eq!(0x091E_01DE, adler32(b"123456789"));
+    }
+
+    #[test]
+    fn test_png() {
+        let png = Image::new(2, 1, Rgb(255, 0, 0)).to_png();
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &png[..8]);
+        assert_eq!(b"IHDR", &png[12..16]);
+        assert_eq!([0, 0, 0, 2, 0, 0, 0, 1, 8, 2], png[16..26]);
+        // the only block holds the filter byte and both pixels
+        let idat = &png[33..];
+        assert_eq!(b"IDAT", &idat[4..8]);
+        assert_eq!([0x78, 0x01, 1, 7, 0, !7, 0xFF], idat[8..15]);
+        assert_eq!([0, 255, 0, 0, 255, 0, 0], idat[15..22]);
+        assert!(png.ends_with(b"IEND\xae\x42\x60\x82"));
+    }
+}
@@ -1,20 +1,45 @@
 use crate::chip8::Chip8;
+use crate::frontend::{Frontend, Hotkey, InputEvent};
+use crate::image::Image;
+use crate::keypad::{HostKey, Keymap};
+use crate::memview::ViewKey;
+use crate::osd::{text_pixels, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::palette::{Palette, Phosphor};
+use crate::platform::Platform;
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
-use sdl2::Sdl;
+use sdl2::{EventPump, Sdl, VideoSubsystem};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub struct InputOutput {
     pub canvas: Canvas<Window>,
+    /// A second window for debug views, while one is shown
+    debug_canvas: Option<Canvas<Window>>,
+    video: VideoSubsystem,
     device: AudioDevice<SquareWave>,
+    event_pump: EventPump,
+    scale: u32,
+    palette: Palette,
+    phosphor: Phosphor,
+    keymap: Keymap,
+    start: Instant,
 }
 
 impl InputOutput {
     /// Initializes Core
-    pub fn initialize(sdl_context: &Sdl, scale: u32) -> Self {
+    pub fn initialize(
+        sdl_context: &Sdl,
+        scale: u32,
+        palette: Palette,
+        phosphor_frames: u32,
+        keymap: Keymap,
+    ) -> Self {
         // Set up audio
         let audio_subsystem = sdl_context.audio().unwrap();
         let desired_spec = AudioSpecDesired {
@@ -44,141 +69,85 @@ impl InputOutput {
             .unwrap();
         let mut canvas = window.into_canvas().build().unwrap();
 
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        let background = palette.background;
+        canvas.set_draw_color(Color::RGB(background.0, background.1, background.2));
         canvas.clear();
         canvas.present();
 
-        Self { canvas, device }
-    }
-
-    /// Handles key down event
-    pub fn handle_key_down(&mut self, chip8: &mut Chip8, keycode: Keycode) {
-        match keycode {
-            Keycode::Num1 => {
-                chip8.set_key(0x1, true);
-            }
-            Keycode::Num2 => {
-                chip8.set_key(0x2, true);
-            }
-            Keycode::Num3 => {
-                chip8.set_key(0x3, true);
-            }
-            Keycode::Num4 => {
-                chip8.set_key(0xC, true);
-            }
-            Keycode::Q => {
-                chip8.set_key(0x4, true);
-            }
-            Keycode::W => {
-                chip8.set_key(0x5, true);
-            }
-            Keycode::E => {
-                chip8.set_key(0x6, true);
-            }
-            Keycode::R => {
-                chip8.set_key(0xD, true);
-            }
-            Keycode::A => {
-                chip8.set_key(0x7, true);
-            }
-            Keycode::S => {
-                chip8.set_key(0x8, true);
-            }
-            Keycode::D => {
-                chip8.set_key(0x9, true);
-            }
-            Keycode::F => {
-                chip8.set_key(0xE, true);
-            }
-            Keycode::Z => {
-                chip8.set_key(0xA, true);
-            }
-            Keycode::X => {
-                chip8.set_key(0x0, true);
-            }
-            Keycode::C => {
-                chip8.set_key(0xB, true);
-            }
-            Keycode::V => {
-                chip8.set_key(0xF, true);
-            }
-            _ => {}
+        Self {
+            canvas,
+            debug_canvas: None,
+            video: video_subsystem,
+            device,
+            event_pump: sdl_context.event_pump().unwrap(),
+            scale,
+            palette,
+            phosphor: Phosphor::new(64 * 32, phosphor_frames),
+            keymap,
+            start: Instant::now(),
         }
     }
 
-    /// Handles key up event
-    pub fn handle_key_up(&mut self, chip8: &mut Chip8, keycode: Keycode) {
-        match keycode {
-            Keycode::Num1 => {
-                chip8.set_key(0x1, false);
-            }
-            Keycode::Num2 => {
-                chip8.set_key(0x2, false);
-            }
-            Keycode::Num3 => {
-                chip8.set_key(0x3, false);
-            }
-            Keycode::Num4 => {
-                chip8.set_key(0xC, false);
-            }
-            Keycode::Q => {
-                chip8.set_key(0x4, false);
-            }
-            Keycode::W => {
-                chip8.set_key(0x5, false);
-            }
-            Keycode::E => {
-                chip8.set_key(0x6, false);
-            }
-            Keycode::R => {
-                chip8.set_key(0xD, false);
-            }
-            Keycode::A => {
-                chip8.set_key(0x7, false);
-            }
-            Keycode::S => {
-                chip8.set_key(0x8, false);
-            }
-            Keycode::D => {
-                chip8.set_key(0x9, false);
-            }
-            Keycode::F => {
-                chip8.set_key(0xE, false);
-            }
-            Keycode::Z => {
-                chip8.set_key(0xA, false);
-            }
-            Keycode::X => {
-                chip8.set_key(0x0, false);
-            }
-            Keycode::C => {
-                chip8.set_key(0xB, false);
-            }
-            Keycode::V => {
-                chip8.set_key(0xF, false);
-            }
-            _ => {}
+    /// Draws the CPU's display to the canvas, with lines of OSD text on top
+    ///
+    /// The window takes the platform's display size, and CHIP-8X pixels take
+    /// their zone's color on its background instead of the palette.
+    pub fn draw_canvas(&mut self, chip8: &Chip8, scale: u32, overlay: &[String]) {
+        let (width, height) = chip8.platform.display();
+        let size = (width as u32 * scale, height as u32 * scale);
+        if self.canvas.window().size() != size {
+            let _ = self.canvas.window_mut().set_size(size.0, size.1);
         }
-    }
+        let brightness = self.phosphor.update(&chip8.gfx);
+        for (i, &pixel_brightness) in brightness.iter().enumerate() {
+            let x = (i % width) * scale as usize;
+            let y = (i / width) * scale as usize;
 
-    /// Draws the CPU's display to the canvas
-    pub fn draw_canvas(&mut self, chip8: &mut Chip8, scale: u32) {
-        for i in 0..64 * 32 {
-            let current_pixel = chip8.gfx[i];
-            let x = (i % 64) * scale as usize;
-            let y = (i / 64) * scale as usize;
-
-            self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-            if current_pixel == 1 {
-                self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-            }
+            let palette = match chip8.platform {
+                Platform::Chip8x => {
+                    let zone = (i / width) * 8 + (i % width) / 8;
+                    Palette::chip8x(chip8.colors[zone], chip8.background)
+                }
+                _ => self.palette,
+            };
+            let color = palette.shade(pixel_brightness);
+            self.canvas
+                .set_draw_color(Color::RGB(color.0, color.1, color.2));
             let _ = self
                 .canvas
                 .fill_rect(Rect::new(x as i32, y as i32, scale, scale));
         }
+        self.draw_overlay(overlay, scale);
         self.canvas.present();
     }
 
+    /// Draws text in the top left corner, over a box in the background color
+    fn draw_overlay(&mut self, lines: &[String], scale: u32) {
+        let size = (scale / 4).max(1);
+        let background = self.palette.background;
+        let foreground = self.palette.foreground;
+        for (row, line) in lines.iter().enumerate() {
+            let top = (row * GLYPH_HEIGHT) as i32 * size as i32;
+            let width = (line.chars().count() * GLYPH_WIDTH + 1) as u32 * size;
+            self.canvas
+                .set_draw_color(Color::RGB(background.0, background.1, background.2));
+            let _ = self
+                .canvas
+                .fill_rect(Rect::new(0, top, width, GLYPH_HEIGHT as u32 * size));
+
+            self.canvas
+                .set_draw_color(Color::RGB(foreground.0, foreground.1, foreground.2));
+            for (x, y) in text_pixels(line) {
+                let _ = self.canvas.fill_rect(Rect::new(
+                    ((x + 1) as u32 * size) as i32,
+                    top + ((y + 1) as u32 * size) as i32,
+                    size,
+                    size,
+                ));
+            }
+        }
+    }
+
     /// Plays a beep sound
     pub fn play_sound(&mut self) {
         self.device.resume();
@@ -190,6 +159,231 @@ impl InputOutput {
     }
 }
 
+impl Frontend for InputOutput {
+    fn present(&mut self, chip8: &Chip8, overlay: &[String]) {
+        self.draw_canvas(chip8, self.scale, overlay);
+    }
+
+    fn set_buzzer(&mut self, on: bool) {
+        if on {
+            self.play_sound();
+        } else {
+            self.stop_sound();
+        }
+    }
+
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        let keymap = &self.keymap;
+        for event in self.event_pump.poll_iter() {
+            let input = match event {
+                // with a debug window open, closing either window no longer quits
+                Event::Window {
+                    win_event: WindowEvent::Close,
+                    window_id,
+                    ..
+                } if window_id == self.canvas.window().id() => Some(InputEvent::Quit),
+                Event::Window {
+                    win_event: WindowEvent::Close,
+                    ..
+                } => Some(InputEvent::Hotkey(Hotkey::CloseDebugWindow)),
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => Some(InputEvent::Quit),
+                // the debug window takes keys for its view instead of the keypad
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat,
+                    window_id,
+                    ..
+                } if window_id != self.canvas.window().id() => view_key(keycode)
+                    .map(InputEvent::ViewKey)
+                    .or_else(|| hotkey(keycode, keymod, repeat).map(InputEvent::Hotkey)),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat,
+                    ..
+                } => hotkey(keycode, keymod, repeat)
+                    .map(InputEvent::Hotkey)
+                    .or_else(|| keypad_key(keycode, keymap).map(InputEvent::KeyDown)),
+                Event::KeyUp {
+                    keycode: Some(Keycode::Tab),
+                    ..
+                } => Some(InputEvent::Hotkey(Hotkey::FastForward(false))),
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => keypad_key(keycode, keymap).map(InputEvent::KeyUp),
+                _ => None,
+            };
+            events.extend(input);
+        }
+        events
+    }
+
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+
+    fn set_title(&mut self, title: &str) {
+        let _ = self.canvas.window_mut().set_title(title);
+    }
+
+    fn show_debug_window(&mut self, image: Option<&Image>) {
+        let image = match image {
+            Some(image) => image,
+            None => {
+                self.debug_canvas = None;
+                return;
+            }
+        };
+        if self.debug_canvas.is_none() {
+            let window = self
+                .video
+                .window("rfc chip8 debug", image.width, image.height)
+                .build();
+            self.debug_canvas = window
+                .ok()
+                .and_then(|window| window.into_canvas().build().ok());
+        }
+        let canvas = match &mut self.debug_canvas {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        if canvas.window().size() != (image.width, image.height) {
+            let _ = canvas.window_mut().set_size(image.width, image.height);
+        }
+        let creator = canvas.texture_creator();
+        if let Ok(mut texture) =
+            creator.create_texture_static(PixelFormatEnum::RGB24, image.width, image.height)
+        {
+            let _ = texture.update(None, &image.rgb_bytes(), image.width as usize * 3);
+            let _ = canvas.copy(&texture, None, None);
+        }
+        canvas.present();
+    }
+
+    fn needs_redraw(&self, chip8: &Chip8) -> bool {
+        // pixels that were turned off may still be fading out
+        self.phosphor.is_fading(&chip8.gfx)
+    }
+}
+
+/// Maps emulator hotkeys
+///
+/// - F1 - toggle the OSD
+/// - P - pause or resume
+/// - N - advance one frame while paused
+/// - F5 - soft reset, reloading the ROM but keeping the rest of memory
+/// - Shift+F5 - hard reset, starting from a fresh machine
+/// - Tab (held) - fast-forward
+/// - M - toggle slow motion
+/// - `=` / `-` - more or fewer instructions per frame
+/// - F2 - show or hide the sprite sheet in a second window
+/// - F3 - show or hide the memory viewer in a second window
+/// - F12 - save a screenshot next to the ROM
+/// - Shift+F12 - start or stop recording a GIF next to the ROM
+fn hotkey(keycode: Keycode, keymod: Mod, repeat: bool) -> Option<Hotkey> {
+    let hotkey = match keycode {
+        Keycode::F1 => Hotkey::ToggleOsd,
+        Keycode::P => Hotkey::TogglePause,
+        Keycode::N => Hotkey::AdvanceFrame,
+        Keycode::F5 if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => Hotkey::HardReset,
+        Keycode::F5 => Hotkey::SoftReset,
+        Keycode::Tab => Hotkey::FastForward(true),
+        Keycode::M => Hotkey::ToggleSlowMotion,
+        Keycode::Equals => Hotkey::SpeedUp,
+        Keycode::Minus => Hotkey::SpeedDown,
+        Keycode::F2 => Hotkey::ToggleSprites,
+        Keycode::F3 => Hotkey::ToggleMemory,
+        Keycode::F12 if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => {
+            Hotkey::ToggleRecording
+        }
+        Keycode::F12 => Hotkey::Screenshot,
+        _ => return None,
+    };
+
+    // holding a toggle would flip it back and forth; none of these keys are on the keypad
+    let toggle = matches!(
+        hotkey,
+        Hotkey::ToggleOsd
+            | Hotkey::TogglePause
+            | Hotkey::SoftReset
+            | Hotkey::HardReset
+            | Hotkey::ToggleSlowMotion
+            | Hotkey::ToggleSprites
+            | Hotkey::ToggleMemory
+            | Hotkey::Screenshot
+            | Hotkey::ToggleRecording
+    );
+    if repeat && toggle {
+        return None;
+    }
+    Some(hotkey)
+}
+
+/// Maps keys pressed in the debug window: arrows, Page Up/Down, Tab and
+/// Backspace move around, and hex digits edit
+fn view_key(keycode: Keycode) -> Option<ViewKey> {
+    let key = match keycode {
+        Keycode::Up => ViewKey::Up,
+        Keycode::Down => ViewKey::Down,
+        Keycode::Left => ViewKey::Left,
+        Keycode::Right => ViewKey::Right,
+        Keycode::PageUp => ViewKey::PageUp,
+        Keycode::PageDown => ViewKey::PageDown,
+        Keycode::Tab => ViewKey::Tab,
+        Keycode::Backspace => ViewKey::Backspace,
+        _ => {
+            let digit = std::char::from_u32(keycode as u32)?.to_digit(16)?;
+            ViewKey::Digit(digit as u8)
+        }
+    };
+    Some(key)
+}
+
+/// Maps an SDL keycode to its hex keypad key; printable SDL keycodes are their ASCII values
+///
+/// The numeric keypad, laid out like the hex keypad, is CHIP-8X's second
+/// keypad, keys 0x10-0x1F. Other keys go through the ROM's keymap.
+fn keypad_key(keycode: Keycode, keymap: &Keymap) -> Option<usize> {
+    let second = match keycode {
+        Keycode::Kp7 => 0x1,
+        Keycode::Kp8 => 0x2,
+        Keycode::Kp9 => 0x3,
+        Keycode::KpDivide => 0xC,
+        Keycode::Kp4 => 0x4,
+        Keycode::Kp5 => 0x5,
+        Keycode::Kp6 => 0x6,
+        Keycode::KpMultiply => 0xD,
+        Keycode::Kp1 => 0x7,
+        Keycode::Kp2 => 0x8,
+        Keycode::Kp3 => 0x9,
+        Keycode::KpMinus => 0xE,
+        Keycode::Kp0 => 0xA,
+        Keycode::KpPeriod => 0x0,
+        Keycode::KpEnter => 0xB,
+        Keycode::KpPlus => 0xF,
+        Keycode::Up => return keymap.key(HostKey::Up),
+        Keycode::Down => return keymap.key(HostKey::Down),
+        Keycode::Left => return keymap.key(HostKey::Left),
+        Keycode::Right => return keymap.key(HostKey::Right),
+        _ => {
+            let c = std::char::from_u32(keycode as u32)?;
+            return keymap.key(HostKey::Char(c));
+        }
+    };
+    Some(0x10 + second)
+}
+
 struct SquareWave {
     phase_inc: f32,
     phase: f32,
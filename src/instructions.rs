@@ -1,42 +1,78 @@
 extern crate rand;
-use crate::chip8::Chip8;
+use crate::chip8::{Chip8, Fault};
+use crate::decode::Instruction;
+use crate::platform::Platform;
+use crate::quirks::{InvalidOpcode, MachineCode};
 use crate::utils::*;
+use crate::vip;
 use std::num::Wrapping;
 
 /// 0nnn - Jump to a machine code routine at nnn.
-pub fn sys_addr(chip8: &mut Chip8) {
-    chip8.pc = chip8.opcode & 0x0FFF;
+///
+/// The platforms' own routines are built in: HIRES clears its display with
+/// `0230` and CHIP-8X steps the background color with `02A0`.
+pub fn sys_addr(chip8: &mut Chip8, ins: Instruction) {
+    match (chip8.platform, ins.nnn) {
+        (Platform::Hires, 0x230) => return cls(chip8, ins),
+        (Platform::Chip8x, 0x2A0) => {
+            chip8.background = (chip8.background + 1) % 4;
+            chip8.should_draw = true;
+            chip8.pc += 2;
+            return;
+        }
+        _ => {}
+    }
+    match chip8.quirks.machine_code {
+        MachineCode::Ignore => chip8.pc += 2,
+        MachineCode::Error => chip8.fault(Fault::MachineCode),
+        MachineCode::Vip => vip::call_machine_code(chip8, ins.nnn),
+    }
 }
 
 /// 00E0 - Clear the display.
-pub fn cls(chip8: &mut Chip8) {}
+pub fn cls(chip8: &mut Chip8, _ins: Instruction) {
+    chip8.gfx.fill(0);
+    chip8.should_draw = true;
+    chip8.pc += 2;
+}
 
 /// 00EE - Return from a subroutine.
-pub fn ret(chip8: &mut Chip8) {
+pub fn ret(chip8: &mut Chip8, _ins: Instruction) {
+    if chip8.sp == 0 {
+        chip8.fault(Fault::StackUnderflow);
+        return;
+    }
+    let from = chip8.pc;
     chip8.pc = chip8.stack[chip8.sp as usize];
     chip8.sp -= 1;
     chip8.pc += 2;
+    let to = chip8.pc;
+    chip8.notify(|observer| observer.ret(from, to));
 }
 
 /// `1nnn` - Jump to location nnn.
-pub fn jp_addr(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    chip8.pc = vars.nnn;
+pub fn jp_addr(chip8: &mut Chip8, ins: Instruction) {
+    chip8.pc = ins.nnn;
 }
 
 /// `2nnn` - Call subroutine at nnn.
-pub fn call_addr(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
+pub fn call_addr(chip8: &mut Chip8, ins: Instruction) {
+    if chip8.sp as usize + 1 >= chip8.stack.len() {
+        chip8.fault(Fault::StackOverflow);
+        return;
+    }
     chip8.sp += 1;
     chip8.stack[chip8.sp as usize] = chip8.pc;
-    chip8.pc = vars.nnn;
+    let from = chip8.pc;
+    chip8.pc = ins.nnn;
+    chip8.notify(|observer| observer.call(from, ins.nnn));
 }
 
 /// `3xkk` - Skip next instruction if Vx = kk.
-pub fn se_vx_byte(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
+pub fn se_vx_byte(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
 
-    if chip8.v[vars.x] == vars.kk {
+    if chip8.v[x] == ins.kk {
         chip8.pc += 4;
     } else {
         chip8.pc += 2;
@@ -44,10 +80,10 @@ pub fn se_vx_byte(chip8: &mut Chip8) {
 }
 
 /// `4xkk` - Skip next instruction if Vx != kk.
-pub fn sne_vx_byte(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
+pub fn sne_vx_byte(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
 
-    if chip8.v[vars.x] != vars.kk {
+    if chip8.v[x] != ins.kk {
         chip8.pc += 4;
     } else {
         chip8.pc += 2;
@@ -55,10 +91,11 @@ pub fn sne_vx_byte(chip8: &mut Chip8) {
 }
 
 /// `5xy0` - Skip next instruction if Vx = Vy.
-pub fn se_vx_vy(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
+pub fn se_vx_vy(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let y = ins.y as usize;
 
-    if chip8.v[vars.x] == chip8.v[vars.y] {
+    if chip8.v[x] == chip8.v[y] {
         chip8.pc += 4;
     } else {
         chip8.pc += 2;
@@ -66,124 +103,132 @@ pub fn se_vx_vy(chip8: &mut Chip8) {
 }
 
 /// `6xkk` - Set Vx = kk.
-pub fn ld_vx_byte(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    chip8.v[vars.x] = vars.kk;
+pub fn ld_vx_byte(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    chip8.set_v(x, ins.kk);
     chip8.pc += 2;
 }
 
 /// `7xkk` - Set Vx = Vx + kk.
-pub fn add_vx_byte(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    let vx = chip8.v[vars.x] as u16;
-    chip8.v[vars.x] = (vx + vars.kk as u16) as u8;
+pub fn add_vx_byte(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let vx = chip8.v[x] as u16;
+    chip8.set_v(x, (vx + ins.kk as u16) as u8);
     chip8.pc += 2;
 }
 
 /// `8xy0` - Set Vx = Vy.
-pub fn ld_vx_vy(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    chip8.v[vars.x] = chip8.v[vars.y];
+pub fn ld_vx_vy(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let y = ins.y as usize;
+    chip8.set_v(x, chip8.v[y]);
     chip8.pc += 2;
 }
 
 /// `8xy1` - Set Vx = Vx OR Vy.
-pub fn or_vx_vy(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    chip8.v[vars.x] = chip8.v[vars.x] | chip8.v[vars.y];
+pub fn or_vx_vy(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let y = ins.y as usize;
+    chip8.set_v(x, chip8.v[x] | chip8.v[y]);
     chip8.pc += 2;
 }
 
 /// `8xy2` - Set Vx = Vx AND Vy.
-pub fn and_vx_vy(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    chip8.v[vars.x] = chip8.v[vars.x] & chip8.v[vars.y];
+pub fn and_vx_vy(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let y = ins.y as usize;
+    chip8.set_v(x, chip8.v[x] & chip8.v[y]);
     chip8.pc += 2;
 }
 
 /// `8xy3` - Set Vx = Vx XOR Vy.
-pub fn xor_vx_vy(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    chip8.v[vars.x] = chip8.v[vars.x] ^ chip8.v[vars.y];
+pub fn xor_vx_vy(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let y = ins.y as usize;
+    chip8.set_v(x, chip8.v[x] ^ chip8.v[y]);
     chip8.pc += 2;
 }
 
 /// `8xy4` - Set Vx = Vx + Vy, set VF = carry.
-pub fn add_vx_vy(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    let vx: u16 = chip8.v[vars.x].into();
-    let vy: u16 = chip8.v[vars.y].into();
+pub fn add_vx_vy(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let y = ins.y as usize;
+    let vx: u16 = chip8.v[x].into();
+    let vy: u16 = chip8.v[y].into();
     let mut sum = vx + vy;
 
     if sum > 255 {
         sum = sum & 0x00FF;
-        chip8.v[0xF] = 1;
+        chip8.set_v(0xF, 1);
     }
 
-    chip8.v[vars.x] = sum as u8;
+    chip8.set_v(x, sum as u8);
     chip8.pc += 2;
 }
 
 /// `8xy5` - Set Vx = Vx - Vy, set VF = NOT borrow.
-pub fn sub_vx_vy(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    chip8.v[0xF] = (chip8.v[vars.x] > chip8.v[vars.y]) as u8;
-
-    let lhs = Wrapping(chip8.v[vars.x]);
-    let rhs = Wrapping(chip8.v[vars.y]);
-    chip8.v[vars.x] = (lhs - rhs).0;
+pub fn sub_vx_vy(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let y = ins.y as usize;
+    chip8.set_v(0xF, (chip8.v[x] > chip8.v[y]) as u8);
+
+    let lhs = Wrapping(chip8.v[x]);
+    let rhs = Wrapping(chip8.v[y]);
+    chip8.set_v(x, (lhs - rhs).0);
     chip8.pc += 2;
 }
 
 /// `8xy6` - Set Vx = Vx SHR 1.
-pub fn shr_vx_vy(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    match chip8.v[vars.x] & 0b1 {
+pub fn shr_vx_vy(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    match chip8.v[x] & 0b1 {
         0b1 => {
-            chip8.v[0xF] = 1;
-            chip8.v[vars.x] = chip8.v[vars.x] >> 1;
+            chip8.set_v(0xF, 1);
+            chip8.set_v(x, chip8.v[x] >> 1);
         }
         _ => {
-            chip8.v[0xF] = 0;
+            chip8.set_v(0xF, 0);
         }
     }
     chip8.pc += 2;
 }
 
 /// `8xy7` - Set Vx = Vy - Vx, set VF = NOT borrow.
-pub fn subn_vx_vy(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    match chip8.v[vars.y] > chip8.v[vars.x] {
+pub fn subn_vx_vy(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let y = ins.y as usize;
+    match chip8.v[y] > chip8.v[x] {
         true => {
-            chip8.v[vars.x] = chip8.v[vars.y] - chip8.v[vars.x];
-            chip8.v[0xF] = 1;
+            chip8.set_v(x, chip8.v[y] - chip8.v[x]);
+            chip8.set_v(0xF, 1);
         }
         false => {
-            chip8.v[0xF] = 0;
+            chip8.set_v(0xF, 0);
         }
     }
     chip8.pc += 2;
 }
 
 /// `8xyE` - Set Vx = Vx SHL 1.
-pub fn shl_vx_vy(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    match chip8.v[vars.x] >> 7 {
+pub fn shl_vx_vy(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    match chip8.v[x] >> 7 {
         1 => {
-            chip8.v[0xF] = 1;
+            chip8.set_v(0xF, 1);
         }
         _ => {
-            chip8.v[0xF] = 0;
+            chip8.set_v(0xF, 0);
         }
     }
-    chip8.v[vars.x] = chip8.v[vars.x] << 1;
+    chip8.set_v(x, chip8.v[x] << 1);
     chip8.pc += 2;
 }
 
 /// `9xy0` - Skip next instruction if Vx != Vy.
-pub fn sne_vx_vy(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    if chip8.v[vars.x] != chip8.v[vars.y] {
+pub fn sne_vx_vy(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let y = ins.y as usize;
+    if chip8.v[x] != chip8.v[y] {
         chip8.pc += 4;
     } else {
         chip8.pc += 2;
@@ -191,41 +236,84 @@ pub fn sne_vx_vy(chip8: &mut Chip8) {
 }
 
 /// `Annn` - Set I = nnn.
-pub fn ld_i_addr(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    chip8.i = vars.nnn;
+pub fn ld_i_addr(chip8: &mut Chip8, ins: Instruction) {
+    chip8.set_i(ins.nnn);
     chip8.pc += 2;
 }
 
 /// `Bnnn` - Jump to location nnn + V0.
-pub fn jp_v0_addr(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    chip8.pc = vars.nnn + chip8.v[0x0] as u16;
+pub fn jp_v0_addr(chip8: &mut Chip8, ins: Instruction) {
+    if chip8.platform == Platform::Chip8x {
+        return set_color(chip8, ins);
+    }
+    chip8.pc = ins.nnn + chip8.v[0x0] as u16;
+}
+
+/// CHIP-8X `Bxy0` - Color zones of 8x4 pixels with color Vy; the low nibbles
+/// of Vx and Vx+1 are the first zone's column and row, the high nibbles how
+/// many more zones the area spans across and down.
+///
+/// CHIP-8X `Bxyn` - Color n rows of 8 pixels with color Vx+1, starting with
+/// the zone holding pixel (Vx, Vy).
+fn set_color(chip8: &mut Chip8, ins: Instruction) {
+    let (x, y) = (ins.x as usize, ins.y as usize);
+    let (columns, rows, color) = if ins.n == 0 {
+        let (across, down) = (chip8.v[x], chip8.v[(x + 1) % 16]);
+        let columns = (across & 0xF)..=(across & 0xF) + (across >> 4);
+        let first = (down & 0xF) * 4;
+        let rows = first..=first + (down >> 4) * 4 + 3;
+        (columns, rows, chip8.v[y])
+    } else {
+        let column = chip8.v[x] / 8;
+        let first = chip8.v[y];
+        let rows = first..=first.saturating_add(ins.n - 1);
+        (column..=column, rows, chip8.v[(x + 1) % 16])
+    };
+    for row in rows.map(usize::from).filter(|&row| row < 32) {
+        for column in columns
+            .clone()
+            .map(usize::from)
+            .filter(|&column| column < 8)
+        {
+            chip8.colors[row * 8 + column] = color & 0x7;
+        }
+    }
+    chip8.should_draw = true;
+    chip8.pc += 2;
 }
 
 /// `Cxkk` - Set Vx = random byte AND kk.
-pub fn rnd_vx_byte(chip8: &mut Chip8, rnd_fn: fn() -> u8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    chip8.v[vars.x] = vars.kk & rnd_fn();
+pub fn rnd_vx_byte(chip8: &mut Chip8, ins: Instruction, rnd_fn: fn(&mut Chip8) -> u8) {
+    let x = ins.x as usize;
+    let random = rnd_fn(chip8);
+    chip8.set_v(x, ins.kk & random);
     chip8.pc += 2;
 }
 
 /// `Dxyn` - Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
-pub fn drw_vx_vy_nibble(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    let vx = chip8.v[vars.x] as usize;
-    let vy = chip8.v[vars.y] as usize;
-    let n = vars.nibbles[3];
+pub fn drw_vx_vy_nibble(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let y = ins.y as usize;
+    let vx = chip8.v[x] as usize;
+    let vy = chip8.v[y] as usize;
+    let n = ins.n as usize;
     let sprite_i = chip8.i as usize;
-    for (i, &sprite) in chip8.memory[sprite_i..sprite_i + n].iter().enumerate() {
-        let row = ((vy + i) % 32) * 64;
+    if !chip8.check_memory(sprite_i, n) {
+        return;
+    }
+    let (width, height) = chip8.platform.display();
+    let mut collision = false;
+    for i in 0..n {
+        let sprite = chip8.read_memory(sprite_i + i);
+        let row = ((vy + i) % height) * width;
         for (j, &new_bit) in into_bit_vec(sprite).iter().enumerate() {
-            let offset = (vx + j) % 64;
+            let offset = (vx + j) % width;
             let bit_index = row + offset;
             let old_bit = chip8.gfx[bit_index];
 
             if old_bit == 1 && new_bit == 1 {
-                chip8.v[0xF] = 1;
+                chip8.set_v(0xF, 1);
+                collision = true;
             }
 
             let result = old_bit ^ new_bit;
@@ -233,77 +321,172 @@ pub fn drw_vx_vy_nibble(chip8: &mut Chip8) {
         }
     }
 
+    chip8.notify(|observer| observer.draw(vx as u8, vy as u8, sprite_i as u16, n as u8, collision));
     chip8.should_draw = true;
     chip8.pc += 2;
 }
 
 /// `Ex9E` - Skip next instruction if key with the value of Vx is pressed.
-pub fn skp_vx(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
-    if chip8.key[vars.x] == true {
+pub fn skp_vx(chip8: &mut Chip8, ins: Instruction) {
+    let key = (chip8.v[ins.x as usize] & 0xF) as usize;
+    if chip8.take_key(key) {
+        chip8.pc += 4;
+    } else {
         chip8.pc += 2;
     }
 }
 
 /// `ExA1` - Skip next instruction if key with the value of Vx is not pressed.
-pub fn sknp_vx(chip8: &mut Chip8) {}
+pub fn sknp_vx(chip8: &mut Chip8, ins: Instruction) {
+    let key = (chip8.v[ins.x as usize] & 0xF) as usize;
+    if chip8.take_key(key) {
+        chip8.pc += 2;
+    } else {
+        chip8.pc += 4;
+    }
+}
 
 /// `Fx07` - Set Vx = delay timer value.
-pub fn ld_vx_dt(chip8: &mut Chip8) {}
+pub fn ld_vx_dt(chip8: &mut Chip8, ins: Instruction) {
+    chip8.set_v(ins.x as usize, chip8.delay_timer);
+    chip8.pc += 2;
+}
 
 /// `Fx0A` - Wait for a key press, store the value of the key in Vx.
-pub fn ld_vx_k(chip8: &mut Chip8) {}
+///
+/// Like the COSMAC VIP this waits for the key to be released again, unless the
+/// `key_wait_on_press` quirk is set. Until then the program counter stays put
+/// so the instruction runs again next cycle.
+pub fn ld_vx_k(chip8: &mut Chip8, ins: Instruction) {
+    let key = match chip8.key_wait {
+        Some(key) => key,
+        None => match (0..16).find(|&key| chip8.take_key(key)) {
+            Some(key) => key,
+            None => {
+                chip8.notify(|observer| observer.key_wait(ins.x));
+                return;
+            }
+        },
+    };
+
+    if chip8.key[key] && !chip8.quirks.key_wait_on_press {
+        chip8.key_wait = Some(key);
+        chip8.notify(|observer| observer.key_wait(ins.x));
+        return;
+    }
+
+    chip8.key_wait = None;
+    chip8.set_v(ins.x as usize, key as u8);
+    chip8.pc += 2;
+}
 
 /// `Fx15` - Set delay timer = Vx.
-pub fn ld_dt_vx(chip8: &mut Chip8) {}
+pub fn ld_dt_vx(chip8: &mut Chip8, ins: Instruction) {
+    chip8.delay_timer = chip8.v[ins.x as usize];
+    chip8.pc += 2;
+}
 
 /// `Fx18` - Set sound timer = Vx.
-pub fn ld_st_vx(chip8: &mut Chip8) {}
+pub fn ld_st_vx(chip8: &mut Chip8, ins: Instruction) {
+    chip8.sound_timer = chip8.v[ins.x as usize];
+    chip8.pc += 2;
+}
 
 /// `Fx1E` - Set I = I + Vx.
-pub fn add_i_vx(chip8: &mut Chip8) {}
+pub fn add_i_vx(chip8: &mut Chip8, ins: Instruction) {
+    let i = chip8.i.wrapping_add(chip8.v[ins.x as usize] as u16);
+    chip8.set_i(i);
+    chip8.pc += 2;
+}
 
 /// `Fx29` - Set I = location of sprite for digit Vx.
-pub fn ld_f_vx(chip8: &mut Chip8) {}
+pub fn ld_f_vx(chip8: &mut Chip8, ins: Instruction) {
+    // the font's glyphs are 5 bytes each, from address 0
+    let digit = chip8.v[ins.x as usize] & 0xF;
+    chip8.set_i(digit as u16 * 5);
+    chip8.pc += 2;
+}
 
 /// `Fx33` - Store BCD representation of Vx in memory locations I, I+1, and I+2.
-pub fn ld_b_vx(chip8: &mut Chip8) {
-    // let nibbles = to_nibbles(&chip8.opcode);
-    let vars = opcode_to_variables(&chip8.opcode);
-    let num = chip8.v[vars.x];
+pub fn ld_b_vx(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
+    let num = chip8.v[x];
 
     let hundreds = (num / 100) as u8;
     let tens = (num % 100 / 10) as u8;
     let ones = (num % 100 % 10) as u8;
 
     let i = chip8.i as usize;
+    if !chip8.check_memory(i, 3) {
+        return;
+    }
 
-    chip8.memory[i] = hundreds;
-    chip8.memory[i + 1] = tens;
-    chip8.memory[i + 2] = ones;
+    chip8.write_memory(i, hundreds);
+    chip8.write_memory(i + 1, tens);
+    chip8.write_memory(i + 2, ones);
     chip8.pc += 2;
 }
 
 /// `Fx55` - Store registers V0 through Vx in memory starting at location I.
-pub fn ld_i_vx(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
+pub fn ld_i_vx(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
     let mem_i = chip8.i as usize;
-    for i in 0..(vars.x + 1) {
-        chip8.memory[mem_i + i] = chip8.v[i]
+    if !chip8.check_memory(mem_i, x + 1) {
+        return;
+    }
+    for i in 0..(x + 1) {
+        chip8.write_memory(mem_i + i, chip8.v[i]);
     }
     chip8.pc += 2;
 }
 
 /// `Fx65` - Read registers V0 through Vx from memory starting at location I.
-pub fn ld_vx_i(chip8: &mut Chip8) {
-    let vars = opcode_to_variables(&chip8.opcode);
+pub fn ld_vx_i(chip8: &mut Chip8, ins: Instruction) {
+    let x = ins.x as usize;
     let mem_i = chip8.i as usize;
-    for i in 0..(vars.x + 1) {
-        chip8.v[i] = chip8.memory[mem_i + i]
+    if !chip8.check_memory(mem_i, x + 1) {
+        return;
+    }
+    for i in 0..(x + 1) {
+        let value = chip8.read_memory(mem_i + i);
+        chip8.set_v(i, value);
     }
     chip8.pc += 2;
 }
 
+/// Any word that does not decode to an instruction.
+///
+/// On CHIP-8X, `ExF2` and `ExF5` skip the next instruction if key Vx on the
+/// second keypad is, or is not, pressed.
+pub fn invalid(chip8: &mut Chip8, ins: Instruction) {
+    if chip8.platform == Platform::Chip8x && chip8.opcode & 0xF0FF == 0xE0F2 {
+        let key = 0x10 + (chip8.v[ins.x as usize] & 0xF) as usize;
+        chip8.pc += if chip8.take_key(key) { 4 } else { 2 };
+        return;
+    }
+    if chip8.platform == Platform::Chip8x && chip8.opcode & 0xF0FF == 0xE0F5 {
+        let key = 0x10 + (chip8.v[ins.x as usize] & 0xF) as usize;
+        chip8.pc += if chip8.take_key(key) { 2 } else { 4 };
+        return;
+    }
+    match chip8.quirks.invalid_opcode {
+        InvalidOpcode::Halt => chip8.fault(Fault::InvalidOpcode),
+        InvalidOpcode::Nop => chip8.pc += 2,
+        InvalidOpcode::Trap if chip8.trap != Some(chip8.pc) => chip8.trap = Some(chip8.pc),
+        InvalidOpcode::Trap => {
+            chip8.trap = None;
+            chip8.pc += 2;
+        }
+        InvalidOpcode::Log => {
+            eprintln!(
+                "Skipping invalid opcode {:04X} at {:03X}",
+                chip8.opcode, chip8.pc
+            );
+            chip8.pc += 2;
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -312,12 +495,57 @@ mod test {
         Chip8::initialize()
     }
 
+    #[test]
+    fn test_cls() {
+        let mut chip8 = setup();
+        chip8.pc = 512;
+        chip8.gfx[0] = 1;
+        chip8.gfx[64 * 32 - 1] = 1;
+        cls(&mut chip8, Instruction::decode(0x00E0));
+
+        assert!(
+            chip8.gfx.iter().all(|&pixel| pixel == 0),
+            "should clear the display"
+        );
+        assert!(chip8.should_draw, "should draw to screen");
+        assert_eq!(514, chip8.pc);
+    }
+
     #[test]
     fn test_sys_addr() {
         let mut chip8 = setup();
-        let test_opcode = 0x1ABC;
-        chip8.opcode = test_opcode;
-        sys_addr(&mut chip8);
+        let ins = Instruction::decode(0x0ABC);
+        sys_addr(&mut chip8, ins);
+        assert_eq!(
+            0x202, chip8.pc,
+            "should ignore machine code calls by default"
+        );
+
+        chip8.quirks.machine_code = MachineCode::Error;
+        sys_addr(&mut chip8, ins);
+        assert_eq!(Some(Fault::MachineCode), chip8.fault);
+        assert_eq!(0x202, chip8.pc);
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        let ins = Instruction::decode(0xE0FF);
+        let mut chip8 = setup();
+        chip8.quirks.invalid_opcode = InvalidOpcode::Nop;
+        invalid(&mut chip8, ins);
+        assert_eq!((0x202, None), (chip8.pc, chip8.fault));
+
+        let mut chip8 = setup();
+        chip8.quirks.invalid_opcode = InvalidOpcode::Trap;
+        invalid(&mut chip8, ins);
+        assert_eq!((0x200, Some(0x200)), (chip8.pc, chip8.trap));
+        assert_eq!(Some(Fault::InvalidOpcode), chip8.stop_cause());
+        invalid(&mut chip8, ins);
+        assert_eq!(
+            (0x202, None),
+            (chip8.pc, chip8.trap),
+            "should skip the word when resumed"
+        );
     }
 
     #[test]
@@ -327,8 +555,8 @@ mod test {
         let initial_sp = 1;
         chip8.sp = initial_sp;
         chip8.stack[chip8.sp as usize] = test_addr;
-        chip8.opcode = 0x00EE;
-        ret(&mut chip8);
+        let ins = Instruction::decode(0x00EE);
+        ret(&mut chip8, ins);
         assert_eq!(
             test_addr + 2,
             chip8.pc,
@@ -346,9 +574,9 @@ mod test {
         let mut chip8 = setup();
         let test_opcode = 0x1ABC;
         let initial_pc = 512;
-        chip8.opcode = test_opcode;
+        let ins = Instruction::decode(test_opcode);
         chip8.pc = initial_pc;
-        jp_addr(&mut chip8);
+        jp_addr(&mut chip8, ins);
         assert_eq!(
             test_opcode & 0x0FFF,
             chip8.pc,
@@ -362,8 +590,8 @@ mod test {
         let test_opcode = 0x2ABC;
         let initial_sp = chip8.sp;
         let initial_pc = chip8.pc;
-        chip8.opcode = test_opcode;
-        call_addr(&mut chip8);
+        let ins = Instruction::decode(test_opcode);
+        call_addr(&mut chip8, ins);
 
         assert_eq!(
             chip8.sp,
@@ -386,10 +614,10 @@ mod test {
         let mut chip8 = setup();
         let test_opcode = 0x32B0;
         let initial_pc = 512;
-        chip8.opcode = test_opcode;
+        let ins = Instruction::decode(test_opcode);
         chip8.v[2] = 0xB0;
         chip8.pc = initial_pc;
-        se_vx_byte(&mut chip8);
+        se_vx_byte(&mut chip8, ins);
 
         assert_eq!(
             initial_pc + 4,
@@ -403,10 +631,10 @@ mod test {
         let mut chip8 = setup();
         let test_opcode = 0x32B0;
         let initial_pc = 512;
-        chip8.opcode = test_opcode;
+        let ins = Instruction::decode(test_opcode);
         chip8.v[2] = 0xFF;
         chip8.pc = initial_pc;
-        se_vx_byte(&mut chip8);
+        se_vx_byte(&mut chip8, ins);
 
         assert_eq!(
             initial_pc + 2,
@@ -420,10 +648,10 @@ mod test {
         let mut chip8 = setup();
         let test_opcode = 0x32B0;
         let initial_pc = 512;
-        chip8.opcode = test_opcode;
+        let ins = Instruction::decode(test_opcode);
         chip8.v[2] = 0xFF;
         chip8.pc = initial_pc;
-        sne_vx_byte(&mut chip8);
+        sne_vx_byte(&mut chip8, ins);
 
         assert_eq!(
             initial_pc + 4,
@@ -436,10 +664,10 @@ mod test {
     fn test_sne_vx_byte_eq() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x4CB0;
+        let ins = Instruction::decode(0x4CB0);
         chip8.v[0xC] = 0xB0;
         chip8.pc = initial_pc;
-        sne_vx_byte(&mut chip8);
+        sne_vx_byte(&mut chip8, ins);
 
         assert_eq!(
             initial_pc + 2,
@@ -452,11 +680,11 @@ mod test {
     fn test_se_vx_vy_eq() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x5CE0;
+        let ins = Instruction::decode(0x5CE0);
         chip8.pc = initial_pc;
         chip8.v[0xC] = 0xE;
         chip8.v[0xE] = 0xE;
-        se_vx_vy(&mut chip8);
+        se_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             initial_pc + 4,
@@ -469,11 +697,11 @@ mod test {
     fn test_se_vx_vy_neq() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x5CE0;
+        let ins = Instruction::decode(0x5CE0);
         chip8.pc = initial_pc;
         chip8.v[0xC] = 0xE;
         chip8.v[0xE] = 0xF;
-        se_vx_vy(&mut chip8);
+        se_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             initial_pc + 2,
@@ -486,10 +714,10 @@ mod test {
     fn test_ld_vx_byte() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x60AA;
+        let ins = Instruction::decode(0x60AA);
         chip8.v[0x0] = 5;
         chip8.pc = initial_pc;
-        ld_vx_byte(&mut chip8);
+        ld_vx_byte(&mut chip8, ins);
 
         assert_eq!(0xAA, chip8.v[0x0], "should load `kk` into `vx`");
         assert_eq!(
@@ -503,11 +731,11 @@ mod test {
     fn test_ld_vx_vy() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x8690;
+        let ins = Instruction::decode(0x8690);
         chip8.v[0x6] = 0xDA;
         chip8.v[0x9] = 0x12;
         chip8.pc = initial_pc;
-        ld_vx_vy(&mut chip8);
+        ld_vx_vy(&mut chip8, ins);
 
         assert_eq!(0x12, chip8.v[0x6], "should store the value of `vy` in `vx`");
         assert_eq!(
@@ -521,10 +749,10 @@ mod test {
     pub fn test_or_vx_vy() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x8DB1;
+        let ins = Instruction::decode(0x8DB1);
         chip8.v[0xD] = 0xA;
         chip8.v[0xB] = 0x5;
-        or_vx_vy(&mut chip8);
+        or_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             0xA | 0x5,
@@ -542,10 +770,10 @@ mod test {
     pub fn test_and_vx_vy() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x8DB1;
+        let ins = Instruction::decode(0x8DB1);
         chip8.v[0xD] = 0xA;
         chip8.v[0xB] = 0x5;
-        and_vx_vy(&mut chip8);
+        and_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             0xA & 0x5,
@@ -563,10 +791,10 @@ mod test {
     pub fn test_xor_vx_vy() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x8DB1;
+        let ins = Instruction::decode(0x8DB1);
         chip8.v[0xD] = 0xA;
         chip8.v[0xB] = 0x5;
-        xor_vx_vy(&mut chip8);
+        xor_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             0xA ^ 0x5,
@@ -584,10 +812,10 @@ mod test {
     fn test_add_vx_byte() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x7210;
+        let ins = Instruction::decode(0x7210);
         chip8.v[0x2] = 0x6;
         chip8.pc = initial_pc;
-        add_vx_byte(&mut chip8);
+        add_vx_byte(&mut chip8, ins);
 
         assert_eq!(
             0x16, chip8.v[0x2],
@@ -604,10 +832,10 @@ mod test {
     fn test_add_vx_byte_overflow() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x76FF;
+        let ins = Instruction::decode(0x76FF);
         chip8.v[0x2] = 0x6;
         chip8.pc = initial_pc;
-        add_vx_byte(&mut chip8);
+        add_vx_byte(&mut chip8, ins);
 
         assert_eq!(
             0x6, chip8.v[0x2],
@@ -627,11 +855,11 @@ mod test {
         let initial_pc = chip8.pc;
         let initial_v4 = 100;
         let initial_v5 = 50;
-        chip8.opcode = test_opcode;
+        let ins = Instruction::decode(test_opcode);
         chip8.v[4] = initial_v4;
         chip8.v[5] = initial_v5;
         chip8.v[0xF] = 0;
-        add_vx_vy(&mut chip8);
+        add_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             chip8.v[0x4],
@@ -653,11 +881,11 @@ mod test {
         let initial_pc = chip8.pc;
         let initial_v4: u16 = 200;
         let initial_v5: u16 = 200;
-        chip8.opcode = test_opcode;
+        let ins = Instruction::decode(test_opcode);
         chip8.v[4] = initial_v4 as u8;
         chip8.v[5] = initial_v5 as u8;
         chip8.v[0xF] = 0;
-        add_vx_vy(&mut chip8);
+        add_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             chip8.v[0x4],
@@ -679,12 +907,12 @@ mod test {
     fn test_sub_vx_vy_greater_than() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x8355;
+        let ins = Instruction::decode(0x8355);
         chip8.v[0x3] = 100;
         chip8.v[0x5] = 25;
         chip8.v[0xF] = 0;
         chip8.pc = initial_pc;
-        sub_vx_vy(&mut chip8);
+        sub_vx_vy(&mut chip8, ins);
 
         assert_eq!(1, chip8.v[0xF], "should set `vf` to 1 when `vx` > `vy`");
         assert_eq!(
@@ -703,12 +931,12 @@ mod test {
         // Revisit this, as it's possible it's wrong
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x8355;
+        let ins = Instruction::decode(0x8355);
         chip8.v[0x3] = 25;
         chip8.v[0x5] = 100;
         chip8.v[0xF] = 0;
         chip8.pc = initial_pc;
-        sub_vx_vy(&mut chip8);
+        sub_vx_vy(&mut chip8, ins);
 
         assert_eq!(0, chip8.v[0xF], "should not set `vf` to 1 when `vx` < `vy`");
         assert_eq!(
@@ -726,9 +954,9 @@ mod test {
     fn test_shr_vx_vy_is_one() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x8016;
+        let ins = Instruction::decode(0x8016);
         chip8.v[0x0] = 0b1001;
-        shr_vx_vy(&mut chip8);
+        shr_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             1, chip8.v[0xF],
@@ -749,9 +977,9 @@ mod test {
     fn test_shr_vx_vy_is_not_one() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x8016;
+        let ins = Instruction::decode(0x8016);
         chip8.v[0x0] = 0b1000;
-        shr_vx_vy(&mut chip8);
+        shr_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             0, chip8.v[0xF],
@@ -769,11 +997,11 @@ mod test {
     fn test_subn_vx_vy_greater_than() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x8CD7;
+        let ins = Instruction::decode(0x8CD7);
         chip8.v[0xD] = 200;
         chip8.v[0xC] = 160;
         chip8.v[0xF] = 0;
-        subn_vx_vy(&mut chip8);
+        subn_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             40, chip8.v[0xC],
@@ -791,11 +1019,11 @@ mod test {
     fn test_subn_vx_vy_less_than() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x8CD7;
+        let ins = Instruction::decode(0x8CD7);
         chip8.v[0xD] = 160;
         chip8.v[0xC] = 200;
         chip8.v[0xF] = 0;
-        subn_vx_vy(&mut chip8);
+        subn_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             200, chip8.v[0xC],
@@ -814,9 +1042,9 @@ mod test {
         let mut chip8 = setup();
         let initial_pc = 512;
         let initial_vx: u8 = 0b10000000;
-        chip8.opcode = 0x8ABE;
+        let ins = Instruction::decode(0x8ABE);
         chip8.v[0xA] = initial_vx;
-        shl_vx_vy(&mut chip8);
+        shl_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             1, chip8.v[0xF],
@@ -839,9 +1067,9 @@ mod test {
         let mut chip8 = setup();
         let initial_pc = 512;
         let initial_vx: u8 = 0b1;
-        chip8.opcode = 0x8ABE;
+        let ins = Instruction::decode(0x8ABE);
         chip8.v[0xA] = initial_vx;
-        shl_vx_vy(&mut chip8);
+        shl_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             0, chip8.v[0xF],
@@ -863,11 +1091,11 @@ mod test {
     fn test_sne_vx_vy_neq() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x9120;
+        let ins = Instruction::decode(0x9120);
         chip8.v[0x1] = 100;
         chip8.v[0x2] = 150;
         chip8.pc = initial_pc;
-        sne_vx_vy(&mut chip8);
+        sne_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             initial_pc + 4,
@@ -880,11 +1108,11 @@ mod test {
     fn test_sne_vx_vy_eq() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0x9120;
+        let ins = Instruction::decode(0x9120);
         chip8.v[0x1] = 100;
         chip8.v[0x2] = 100;
         chip8.pc = initial_pc;
-        sne_vx_vy(&mut chip8);
+        sne_vx_vy(&mut chip8, ins);
 
         assert_eq!(
             initial_pc + 2,
@@ -897,9 +1125,9 @@ mod test {
     fn test_ld_i_addr() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0xA666;
+        let ins = Instruction::decode(0xA666);
         chip8.i = 0;
-        ld_i_addr(&mut chip8);
+        ld_i_addr(&mut chip8, ins);
 
         assert_eq!(0x666, chip8.i, "should load addr into register i");
         assert_eq!(
@@ -912,10 +1140,10 @@ mod test {
     #[test]
     fn test_jp_v0_addr() {
         let mut chip8 = setup();
-        chip8.opcode = 0xB512;
+        let ins = Instruction::decode(0xB512);
         chip8.v[0x0] = 100;
         chip8.pc = 512;
-        jp_v0_addr(&mut chip8);
+        jp_v0_addr(&mut chip8, ins);
 
         assert_eq!(
             0x512 + 100,
@@ -928,9 +1156,9 @@ mod test {
     fn test_rnd_vx_byte() {
         let mut chip8 = setup();
         let initial_pc = 512;
-        chip8.opcode = 0xC144;
+        let ins = Instruction::decode(0xC144);
         chip8.pc = initial_pc;
-        rnd_vx_byte(&mut chip8, || 0x40);
+        rnd_vx_byte(&mut chip8, ins, |_| 0x40);
 
         assert_eq!(
             0x44 & 0x40,
@@ -950,19 +1178,19 @@ mod test {
         let sprites = [0b10101100; 4];
         let vx: usize = 0;
         let vy: usize = 0;
-        chip8.opcode = 0xD8B4;
+        let ins = Instruction::decode(0xD8B4);
         chip8.i = 1000;
         chip8.v[0xF] = 0;
         chip8.v[0x8] = vx as u8;
         chip8.v[0xB] = vy as u8;
-        chip8.gfx = [0; 64 * 32];
+        chip8.gfx = vec![0; 64 * 32];
         chip8.memory = [0; 4096];
         // prepare memory with sprites
         for (sprite_i, &sprite) in sprites.iter().enumerate() {
             chip8.memory[chip8.i as usize + sprite_i] = sprite;
         }
-        let old_gfx = chip8.gfx; // save a copy of initial gfx state
-        drw_vx_vy_nibble(&mut chip8);
+        let old_gfx = chip8.gfx.clone(); // save a copy of initial gfx state
+        drw_vx_vy_nibble(&mut chip8, ins);
 
         for (i, &sprite) in sprites.iter().enumerate() {
             for (j, &sprite_bit) in into_bit_vec(sprite).iter().enumerate() {
@@ -993,19 +1221,19 @@ mod test {
         let sprites = [0b10101100; 4];
         let vx: usize = 56;
         let vy: usize = 30;
-        chip8.opcode = 0xD8B4;
+        let ins = Instruction::decode(0xD8B4);
         chip8.i = 1000;
         chip8.v[0xF] = 0;
         chip8.v[0x8] = vx as u8;
         chip8.v[0xB] = vy as u8;
-        chip8.gfx = [0; 64 * 32];
+        chip8.gfx = vec![0; 64 * 32];
         chip8.memory = [0; 4096];
         // prepare memory with sprites
         for (sprite_i, &sprite) in sprites.iter().enumerate() {
             chip8.memory[chip8.i as usize + sprite_i] = sprite;
         }
-        let old_gfx = chip8.gfx; // save a copy of initial gfx state
-        drw_vx_vy_nibble(&mut chip8);
+        let old_gfx = chip8.gfx.clone(); // save a copy of initial gfx state
+        drw_vx_vy_nibble(&mut chip8, ins);
 
         for (i, &sprite) in sprites.iter().enumerate() {
             for (j, &sprite_bit) in into_bit_vec(sprite).iter().enumerate() {
@@ -1036,19 +1264,19 @@ mod test {
         let sprites = [0b10101100; 4];
         let vx: usize = 0;
         let vy: usize = 0;
-        chip8.opcode = 0xD8B4;
+        let ins = Instruction::decode(0xD8B4);
         chip8.i = 1000;
         chip8.v[0xF] = 0;
         chip8.v[0x8] = vx as u8;
         chip8.v[0xB] = vy as u8;
-        chip8.gfx = [1; 64 * 32];
+        chip8.gfx = vec![1; 64 * 32];
         chip8.memory = [0; 4096];
         // prepare memory with sprites
         for (sprite_i, &sprite) in sprites.iter().enumerate() {
             chip8.memory[chip8.i as usize + sprite_i] = sprite;
         }
-        let old_gfx = chip8.gfx; // save a copy of initial gfx state
-        drw_vx_vy_nibble(&mut chip8);
+        let old_gfx = chip8.gfx.clone(); // save a copy of initial gfx state
+        drw_vx_vy_nibble(&mut chip8, ins);
 
         for (i, &sprite) in sprites.iter().enumerate() {
             for (j, &sprite_bit) in into_bit_vec(sprite).iter().enumerate() {
@@ -1076,19 +1304,19 @@ mod test {
         let sprites = [0b10101100; 4];
         let vx: usize = 58;
         let vy: usize = 26;
-        chip8.opcode = 0xD8B4;
+        let ins = Instruction::decode(0xD8B4);
         chip8.i = 1000;
         chip8.v[0xF] = 0;
         chip8.v[0x8] = vx as u8;
         chip8.v[0xB] = vy as u8;
-        chip8.gfx = [1; 64 * 32];
+        chip8.gfx = vec![1; 64 * 32];
         chip8.memory = [0; 4096];
         // prepare memory with sprites
         for (sprite_i, &sprite) in sprites.iter().enumerate() {
             chip8.memory[chip8.i as usize + sprite_i] = sprite;
         }
-        let old_gfx = chip8.gfx; // save a copy of initial gfx state
-        drw_vx_vy_nibble(&mut chip8);
+        let old_gfx = chip8.gfx.clone(); // save a copy of initial gfx state
+        drw_vx_vy_nibble(&mut chip8, ins);
 
         for (i, &sprite) in sprites.iter().enumerate() {
             for (j, &sprite_bit) in into_bit_vec(sprite).iter().enumerate() {
@@ -1113,37 +1341,136 @@ mod test {
     #[test]
     fn test_skp_vx() {
         let mut chip8 = setup();
-        chip8.opcode = 0xE19E;
+        let ins = Instruction::decode(0xE19E);
         chip8.pc = 512;
-        chip8.key[1] = true;
-        skp_vx(&mut chip8);
+        chip8.v[1] = 0xA;
+        chip8.key[0xA] = true;
+        skp_vx(&mut chip8, ins);
 
         assert_eq!(
-            514, chip8.pc,
+            516, chip8.pc,
             "should skip next instruction if `vx` value key is pressed"
         );
+
+        chip8.key[0xA] = false;
+        skp_vx(&mut chip8, ins);
+        assert_eq!(
+            518, chip8.pc,
+            "should not skip next instruction if `vx` value key is not pressed"
+        );
     }
 
     #[test]
-    fn test_sknp_vx() {}
+    fn test_sknp_vx() {
+        let mut chip8 = setup();
+        let ins = Instruction::decode(0xE1A1);
+        chip8.pc = 512;
+        chip8.v[1] = 0xA;
+        sknp_vx(&mut chip8, ins);
+
+        assert_eq!(
+            516, chip8.pc,
+            "should skip next instruction if `vx` value key is not pressed"
+        );
+
+        chip8.key_latch[0xA] = true;
+        sknp_vx(&mut chip8, ins);
+        assert_eq!(
+            518, chip8.pc,
+            "should not skip next instruction if `vx` value key was tapped"
+        );
+    }
 
     #[test]
-    fn test_ld_vx_dt() {}
+    fn test_ld_vx_dt() {
+        let mut chip8 = setup();
+        chip8.pc = 512;
+        chip8.delay_timer = 0x2A;
+        ld_vx_dt(&mut chip8, Instruction::decode(0xF307));
+
+        assert_eq!(0x2A, chip8.v[3], "should load the delay timer into `vx`");
+        assert_eq!(514, chip8.pc);
+    }
 
     #[test]
-    fn test_ld_vx_k() {}
+    fn test_ld_vx_k() {
+        let mut chip8 = setup();
+        let ins = Instruction::decode(0xF20A);
+        chip8.pc = 512;
+        ld_vx_k(&mut chip8, ins);
+        assert_eq!(512, chip8.pc, "should wait while no key is pressed");
+
+        chip8.key[0x7] = true;
+        ld_vx_k(&mut chip8, ins);
+        assert_eq!(Some(0x7), chip8.key_wait, "should wait for release");
+        assert_eq!(512, chip8.pc);
+
+        chip8.key[0x7] = false;
+        ld_vx_k(&mut chip8, ins);
+        assert_eq!(None, chip8.key_wait);
+        assert_eq!(0x7, chip8.v[2], "should store the key in `vx`");
+        assert_eq!(514, chip8.pc, "should increment program counter by 2");
+    }
 
     #[test]
-    fn test_ld_dt_vx() {}
+    fn test_ld_dt_vx() {
+        let mut chip8 = setup();
+        chip8.pc = 512;
+        chip8.v[3] = 0x2A;
+        ld_dt_vx(&mut chip8, Instruction::decode(0xF315));
+
+        assert_eq!(
+            0x2A, chip8.delay_timer,
+            "should set the delay timer to `vx`"
+        );
+        assert_eq!(514, chip8.pc);
+    }
 
     #[test]
-    fn test_ld_st_vx() {}
+    fn test_ld_st_vx() {
+        let mut chip8 = setup();
+        chip8.pc = 512;
+        chip8.v[3] = 0x2A;
+        ld_st_vx(&mut chip8, Instruction::decode(0xF318));
+
+        assert_eq!(
+            0x2A, chip8.sound_timer,
+            "should set the sound timer to `vx`"
+        );
+        assert_eq!(514, chip8.pc);
+    }
 
     #[test]
-    fn test_add_i_vx() {}
+    fn test_add_i_vx() {
+        let mut chip8 = setup();
+        chip8.pc = 512;
+        chip8.i = 0x300;
+        chip8.v[4] = 0x2A;
+        add_i_vx(&mut chip8, Instruction::decode(0xF41E));
+
+        assert_eq!(0x32A, chip8.i, "should add `vx` to register i");
+        assert_eq!(514, chip8.pc, "should increment program counter by 2");
+    }
 
     #[test]
-    fn test_ld_f_vx() {}
+    fn test_ld_f_vx() {
+        let mut chip8 = setup();
+        chip8.pc = 512;
+        chip8.v[6] = 0xA;
+        ld_f_vx(&mut chip8, Instruction::decode(0xF629));
+
+        assert_eq!(50, chip8.i, "should point i at the glyph for `vx`");
+        assert_eq!(
+            [0xF0, 0x90, 0xF0, 0x90, 0x90],
+            chip8.memory[50..55],
+            "should be the \"A\" glyph"
+        );
+        assert_eq!(514, chip8.pc, "should increment program counter by 2");
+
+        chip8.v[6] = 0x1F;
+        ld_f_vx(&mut chip8, Instruction::decode(0xF629));
+        assert_eq!(75, chip8.i, "should only use the low digit of `vx`");
+    }
 
     #[test]
     fn test_ld_b_vx() {
@@ -1151,11 +1478,11 @@ mod test {
         let test_opcode = 0xFB33;
         let initial_i: usize = 100;
         let initial_pc = 5;
-        chip8.opcode = test_opcode;
+        let ins = Instruction::decode(test_opcode);
         chip8.v[0xB] = 123;
         chip8.i = initial_i as u16;
         chip8.pc = initial_pc;
-        ld_b_vx(&mut chip8);
+        ld_b_vx(&mut chip8, ins);
 
         assert_eq!(
             [1, 2, 3],
@@ -1174,14 +1501,14 @@ mod test {
         let mut chip8 = setup();
         let initial_pc = 512;
         let mem_start: usize = 1024;
-        chip8.opcode = 0xFF55;
+        let ins = Instruction::decode(0xFF55);
         chip8.pc = initial_pc;
         chip8.i = mem_start as u16;
         for i in 0..16 {
             chip8.v[i] = 123;
             chip8.memory[mem_start + i] = 0;
         }
-        ld_i_vx(&mut chip8);
+        ld_i_vx(&mut chip8, ins);
 
         assert_eq!(
             chip8.v[0..0xF],
@@ -1200,14 +1527,14 @@ mod test {
         let mut chip8 = setup();
         let initial_pc = 512;
         let mem_start: usize = 1024;
-        chip8.opcode = 0xFF65;
+        let ins = Instruction::decode(0xFF65);
         chip8.pc = initial_pc;
         chip8.i = mem_start as u16;
         for i in 0..0xF {
             chip8.v[i] = 0;
             chip8.memory[mem_start + i] = 123;
         }
-        ld_vx_i(&mut chip8);
+        ld_vx_i(&mut chip8, ins);
 
         assert_eq!(
             chip8.memory[mem_start..mem_start + 0xF],
@@ -1220,4 +1547,92 @@ mod test {
             "should increment program counter by 2"
         );
     }
+
+    #[test]
+    fn test_drw_wraps_tall_displays() {
+        let mut chip8 = Chip8::for_platform(Platform::Hires);
+        chip8.i = 0x300;
+        chip8.memory[0x300] = 0x80;
+        chip8.memory[0x301] = 0x80;
+        chip8.v[1] = 63;
+        drw_vx_vy_nibble(&mut chip8, Instruction::decode(0xD112));
+        assert_eq!(1, chip8.gfx[63 * 64 + 63], "should reach the 64th row");
+        assert_eq!(1, chip8.gfx[63], "should wrap to the top");
+    }
+
+    #[test]
+    fn test_platform_routines() {
+        let mut chip8 = Chip8::for_platform(Platform::Chip8x);
+        chip8.quirks.machine_code = MachineCode::Error;
+        sys_addr(&mut chip8, Instruction::decode(0x02A0));
+        sys_addr(&mut chip8, Instruction::decode(0x02A0));
+        assert_eq!(2, chip8.background);
+        assert_eq!((0x304, None), (chip8.pc, chip8.fault));
+
+        let mut chip8 = Chip8::for_platform(Platform::Hires);
+        chip8.quirks.machine_code = MachineCode::Error;
+        chip8.gfx[63 * 64 + 63] = 1;
+        sys_addr(&mut chip8, Instruction::decode(0x0230));
+        assert_eq!(
+            None, chip8.fault,
+            "should clear rather than call machine code"
+        );
+        assert!(
+            chip8.gfx.iter().all(|&pixel| pixel == 0),
+            "should clear all 64 rows"
+        );
+        assert_eq!(0x2C2, chip8.pc);
+        sys_addr(&mut chip8, Instruction::decode(0x02A0));
+        assert_eq!(Some(Fault::MachineCode), chip8.fault);
+    }
+
+    #[test]
+    fn test_chip8x_colors() {
+        let mut chip8 = Chip8::for_platform(Platform::Chip8x);
+        // columns 2-3 and rows 4-11, zone rows 1 and 2, in blue
+        chip8.v[4] = 0x12;
+        chip8.v[5] = 0x11;
+        chip8.v[6] = 2;
+        jp_v0_addr(&mut chip8, Instruction::decode(0xB460));
+        let zone = |column: usize, row: usize| chip8.colors[row * 8 + column];
+        assert_eq!(
+            [2, 2, 2, 2],
+            [zone(2, 4), zone(3, 4), zone(2, 11), zone(3, 11)]
+        );
+        assert_eq!(
+            [1, 1, 1, 1],
+            [zone(1, 4), zone(4, 4), zone(2, 3), zone(2, 12)]
+        );
+        assert_eq!(0x302, chip8.pc);
+
+        // three rows from pixel (20, 30) in green, clipped at the bottom
+        chip8.v[0] = 20;
+        chip8.v[1] = 4;
+        chip8.v[2] = 30;
+        jp_v0_addr(&mut chip8, Instruction::decode(0xB023));
+        let zone = |column: usize, row: usize| chip8.colors[row * 8 + column];
+        assert_eq!([4, 4, 1], [zone(2, 30), zone(2, 31), zone(2, 29)]);
+
+        let mut chip8 = setup();
+        jp_v0_addr(&mut chip8, Instruction::decode(0xB023));
+        assert_eq!(0x023, chip8.pc, "should jump on other platforms");
+    }
+
+    #[test]
+    fn test_chip8x_second_keypad() {
+        let mut chip8 = Chip8::for_platform(Platform::Chip8x);
+        chip8.v[1] = 0xA;
+        chip8.key[0x1A] = true;
+        chip8.opcode = 0xE1F2;
+        invalid(&mut chip8, Instruction::decode(0xE1F2));
+        assert_eq!(0x304, chip8.pc, "should skip with key A down on keypad 2");
+        chip8.opcode = 0xE1F5;
+        invalid(&mut chip8, Instruction::decode(0xE1F5));
+        assert_eq!(0x306, chip8.pc);
+
+        chip8.key[0x1A] = false;
+        chip8.key[0xA] = true;
+        invalid(&mut chip8, Instruction::decode(0xE1F5));
+        assert_eq!(0x30A, chip8.pc, "should not see the first keypad");
+    }
 }
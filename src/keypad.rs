@@ -0,0 +1,137 @@
+/// Host keys for the hex keypad, laid out as the left side of a QWERTY keyboard
+///
+/// ```text
+/// 1 2 3 C        1 2 3 4
+/// 4 5 6 D   <=   Q W E R
+/// 7 8 9 E        A S D F
+/// A 0 B F        Z X C V
+/// ```
+pub const LAYOUT: [(char, usize); 16] = [
+    ('1', 0x1),
+    ('2', 0x2),
+    ('3', 0x3),
+    ('4', 0xC),
+    ('q', 0x4),
+    ('w', 0x5),
+    ('e', 0x6),
+    ('r', 0xD),
+    ('a', 0x7),
+    ('s', 0x8),
+    ('d', 0x9),
+    ('f', 0xE),
+    ('z', 0xA),
+    ('x', 0x0),
+    ('c', 0xB),
+    ('v', 0xF),
+];
+
+/// Maps a host key character to its hex keypad key
+pub fn key_for_char(c: char) -> Option<usize> {
+    let c = c.to_ascii_lowercase();
+    LAYOUT
+        .iter()
+        .find(|(host, _)| *host == c)
+        .map(|(_, key)| *key)
+}
+
+/// A host key a keymap can bind: a character key or one of the arrows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostKey {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Host keys bound to keypad keys on top of `LAYOUT`, e.g. the arrows for a
+/// game's movement keys
+///
+/// Written as `host:key` pairs, like `up:5 down:8 left:7 right:9 space:6`,
+/// where the host key is `up`, `down`, `left`, `right`, `space` or a single
+/// character and the keypad key is in hex, up to `1F` for CHIP-8X.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Keymap {
+    pub bindings: Vec<(HostKey, usize)>,
+}
+
+impl Keymap {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut bindings = Vec::new();
+        for binding in text.split_whitespace() {
+            let (host, key) = binding
+                .split_once(':')
+                .ok_or_else(|| format!("Expected HOST:KEY: {:?}", binding))?;
+            let host = match host.to_ascii_lowercase().as_str() {
+                "up" => HostKey::Up,
+                "down" => HostKey::Down,
+                "left" => HostKey::Left,
+                "right" => HostKey::Right,
+                "space" => HostKey::Char(' '),
+                name => {
+                    let mut chars = name.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => HostKey::Char(c),
+                        _ => return Err(format!("Unknown host key: {:?}", host)),
+                    }
+                }
+            };
+            let key = usize::from_str_radix(key, 16)
+                .ok()
+                .filter(|&key| key < 0x20)
+                .ok_or_else(|| format!("Invalid keypad key: {:?}", key))?;
+            bindings.push((host, key));
+        }
+        Ok(Keymap { bindings })
+    }
+
+    /// The keypad key for a host key, from the bindings or else the layout
+    pub fn key(&self, host: HostKey) -> Option<usize> {
+        let host = match host {
+            HostKey::Char(c) => HostKey::Char(c.to_ascii_lowercase()),
+            arrow => arrow,
+        };
+        let bound = self.bindings.iter().find(|(bound, _)| *bound == host);
+        match (bound, host) {
+            (Some((_, key)), _) => Some(*key),
+            (None, HostKey::Char(c)) => key_for_char(c),
+            (None, _) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_key_for_char() {
+        assert_eq!(Some(0x1), key_for_char('1'));
+        assert_eq!(Some(0xC), key_for_char('4'));
+        assert_eq!(Some(0xF), key_for_char('V'));
+        assert_eq!(None, key_for_char('p'));
+    }
+
+    #[test]
+    fn test_keymap() {
+        let keymap = Keymap::parse("up:5 down:8 space:6 W:c").unwrap();
+        assert_eq!(Some(0x5), keymap.key(HostKey::Up));
+        assert_eq!(Some(0x6), keymap.key(HostKey::Char(' ')));
+        assert_eq!(
+            Some(0xC),
+            keymap.key(HostKey::Char('w')),
+            "should override the layout"
+        );
+        assert_eq!(
+            Some(0x4),
+            keymap.key(HostKey::Char('Q')),
+            "should keep the layout"
+        );
+        assert_eq!(None, keymap.key(HostKey::Left));
+        assert_eq!(Keymap::default(), Keymap::parse("").unwrap());
+
+        assert!(Keymap::parse("up5").is_err());
+        assert!(Keymap::parse("home:5").is_err());
+        assert!(Keymap::parse("up:20").is_err());
+    }
+}
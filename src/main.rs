@@ -1,51 +1,272 @@
+mod analyze;
+mod capture;
+mod cdp1802;
+mod cfg;
 mod chip8;
+mod config;
+mod controls;
+mod crash;
+mod dap;
+mod database;
+mod debugger;
+mod decode;
+mod disasm;
+mod emulator;
+mod expr;
+mod frontend;
+mod gdb;
+mod image;
 mod input_output;
 mod instructions;
+mod keypad;
+mod memview;
+mod movie;
+mod observer;
+mod octo;
+mod osd;
+mod palette;
+mod platform;
+mod quirks;
+mod record;
+mod sprites;
+mod symbols;
+mod terminal;
+mod timing;
+mod trace;
 mod utils;
+mod vip;
 
 use chip8::Chip8;
+use config::{Config, FrontendKind};
+use emulator::Emulator;
+use frontend::Headless;
 use input_output::InputOutput;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use quirks::Quirks;
 use std::env;
-use std::time::Duration;
-
-const SCALE: u32 = 12;
+use std::fs;
+use std::io;
+use std::process;
+use terminal::Terminal;
 
 fn main() {
-    let mut chip8 = Chip8::initialize();
-    let args: Vec<String> = env::args().collect();
-    chip8.load_rom(&args[1]);
-
-    let sdl_context = sdl2::init().unwrap();
-    let mut io = InputOutput::initialize(&sdl_context, SCALE);
-
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    'running: loop {
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'running,
-                Event::KeyDown {
-                    keycode: Some(keycode),
-                    ..
-                } => io.handle_key_down(&mut chip8, keycode),
-                Event::KeyUp {
-                    keycode: Some(keycode),
-                    ..
-                } => io.handle_key_up(&mut chip8, keycode),
-                _ => {}
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("analyze") {
+        lint(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("cfg") {
+        graph(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("source") {
+        decompile(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("sprites") {
+        extract_sprites(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("disasm") {
+        disassemble(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("--dap") {
+        // the editor sends the ROM and settings in its launch request
+        if let Err(e) = dap::serve(io::stdin(), io::stdout(), Quirks::default(), 10) {
+            eprintln!("Debug adapter error: {:?}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let config = match Config::for_run(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: chip-8 [--config FILE] [--OPTION VALUE]... ROM");
+            eprintln!("       chip-8 [--OPTION VALUE]... --post-mortem CRASH-DUMP");
+            eprintln!("       chip-8 disasm [--symbols FILE] ROM");
+            eprintln!("       chip-8 analyze [--symbols FILE] ROM");
+            eprintln!("       chip-8 cfg [--symbols FILE] ROM > FILE.dot");
+            eprintln!("       chip-8 source [--symbols FILE] [--trace FILE] ROM > FILE.8o");
+            eprintln!("       chip-8 sprites [--frames N] [--sprite-range START-END] [--sprite-sheet FILE] ROM");
+            eprintln!("       chip-8 --dap");
+            process::exit(1);
+        }
+    };
+
+    if let Some(port) = config.gdb_port {
+        let chip8 = match &config.post_mortem {
+            Some(dump) => dump.restore(),
+            None => {
+                let mut chip8 = Chip8::for_platform(config.platform);
+                chip8.quirks = config.quirks;
+                chip8.load_rom(&config.rom_path);
+                chip8
             }
+        };
+        if let Err(e) = gdb::serve(chip8, port, config.instructions_per_frame) {
+            eprintln!("GDB server error: {:?}", e);
+            process::exit(1);
         }
+        return;
+    }
 
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 600));
-        chip8.emulate_cycle();
+    match config.frontend {
+        FrontendKind::Sdl => {
+            let sdl_context = sdl2::init().unwrap();
+            let io = InputOutput::initialize(
+                &sdl_context,
+                config.scale,
+                config.palette,
+                config.phosphor_frames,
+                config.keymap.clone(),
+            );
+            Emulator::new(&config, io).run();
+        }
+        FrontendKind::Terminal => {
+            let palette = if config.truecolor {
+                Some(config.palette)
+            } else {
+                None
+            };
+            let keymap = config.keymap.clone();
+            let terminal = match Terminal::initialize(palette, keymap, config.key_timeout) {
+                Ok(terminal) => terminal,
+                Err(e) => {
+                    eprintln!("Error setting up terminal: {:?}", e);
+                    process::exit(1);
+                }
+            };
+            Emulator::new(&config, terminal).run();
+        }
+        FrontendKind::Headless => Emulator::new(&config, Headless::new()).run(),
+    }
+}
+
+/// Prints a listing of a ROM as loaded for its platform
+fn disassemble(args: &[String]) {
+    let (config, rom) = load_subcommand(args, "disasm [--symbols FILE] ROM");
+    let origin = config.platform.load_address();
+    print!("{}", disasm::listing(&rom, origin, &config.symbols));
+}
 
-        if chip8.should_draw == true {
-            io.draw_canvas(&mut chip8, SCALE);
+/// Prints what static analysis finds wrong with a ROM, exiting with 2 if anything
+fn lint(args: &[String]) {
+    let (config, rom) = load_subcommand(args, "analyze [--symbols FILE] ROM");
+    let findings = analyze::analyze(&rom, config.platform);
+    for finding in &findings {
+        println!("{}", finding.describe(&config.symbols));
+    }
+    if !findings.is_empty() {
+        process::exit(2);
+    }
+}
+
+/// Prints the ROM's control-flow graph in Graphviz DOT
+fn graph(args: &[String]) {
+    let (config, rom) = load_subcommand(args, "cfg [--symbols FILE] ROM > FILE.dot");
+    print!("{}", cfg::dot(&rom, config.platform, &config.symbols));
+}
+
+/// Prints Octo source for a ROM, using a trace from `--trace` to find code
+/// that traversal cannot
+fn decompile(args: &[String]) {
+    let (config, rom) =
+        load_subcommand(args, "source [--symbols FILE] [--trace FILE] ROM > FILE.8o");
+    let executed = match &config.trace {
+        Some(path) => match fs::read_to_string(path)
+            .map_err(|e| format!("Error reading {}: {:?}", path, e))
+            .and_then(|trace| octo::coverage(&trace))
+        {
+            Ok(executed) => executed,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+        None => Default::default(),
+    };
+    print!(
+        "{}",
+        octo::source(&rom, config.platform, &config.symbols, &executed)
+    );
+}
+
+/// Exports the sprites a ROM draws, or a range of memory, as a PNG and lists them
+///
+/// Sprites are found by static analysis, unless `--frames` asks for the game
+/// to be run headless that long first, adding the sprites it drew at the
+/// heights it drew them and leaving memory as it was at the end.
+fn extract_sprites(args: &[String]) {
+    let (config, rom) = load_subcommand(
+        args,
+        "sprites [--symbols FILE] [--palette NAME] [--frames N] [--sprite-range START-END] [--sprite-sheet FILE] ROM",
+    );
+    let (memory, sprites) = match config.max_frames {
+        Some(_) => {
+            let mut emulator = Emulator::new(&config, Headless::new());
+            while emulator.step() {}
+            (emulator.chip8.memory, emulator.sprites())
+        }
+        None => {
+            let mut chip8 = Chip8::for_platform(config.platform);
+            chip8.load_bytes(&rom);
+            (chip8.memory, sprites::scan(&rom, config.platform))
+        }
+    };
+    let image = match config.sprite_range {
+        Some((start, end)) => sprites::bitmap(&memory, start, end, config.palette),
+        None => {
+            for (&address, height) in &sprites {
+                println!(
+                    "{:03X}  {:<16}  {} rows",
+                    address,
+                    config.symbols.describe(address),
+                    height
+                );
+            }
+            sprites::sheet(&memory, &sprites, config.palette)
+        }
+    };
+    let path = match &config.sprite_sheet {
+        Some(path) => path.clone(),
+        None => sprites::default_path(&config.rom_path),
+    };
+    if let Err(e) = image.save_png(&path) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+/// Parses a subcommand's options and reads its ROM, exiting with `usage` if
+/// either fails or the ROM does not fit where its platform loads it
+fn load_subcommand(args: &[String], usage: &str) -> (Config, Vec<u8>) {
+    let config = match Config::from_args(args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: chip-8 {}", usage);
+            process::exit(1);
+        }
+    };
+    let rom = match fs::read(&config.rom_path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Error reading {}: {:?}", config.rom_path, e);
+            process::exit(1);
         }
+    };
+    let room = 4096 - config.platform.load_address() as usize;
+    if rom.len() > room {
+        eprintln!(
+            "{} is {} bytes, more than the {} that fit from {:03X}",
+            config.rom_path,
+            rom.len(),
+            room,
+            config.platform.load_address()
+        );
+        process::exit(1);
     }
+    (config, rom)
 }
@@ -0,0 +1,384 @@
+use crate::chip8::Chip8;
+use crate::image::Image;
+use crate::observer::Observer;
+use crate::osd::{GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::palette::{Palette, Rgb};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Frames a written byte stays highlighted, fading out
+pub const FLASH_FRAMES: u8 = 30;
+
+/// Size of a font pixel in the view
+const TEXT_SIZE: u32 = 2;
+
+/// Memory rows of 16 bytes shown at once
+const ROWS: u16 = 32;
+
+/// Highlight behind the bytes at `pc`
+const PC_COLOR: Rgb = Rgb(200, 40, 40);
+
+/// Highlight behind the byte at `I`
+const I_COLOR: Rgb = Rgb(40, 80, 200);
+
+/// What a written byte flashes in before fading to the background
+const FLASH_COLOR: Rgb = Rgb(230, 200, 0);
+
+/// Keys for a debug view, from its window rather than the game's keypad
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViewKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    /// Switches between memory and the registers
+    Tab,
+    /// Drops digits typed so far
+    Backspace,
+    Digit(u8),
+}
+
+/// What the edit cursor is on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Memory(u16),
+    Pc,
+    I,
+    Delay,
+    Sound,
+    V(u8),
+}
+
+impl Field {
+    /// Registers in the order the view lists them
+    const REGISTERS: [Field; 20] = [
+        Field::Pc,
+        Field::I,
+        Field::Delay,
+        Field::Sound,
+        Field::V(0),
+        Field::V(1),
+        Field::V(2),
+        Field::V(3),
+        Field::V(4),
+        Field::V(5),
+        Field::V(6),
+        Field::V(7),
+        Field::V(8),
+        Field::V(9),
+        Field::V(10),
+        Field::V(11),
+        Field::V(12),
+        Field::V(13),
+        Field::V(14),
+        Field::V(15),
+    ];
+
+    /// Hex digits it takes to set the field
+    fn digits(&self) -> usize {
+        match self {
+            Field::Pc | Field::I => 3,
+            _ => 2,
+        }
+    }
+}
+
+/// Observer keeping how recently each byte of memory was written, so the view
+/// can flash it
+///
+/// Clones share the same record, so one can be installed on the core and
+/// another kept to draw from.
+#[derive(Clone)]
+pub struct WriteTracker(Rc<RefCell<Vec<u8>>>);
+
+impl WriteTracker {
+    pub fn new() -> Self {
+        WriteTracker(Rc::new(RefCell::new(vec![0; 4096])))
+    }
+
+    /// Fades every flash by a frame
+    pub fn tick(&self) {
+        for heat in self.0.borrow_mut().iter_mut() {
+            *heat = heat.saturating_sub(1);
+        }
+    }
+
+    /// Frames left on the address's flash
+    pub fn heat(&self, address: u16) -> u8 {
+        self.0.borrow()[address as usize]
+    }
+}
+
+impl Observer for WriteTracker {
+    fn memory_write(&mut self, address: u16, _value: u8) {
+        self.0.borrow_mut()[address as usize] = FLASH_FRAMES;
+    }
+}
+
+/// A hex dump of memory with the registers above it, which can be edited
+///
+/// The dump follows the cursor, 16 bytes to a row. Typing hex digits on a
+/// field sets it once enough digits for it are in; edits go through the
+/// core, so observers see them and cached instructions are dropped.
+pub struct MemoryView {
+    pub cursor: Field,
+    /// Where the cursor goes back to in memory after visiting the registers
+    address: u16,
+    /// Digits typed for the field so far
+    pub typed: String,
+    pub writes: WriteTracker,
+}
+
+impl MemoryView {
+    pub fn new() -> Self {
+        MemoryView {
+            cursor: Field::Memory(0x200),
+            address: 0x200,
+            typed: String::new(),
+            writes: WriteTracker::new(),
+        }
+    }
+
+    /// Moves the cursor or edits the machine; returns true if a key started
+    /// or made an edit, so the caller can pause
+    pub fn handle(&mut self, key: ViewKey, chip8: &mut Chip8) -> bool {
+        if let ViewKey::Digit(digit) = key {
+            self.typed
+                .push(std::char::from_digit(digit as u32, 16).unwrap());
+            if self.typed.len() == self.cursor.digits() {
+                let value = u16::from_str_radix(&self.typed, 16).unwrap();
+                self.typed.clear();
+                self.set(chip8, value);
+                if let Field::Memory(address) = self.cursor {
+                    self.move_to(address as i32 + 1);
+                }
+            }
+            return true;
+        }
+        self.typed.clear();
+        let step = match key {
+            ViewKey::Left => -1,
+            ViewKey::Right => 1,
+            ViewKey::Up => -16,
+            ViewKey::Down => 16,
+            ViewKey::PageUp => -(16 * ROWS as i32),
+            ViewKey::PageDown => 16 * ROWS as i32,
+            ViewKey::Tab => {
+                self.cursor = match self.cursor {
+                    Field::Memory(_) => Field::Pc,
+                    _ => Field::Memory(self.address),
+                };
+                return false;
+            }
+            _ => return false,
+        };
+        match self.cursor {
+            Field::Memory(address) => self.move_to(address as i32 + step),
+            register => {
+                let count = Field::REGISTERS.len() as i32;
+                let index = Field::REGISTERS
+                    .iter()
+                    .position(|&f| f == register)
+                    .unwrap();
+                let index = (index as i32 + step.signum()).rem_euclid(count);
+                self.cursor = Field::REGISTERS[index as usize];
+            }
+        }
+        false
+    }
+
+    fn move_to(&mut self, address: i32) {
+        self.address = address.rem_euclid(4096) as u16;
+        self.cursor = Field::Memory(self.address);
+    }
+
+    fn set(&self, chip8: &mut Chip8, value: u16) {
+        match self.cursor {
+            Field::Memory(address) => chip8.write_memory(address as usize, value as u8),
+            Field::Pc => chip8.pc = value,
+            Field::I => chip8.set_i(value),
+            Field::Delay => chip8.delay_timer = value as u8,
+            Field::Sound => chip8.sound_timer = value as u8,
+            Field::V(x) => chip8.set_v(x as usize, value as u8),
+        }
+    }
+
+    /// Draws the view: registers, then the rows of memory around the cursor
+    pub fn render(&self, chip8: &Chip8, palette: Palette) -> Image {
+        let char_width = GLYPH_WIDTH as u32 * TEXT_SIZE;
+        let line_height = GLYPH_HEIGHT as u32 * TEXT_SIZE;
+        let mut image = Image::new(
+            (4 + 16 * 3 + 1) * char_width,
+            (4 + ROWS as u32) * line_height,
+            palette.background,
+        );
+        let cursor_box = |image: &mut Image, column: u32, line: u32, width: u32| {
+            image.fill(
+                column * char_width,
+                line * line_height,
+                width * char_width,
+                line_height,
+                palette.foreground,
+            );
+        };
+
+        // registers, three lines of (label, field, value, digits) cells
+        let names: Vec<String> = (0..16).map(|x| format!("V{:X}", x)).collect();
+        let mut cells = vec![
+            ("PC", Field::Pc, chip8.pc, 3),
+            ("I", Field::I, chip8.i, 3),
+            ("DT", Field::Delay, chip8.delay_timer as u16, 2),
+            ("ST", Field::Sound, chip8.sound_timer as u16, 2),
+        ];
+        for (x, name) in names.iter().enumerate() {
+            cells.push((name.as_str(), Field::V(x as u8), chip8.v[x] as u16, 2));
+        }
+        for (i, &(name, field, value, digits)) in cells.iter().enumerate() {
+            let (line, column) = match i {
+                0..=3 => (0, 1 + i as u32 * 8),
+                _ => (1 + (i as u32 - 4) / 8, 1 + (i as u32 - 4) % 8 * 6),
+            };
+            let value = format!("{:0width$X}", value, width = digits);
+            let value = self.shown(field, value);
+            let x = column * char_width;
+            image.text(
+                x,
+                line * line_height + 1,
+                name,
+                palette.foreground,
+                TEXT_SIZE,
+            );
+            let column = column + name.len() as u32 + 1;
+            let mut color = palette.foreground;
+            if self.cursor == field {
+                cursor_box(&mut image, column, line, digits as u32);
+                color = palette.background;
+            }
+            let x = column * char_width;
+            image.text(x, line * line_height + 1, &value, color, TEXT_SIZE);
+        }
+
+        // memory, scrolled to keep the cursor in the middle
+        let row = (self.address / 16).saturating_sub(ROWS / 2).min(256 - ROWS);
+        for line in 0..ROWS as u32 {
+            let start = (row + line as u16) * 16;
+            let y = (4 + line) * line_height;
+            image.text(
+                0,
+                y + 1,
+                &format!("{:03X}", start),
+                palette.foreground,
+                TEXT_SIZE,
+            );
+            for offset in 0..16 {
+                let address = start + offset;
+                let column = 4 + offset as u32 * 3;
+                let heat = self.writes.heat(address);
+                let highlight = if address == chip8.pc || address == chip8.pc + 1 {
+                    Some(PC_COLOR)
+                } else if address == chip8.i {
+                    Some(I_COLOR)
+                } else if heat > 0 {
+                    let fade = heat as f32 / FLASH_FRAMES as f32;
+                    Some(palette.background.blend(FLASH_COLOR, fade))
+                } else {
+                    None
+                };
+                if let Some(color) = highlight {
+                    image.fill(column * char_width, y, 2 * char_width, line_height, color);
+                }
+                let mut color = palette.foreground;
+                if self.cursor == Field::Memory(address) {
+                    cursor_box(&mut image, column, 4 + line, 2);
+                    color = palette.background;
+                }
+                let value = format!("{:02X}", chip8.memory[address as usize]);
+                let value = self.shown(Field::Memory(address), value);
+                image.text(column * char_width, y + 1, &value, color, TEXT_SIZE);
+            }
+        }
+        image
+    }
+
+    /// A field's value, with any digits typed for it so far in place of the
+    /// leading ones
+    fn shown(&self, field: Field, value: String) -> String {
+        if self.cursor != field || self.typed.is_empty() {
+            return value;
+        }
+        format!("{}{}", self.typed, &value[self.typed.len()..])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_edit_memory_and_registers() {
+        let mut chip8 = Chip8::initialize();
+        let mut view = MemoryView::new();
+        chip8.add_observer(Box::new(view.writes.clone()));
+
+        // 200: V0 = 0x00, decoded before it is edited
+        chip8.memory[0x200] = 0x60;
+        chip8.emulate_cycle();
+        chip8.pc = 0x200;
+        view.handle(ViewKey::Right, &mut chip8);
+        assert!(view.handle(ViewKey::Digit(4), &mut chip8));
+        assert_eq!(0, chip8.memory[0x201], "should wait for the second digit");
+        view.handle(ViewKey::Digit(2), &mut chip8);
+        assert_eq!(0x42, chip8.memory[0x201]);
+        assert_eq!(Field::Memory(0x202), view.cursor);
+        assert_eq!(FLASH_FRAMES, view.writes.heat(0x201));
+        chip8.emulate_cycle();
+        assert_eq!(0x42, chip8.v[0], "should drop the cached instruction");
+
+        view.handle(ViewKey::Tab, &mut chip8);
+        view.handle(ViewKey::Right, &mut chip8);
+        assert_eq!(Field::I, view.cursor);
+        for digit in [3, 0, 0] {
+            view.handle(ViewKey::Digit(digit), &mut chip8);
+        }
+        assert_eq!(0x300, chip8.i);
+        view.handle(ViewKey::Left, &mut chip8);
+        view.handle(ViewKey::Left, &mut chip8);
+        assert_eq!(Field::V(15), view.cursor, "should wrap around");
+        view.handle(ViewKey::Tab, &mut chip8);
+        assert_eq!(Field::Memory(0x202), view.cursor);
+
+        view.handle(ViewKey::Up, &mut chip8);
+        view.handle(ViewKey::PageUp, &mut chip8);
+        assert_eq!(Field::Memory(0xFF2), view.cursor);
+    }
+
+    #[test]
+    fn test_render() {
+        let mut chip8 = Chip8::initialize();
+        let mut view = MemoryView::new();
+        chip8.add_observer(Box::new(view.writes.clone()));
+        chip8.set_i(0x203);
+        chip8.write_memory(0x205, 1);
+        view.handle(ViewKey::Digit(1), &mut chip8);
+        assert_eq!("10", view.shown(Field::Memory(0x200), String::from("E0")));
+
+        let palette = Palette::default();
+        let image = view.render(&chip8, palette);
+        let char_width = GLYPH_WIDTH as u32 * TEXT_SIZE;
+        // 0x200 is in the row at the middle of the view
+        let y = (4 + ROWS as u32 / 2) * GLYPH_HEIGHT as u32 * TEXT_SIZE;
+        let at = |column: u32| image.pixels[(y * image.width + column * char_width) as usize];
+        assert_eq!(palette.foreground, at(4), "should box the cursor");
+        assert_eq!(PC_COLOR, at(4 + 3));
+        assert_eq!(I_COLOR, at(4 + 9));
+        assert_eq!(
+            palette.background.blend(FLASH_COLOR, 1.0),
+            at(4 + 15),
+            "should flash the write"
+        );
+        view.writes.tick();
+        assert_eq!(FLASH_FRAMES - 1, view.writes.heat(0x205));
+    }
+}
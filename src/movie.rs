@@ -0,0 +1,121 @@
+use std::fs;
+
+/// Keypad input by emulated frame, replayed to make a run repeatable
+///
+/// The file is text: an optional `seed N` line for the random generator,
+/// then one `FRAME +K` or `FRAME -K` line per key going down or up, `K`
+/// being the hex keypad key, or 10-1F for CHIP-8X's second keypad. Blank
+/// lines and `#` comments are skipped.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Movie {
+    pub seed: Option<u64>,
+    /// (frame, key, pressed), in frame order
+    pub events: Vec<(u64, usize, bool)>,
+    /// How many events playback has handed out
+    next: usize,
+}
+
+impl Movie {
+    pub fn new(seed: Option<u64>) -> Self {
+        Movie {
+            seed,
+            ..Movie::default()
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("Error reading {}: {:?}", path, e))?;
+        Movie::parse(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut movie = Movie::default();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("line {}: expected `FRAME +KEY` or `seed N`", number + 1);
+            let (first, second) = line.split_once(' ').ok_or_else(invalid)?;
+            let second = second.trim();
+            if first == "seed" {
+                movie.seed = Some(second.parse().map_err(|_| invalid())?);
+                continue;
+            }
+            let frame: u64 = first.parse().map_err(|_| invalid())?;
+            let pressed = match second.chars().next() {
+                Some('+') => true,
+                Some('-') => false,
+                _ => return Err(invalid()),
+            };
+            let key = usize::from_str_radix(&second[1..], 16).map_err(|_| invalid())?;
+            if key > 0x1F
+                || movie
+                    .events
+                    .last()
+                    .is_some_and(|&(last, _, _)| frame < last)
+            {
+                return Err(invalid());
+            }
+            movie.events.push((frame, key, pressed));
+        }
+        Ok(movie)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_string()).map_err(|e| format!("Error writing {}: {:?}", path, e))
+    }
+
+    /// Records a key going down or up before frame `frame` runs
+    pub fn record(&mut self, frame: u64, key: usize, pressed: bool) {
+        self.events.push((frame, key, pressed));
+    }
+
+    /// The events for frame `frame`, for playback one frame after another
+    pub fn take(&mut self, frame: u64) -> &[(u64, usize, bool)] {
+        let start = self.next;
+        while self
+            .events
+            .get(self.next)
+            .is_some_and(|&(at, _, _)| at <= frame)
+        {
+            self.next += 1;
+        }
+        &self.events[start..self.next]
+    }
+}
+
+impl std::fmt::Display for Movie {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed {}", seed)?;
+        }
+        for &(frame, key, pressed) in &self.events {
+            writeln!(f, "{} {}{:X}", frame, if pressed { '+' } else { '-' }, key)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_play() {
+        let mut movie = Movie::parse("# title screen\nseed 42\n\n10 +5\n12 -5\n12 +A\n").unwrap();
+        assert_eq!(Some(42), movie.seed);
+        assert_eq!("seed 42\n10 +5\n12 -5\n12 +A\n", movie.to_string());
+
+        assert!(movie.take(9).is_empty());
+        assert_eq!([(10, 5, true)], movie.take(10));
+        assert_eq!([(12, 5, false), (12, 0xA, true)], movie.take(12));
+        assert!(movie.take(13).is_empty());
+
+        assert!(Movie::parse("10 5").is_err());
+        assert!(Movie::parse("10 +G").is_err());
+        assert!(Movie::parse("10 +20").is_err());
+        assert!(Movie::parse("10 +1\n9 -1").is_err(), "should be in order");
+    }
+}
@@ -0,0 +1,214 @@
+use crate::chip8::Fault;
+
+/// A register an instruction wrote to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+}
+
+/// One of the two 60Hz timers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timer {
+    Delay,
+    Sound,
+}
+
+/// Callbacks for watching the core run, e.g. from a debugger or a profiler
+///
+/// Every method does nothing by default, so an observer only implements the
+/// ones it cares about. Observers are installed with `Chip8::add_observer`;
+/// with none installed each hook costs an empty loop.
+pub trait Observer {
+    /// An opcode was fetched from `pc`, before it runs
+    fn instruction_fetched(&mut self, _pc: u16, _opcode: u16) {}
+
+    /// An instruction read a byte of memory as data
+    fn memory_read(&mut self, _address: u16, _value: u8) {}
+
+    /// A byte of memory was written through `Chip8::write_memory`
+    fn memory_write(&mut self, _address: u16, _value: u8) {}
+
+    /// An instruction wrote a register, even if the value did not change
+    fn register_write(&mut self, _register: Register, _value: u16) {}
+
+    /// `Dxyn` drew `height` rows of the sprite at `sprite` to (`x`, `y`)
+    fn draw(&mut self, _x: u8, _y: u8, _sprite: u16, _height: u8, _collision: bool) {}
+
+    /// `2nnn` called `to` from the instruction at `from`
+    fn call(&mut self, _from: u16, _to: u16) {}
+
+    /// `00EE` at `from` returned to `to`
+    fn ret(&mut self, _from: u16, _to: u16) {}
+
+    /// A timer counted down to 0
+    fn timer_expired(&mut self, _timer: Timer) {}
+
+    /// `Fx0A` is still waiting for a key, called on each cycle it blocks
+    fn key_wait(&mut self, _register: u8) {}
+
+    /// The machine stopped on a fault, at the instruction that caused it
+    fn fault(&mut self, _fault: Fault) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chip8::Chip8;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Fetch(u16, u16),
+        Read(u16, u8),
+        Write(u16, u8),
+        Register(Register, u16),
+        Draw(u8, u8, u16, u8, bool),
+        Call(u16, u16),
+        Ret(u16, u16),
+        Timer(Timer),
+        KeyWait(u8),
+    }
+
+    struct Recorder(Rc<RefCell<Vec<Event>>>);
+
+    impl Observer for Recorder {
+        fn instruction_fetched(&mut self, pc: u16, opcode: u16) {
+            self.0.borrow_mut().push(Event::Fetch(pc, opcode));
+        }
+
+        fn memory_read(&mut self, address: u16, value: u8) {
+            self.0.borrow_mut().push(Event::Read(address, value));
+        }
+
+        fn memory_write(&mut self, address: u16, value: u8) {
+            self.0.borrow_mut().push(Event::Write(address, value));
+        }
+
+        fn register_write(&mut self, register: Register, value: u16) {
+            self.0.borrow_mut().push(Event::Register(register, value));
+        }
+
+        fn draw(&mut self, x: u8, y: u8, sprite: u16, height: u8, collision: bool) {
+            self.0
+                .borrow_mut()
+                .push(Event::Draw(x, y, sprite, height, collision));
+        }
+
+        fn call(&mut self, from: u16, to: u16) {
+            self.0.borrow_mut().push(Event::Call(from, to));
+        }
+
+        fn ret(&mut self, from: u16, to: u16) {
+            self.0.borrow_mut().push(Event::Ret(from, to));
+        }
+
+        fn timer_expired(&mut self, timer: Timer) {
+            self.0.borrow_mut().push(Event::Timer(timer));
+        }
+
+        fn key_wait(&mut self, register: u8) {
+            self.0.borrow_mut().push(Event::KeyWait(register));
+        }
+    }
+
+    /// Loads a program and installs a recorder, returning its events
+    fn observed(program: &[u16]) -> (Chip8, Rc<RefCell<Vec<Event>>>) {
+        let mut chip8 = Chip8::initialize();
+        for (i, word) in program.iter().enumerate() {
+            chip8.write_memory(0x200 + i * 2, (word >> 8) as u8);
+            chip8.write_memory(0x200 + i * 2 + 1, *word as u8);
+        }
+        let events = Rc::new(RefCell::new(Vec::new()));
+        chip8.add_observer(Box::new(Recorder(events.clone())));
+        (chip8, events)
+    }
+
+    #[test]
+    fn test_fetch_and_register_write() {
+        let (mut chip8, events) = observed(&[0x6A05, 0xA300]);
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        assert_eq!(
+            vec![
+                Event::Fetch(0x200, 0x6A05),
+                Event::Register(Register::V(0xA), 5),
+                Event::Fetch(0x202, 0xA300),
+                Event::Register(Register::I, 0x300),
+            ],
+            *events.borrow()
+        );
+    }
+
+    #[test]
+    fn test_memory_read_and_write() {
+        // I = 0x300, store V0-V1, load V0
+        let (mut chip8, events) = observed(&[0xA300, 0x6007, 0xF155, 0xF065]);
+        for _ in 0..4 {
+            chip8.emulate_cycle();
+        }
+        let events = events.borrow();
+        assert!(events.contains(&Event::Write(0x300, 7)));
+        assert!(events.contains(&Event::Write(0x301, 0)));
+        assert!(events.contains(&Event::Read(0x300, 7)));
+    }
+
+    #[test]
+    fn test_draw_with_collision() {
+        // draw the "0" glyph twice at (0, 0)
+        let (mut chip8, events) = observed(&[0xD005, 0xD005]);
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        let events = events.borrow();
+        assert!(events.contains(&Event::Draw(0, 0, 0, 5, false)));
+        assert!(events.contains(&Event::Draw(0, 0, 0, 5, true)));
+        assert_eq!(
+            10,
+            events
+                .iter()
+                .filter(|event| matches!(event, Event::Read(0..=4, _)))
+                .count(),
+            "should read each sprite row once per draw"
+        );
+    }
+
+    #[test]
+    fn test_call_and_return() {
+        let (mut chip8, events) = observed(&[0x2204, 0x0000, 0x00EE]);
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        let events = events.borrow();
+        assert!(events.contains(&Event::Call(0x200, 0x204)));
+        assert!(events.contains(&Event::Ret(0x204, 0x202)));
+    }
+
+    #[test]
+    fn test_timer_expiry_and_key_wait() {
+        let (mut chip8, events) = observed(&[0xF30A]);
+        chip8.delay_timer = 1;
+        chip8.sound_timer = 2;
+        chip8.emulate_cycle();
+        chip8.update_timers();
+        chip8.update_timers();
+        chip8.update_timers();
+        let events = events.borrow();
+        assert!(events.contains(&Event::KeyWait(3)));
+        assert_eq!(
+            1,
+            events
+                .iter()
+                .filter(|event| **event == Event::Timer(Timer::Delay))
+                .count()
+        );
+        assert!(events.contains(&Event::Timer(Timer::Sound)));
+    }
+
+    #[test]
+    fn test_remove_observers() {
+        let (mut chip8, events) = observed(&[0x6005]);
+        chip8.take_observers();
+        chip8.emulate_cycle();
+        assert!(events.borrow().is_empty());
+    }
+}
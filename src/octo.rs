@@ -0,0 +1,415 @@
+use crate::analyze::Traversal;
+use crate::decode::{Instruction, Op};
+use crate::disasm::mnemonic;
+use crate::platform::Platform;
+use crate::symbols::SymbolMap;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Data bytes per line outside sprites
+const BYTES_PER_LINE: usize = 8;
+
+/// What each ROM address turns into in the source
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Item {
+    Code(u16),
+    Data(u8),
+}
+
+/// Addresses a recorded run executed, read from a `--trace` file
+///
+/// Only the address column is used, so the trace can come from any ROM
+/// labelling.
+pub fn coverage(trace: &str) -> Result<BTreeSet<u16>, String> {
+    let mut executed = BTreeSet::new();
+    for (number, line) in trace.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let address = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|field| u16::from_str_radix(field, 16).ok())
+            .ok_or_else(|| format!("Line {} is not a trace line: {}", number + 1, line))?;
+        executed.insert(address);
+    }
+    Ok(executed)
+}
+
+/// Octo assembler source that reassembles to exactly `rom`
+///
+/// Code is what traversal reaches from the platform's start plus any address
+/// in `executed`, from a recorded run; everything else is data. Sprites drawn
+/// through a known `I` get a byte per line with the bits drawn in a comment,
+/// other data goes eight bytes to a line. Jump, call and `I` targets get labels, from
+/// `symbols` when they have one. Words Octo has no instruction for, or that
+/// would not reassemble to the same bytes, are written as bytes with the
+/// instruction in a comment. ROMs loaded anywhere but 0x200 start with an
+/// `:org` so the labels keep their addresses.
+pub fn source(
+    rom: &[u8],
+    platform: Platform,
+    symbols: &SymbolMap,
+    executed: &BTreeSet<u16>,
+) -> String {
+    let traversal = Traversal::run(rom, platform);
+    let origin = traversal.origin;
+    let mut code: BTreeSet<u16> = traversal.code.keys().copied().collect();
+    code.extend(
+        executed
+            .iter()
+            .filter(|&&address| traversal.word(address).is_some()),
+    );
+
+    let mut items = BTreeMap::new();
+    let mut address = origin;
+    while address < traversal.end() {
+        match traversal.word(address) {
+            Some(word) if code.contains(&address) && !code.contains(&(address + 1)) => {
+                items.insert(address, Item::Code(word));
+                address += 2;
+            }
+            _ => {
+                items.insert(address, Item::Data(rom[(address - origin) as usize]));
+                address += 1;
+            }
+        }
+    }
+    let labels = labels(&items, origin, symbols);
+
+    let mut out = String::new();
+    if origin != 0x200 {
+        out.push_str(&format!(":org {:#05x}\n", origin));
+    }
+    let mut row: Vec<String> = Vec::new();
+    let flush = |out: &mut String, row: &mut Vec<String>| {
+        if !row.is_empty() {
+            out.push_str(&format!("  {}\n", row.join(" ")));
+            row.clear();
+        }
+    };
+    for (&address, &item) in &items {
+        if let Some(names) = labels.get(&address) {
+            flush(&mut out, &mut row);
+            for name in names {
+                out.push_str(&format!(": {}\n", name));
+            }
+        }
+        match item {
+            Item::Code(word) => {
+                flush(&mut out, &mut row);
+                let next_is_code = matches!(items.get(&(address + 2)), Some(Item::Code(_)));
+                let line = match statement(word, &labels, next_is_code) {
+                    Some(statement) => statement,
+                    None => format!(
+                        "{:#04x} {:#04x}  # {}",
+                        word >> 8,
+                        word & 0xFF,
+                        mnemonic(word, symbols)
+                    ),
+                };
+                out.push_str(&format!("  {}\n", line));
+            }
+            Item::Data(byte) if traversal.in_sprite(address) => {
+                flush(&mut out, &mut row);
+                let bits: String = (0..8)
+                    .map(|bit| if byte << bit & 0x80 != 0 { '#' } else { '.' })
+                    .collect();
+                out.push_str(&format!("  {:#04x}  # {}\n", byte, bits));
+            }
+            Item::Data(byte) => {
+                row.push(format!("{:#04x}", byte));
+                let next_is_row = matches!(items.get(&(address + 1)), Some(Item::Data(_)))
+                    && !traversal.in_sprite(address + 1);
+                if row.len() == BYTES_PER_LINE || !next_is_row {
+                    flush(&mut out, &mut row);
+                }
+            }
+        }
+    }
+    flush(&mut out, &mut row);
+    out
+}
+
+/// Names for the addresses the source refers to, or that `symbols` names
+///
+/// `main` has to come first, at `origin`, so Octo starts there without adding
+/// a jump. Addresses in the middle of an instruction cannot be labelled and
+/// are left as numbers.
+fn labels(
+    items: &BTreeMap<u16, Item>,
+    origin: u16,
+    symbols: &SymbolMap,
+) -> BTreeMap<u16, Vec<String>> {
+    let mut targets = BTreeSet::new();
+    for item in items.values() {
+        if let Item::Code(word) = *item {
+            let ins = Instruction::decode(word);
+            if matches!(ins.op, Op::Jp | Op::Call | Op::JpV0Addr | Op::LdIAddr) {
+                targets.insert(ins.nnn);
+            }
+        }
+    }
+    targets.extend(symbols.labels.iter().map(|label| label.address));
+
+    let mut labels: BTreeMap<u16, Vec<String>> = BTreeMap::new();
+    labels.insert(origin, vec![String::from("main")]);
+    for address in targets {
+        let name = match (symbols.label_at(address), items.get(&address)) {
+            (_, None) => continue,
+            (Some("main"), _) => continue,
+            (Some(name), _) => name.to_string(),
+            (None, _) if address == origin => continue,
+            (None, Some(Item::Code(_))) => format!("code_{:03X}", address),
+            (None, Some(Item::Data(_))) => format!("data_{:03X}", address),
+        };
+        labels.entry(address).or_default().push(name);
+    }
+    labels
+}
+
+/// The Octo statement for an instruction, if there is one that assembles back
+/// to `word`
+fn statement(word: u16, labels: &BTreeMap<u16, Vec<String>>, next_is_code: bool) -> Option<String> {
+    let ins = Instruction::decode(word);
+    let (x, y, kk) = (ins.x, ins.y, ins.kk);
+    let label = labels.get(&ins.nnn).map(|names| names[0].clone());
+    let target = label.clone().unwrap_or_else(|| format!("{:#05x}", ins.nnn));
+    // Octo's `if ... then` skips when the condition is false
+    let skip = |condition: String| next_is_code.then(|| format!("if {} then", condition));
+    Some(match ins.op {
+        Op::Cls => String::from("clear"),
+        Op::Ret => String::from("return"),
+        Op::Jp => format!("jump {}", target),
+        Op::Call => label?,
+        Op::SeVxByte => skip(format!("v{:x} != {:#04x}", x, kk))?,
+        Op::SneVxByte => skip(format!("v{:x} == {:#04x}", x, kk))?,
+        Op::SeVxVy => skip(format!("v{:x} != v{:x}", x, y))?,
+        Op::SneVxVy => skip(format!("v{:x} == v{:x}", x, y))?,
+        Op::SkpVx => skip(format!("v{:x} -key", x))?,
+        Op::SknpVx => skip(format!("v{:x} key", x))?,
+        Op::LdVxByte => format!("v{:x} := {:#04x}", x, kk),
+        Op::AddVxByte => format!("v{:x} += {:#04x}", x, kk),
+        Op::LdVxVy => format!("v{:x} := v{:x}", x, y),
+        Op::OrVxVy => format!("v{:x} |= v{:x}", x, y),
+        Op::AndVxVy => format!("v{:x} &= v{:x}", x, y),
+        Op::XorVxVy => format!("v{:x} ^= v{:x}", x, y),
+        Op::AddVxVy => format!("v{:x} += v{:x}", x, y),
+        Op::SubVxVy => format!("v{:x} -= v{:x}", x, y),
+        Op::ShrVxVy => format!("v{:x} >>= v{:x}", x, y),
+        Op::SubnVxVy => format!("v{:x} =- v{:x}", x, y),
+        Op::ShlVxVy => format!("v{:x} <<= v{:x}", x, y),
+        Op::LdIAddr => format!("i := {}", target),
+        Op::JpV0Addr => format!("jump0 {}", target),
+        Op::RndVxByte => format!("v{:x} := random {:#04x}", x, kk),
+        Op::Drw => format!("sprite v{:x} v{:x} {}", x, y, ins.n),
+        Op::LdVxDt => format!("v{:x} := delay", x),
+        Op::LdVxK => format!("v{:x} := key", x),
+        Op::LdDtVx => format!("delay := v{:x}", x),
+        Op::LdStVx => format!("buzzer := v{:x}", x),
+        Op::AddIVx => format!("i += v{:x}", x),
+        Op::LdFVx => format!("i := hex v{:x}", x),
+        Op::LdBVx => format!("bcd v{:x}", x),
+        Op::LdIVx => format!("save v{:x}", x),
+        Op::LdVxI => format!("load v{:x}", x),
+        Op::Sys | Op::Invalid => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Assembles the subset of Octo that `source` writes
+    fn assemble(source: &str) -> Vec<u8> {
+        let tokens: Vec<&str> = source
+            .lines()
+            .flat_map(|line| line.split('#').next().unwrap().split_whitespace())
+            .collect();
+        let mut out: Vec<u8> = Vec::new();
+        let mut origin = 0x200;
+        let mut labels = HashMap::new();
+        let mut fixups = Vec::new();
+        let number = |token: &str| match token.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16).unwrap(),
+            None => token.parse().unwrap(),
+        };
+        let reg = |token: &str| u16::from_str_radix(&token[1..], 16).unwrap();
+        let mut i = 0;
+        while i < tokens.len() {
+            let t = &tokens[i..];
+            // (word, label to add to it, tokens used)
+            let (word, target, used) = match t[0] {
+                ":" => {
+                    labels.insert(t[1], origin + out.len() as u16);
+                    i += 2;
+                    continue;
+                }
+                ":org" => {
+                    origin = number(t[1]);
+                    i += 2;
+                    continue;
+                }
+                byte if byte.starts_with("0x") => {
+                    out.push(number(byte) as u8);
+                    i += 1;
+                    continue;
+                }
+                "clear" => (0x00E0, None, 1),
+                "return" => (0x00EE, None, 1),
+                "jump" => (0x1000, Some(t[1]), 2),
+                "jump0" => (0xB000, Some(t[1]), 2),
+                "sprite" => (
+                    0xD000 | reg(t[1]) << 8 | reg(t[2]) << 4 | number(t[3]),
+                    None,
+                    4,
+                ),
+                "bcd" => (0xF033 | reg(t[1]) << 8, None, 2),
+                "save" => (0xF055 | reg(t[1]) << 8, None, 2),
+                "load" => (0xF065 | reg(t[1]) << 8, None, 2),
+                "delay" => (0xF015 | reg(t[2]) << 8, None, 3),
+                "buzzer" => (0xF018 | reg(t[2]) << 8, None, 3),
+                "i" => match (t[1], t[2]) {
+                    ("+=", vx) => (0xF01E | reg(vx) << 8, None, 3),
+                    (":=", "hex") => (0xF029 | reg(t[3]) << 8, None, 4),
+                    (_, target) => (0xA000, Some(target), 3),
+                },
+                "if" => {
+                    let x = reg(t[1]) << 8;
+                    match (t[2], t[3]) {
+                        ("key", _) => (0xE0A1 | x, None, 4),
+                        ("-key", _) => (0xE09E | x, None, 4),
+                        ("!=", vy) if vy.starts_with('v') => (0x5000 | x | reg(vy) << 4, None, 5),
+                        ("==", vy) if vy.starts_with('v') => (0x9000 | x | reg(vy) << 4, None, 5),
+                        ("!=", kk) => (0x3000 | x | number(kk), None, 5),
+                        (_, kk) => (0x4000 | x | number(kk), None, 5),
+                    }
+                }
+                vx if vx.starts_with('v') && vx.len() == 2 => {
+                    let x = reg(vx) << 8;
+                    let alu = |n: u16| 0x8000 | x | reg(t[2]) << 4 | n;
+                    match (t[1], t[2]) {
+                        (":=", "random") => (0xC000 | x | number(t[3]), None, 4),
+                        (":=", "delay") => (0xF007 | x, None, 3),
+                        (":=", "key") => (0xF00A | x, None, 3),
+                        (":=", vy) if vy.starts_with('v') => (alu(0), None, 3),
+                        (":=", kk) => (0x6000 | x | number(kk), None, 3),
+                        ("+=", vy) if vy.starts_with('v') => (alu(4), None, 3),
+                        ("+=", kk) => (0x7000 | x | number(kk), None, 3),
+                        ("|=", _) => (alu(1), None, 3),
+                        ("&=", _) => (alu(2), None, 3),
+                        ("^=", _) => (alu(3), None, 3),
+                        ("-=", _) => (alu(5), None, 3),
+                        (">>=", _) => (alu(6), None, 3),
+                        ("=-", _) => (alu(7), None, 3),
+                        ("<<=", _) => (alu(0xE), None, 3),
+                        other => panic!("unexpected {:?}", other),
+                    }
+                }
+                call => (0x2000, Some(call), 1),
+            };
+            if let Some(target) = target {
+                fixups.push((out.len(), target));
+            }
+            out.extend(word.to_be_bytes());
+            i += used;
+        }
+        for (offset, target) in fixups {
+            let address = match labels.get(target) {
+                Some(&address) => address,
+                None => number(target),
+            };
+            out[offset] |= (address >> 8) as u8;
+            out[offset + 1] |= address as u8;
+        }
+        out
+    }
+
+    // 200: call 20C; 202: I = 214; 204: draw; 206: skip unless key V0;
+    // 208: jump 200; 20A: jump 20A; 20C: V1 += 2; 20E: skip if V1 == 3;
+    // 210: ret; 212: ret; 214: sprite; 216: unused
+    const PROGRAM: [u8; 25] = [
+        0x22, 0x0C, 0xA2, 0x14, 0xD0, 0x12, 0xE0, 0xA1, 0x12, 0x00, 0x12, 0x0A, 0x71, 0x02, 0x31,
+        0x03, 0x00, 0xEE, 0x00, 0xEE, 0xF0, 0x90, 0x01, 0x02, 0x03,
+    ];
+
+    #[test]
+    fn test_source() {
+        let symbols = SymbolMap::parse("0x20C count").unwrap();
+        let source = source(&PROGRAM, Platform::Chip8, &symbols, &BTreeSet::new());
+        assert_eq!(
+            "\
+: main
+  count
+  i := data_214
+  sprite v0 v1 2
+  if v0 key then
+  jump main
+: code_20A
+  jump code_20A
+: count
+  v1 += 0x02
+  if v1 != 0x03 then
+  return
+  return
+: data_214
+  0xf0  # ####....
+  0x90  # #..#....
+  0x01 0x02 0x03
+",
+            source
+        );
+        assert_eq!(PROGRAM.to_vec(), assemble(&source));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let trace = "main      200  2206  CALL 0x206\nmain+6    206  00EE  RET\n";
+        let executed = coverage(trace).unwrap();
+        assert_eq!(vec![0x200, 0x206], executed.into_iter().collect::<Vec<_>>());
+        assert!(coverage("hello world").is_err());
+
+        // 202: JP V0, 204 with V0 unknown; only the run shows what is code
+        let rom = [0xC0, 0x01, 0xB2, 0x04, 0x01, 0x23, 0x12, 0x06];
+        let executed = BTreeSet::from([0x204, 0x206]);
+        let source = source(&rom, Platform::Chip8, &SymbolMap::default(), &executed);
+        assert_eq!(
+            "\
+: main
+  v0 := random 0x01
+  jump0 code_204
+: code_204
+  0x01 0x23  # SYS 0x123
+: code_206
+  jump code_206
+",
+            source
+        );
+        assert_eq!(rom.to_vec(), assemble(&source));
+    }
+
+    #[test]
+    fn test_source_at_origin() {
+        // 300: I = 304; 302: jump 302; 304: data
+        let rom = [0xA3, 0x04, 0x13, 0x02, 0x42];
+        let source = source(
+            &rom,
+            Platform::Chip8x,
+            &SymbolMap::default(),
+            &BTreeSet::new(),
+        );
+        assert_eq!(
+            "\
+:org 0x300
+: main
+  i := data_304
+: code_302
+  jump code_302
+: data_304
+  0x42
+",
+            source
+        );
+        assert_eq!(rom.to_vec(), assemble(&source));
+    }
+}
@@ -0,0 +1,234 @@
+use crate::chip8::FONTS;
+use std::time::{Duration, Instant};
+
+/// How long a transient message stays on screen
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+
+/// Width of a glyph in font pixels, plus one column of spacing
+pub const GLYPH_WIDTH: usize = 5;
+
+/// Height of a glyph in font pixels, plus one row of spacing
+pub const GLYPH_HEIGHT: usize = 6;
+
+/// On-screen display of emulation stats and transient messages
+pub struct Osd {
+    pub visible: bool,
+    pub paused: bool,
+    pub nominal_ips: f32,
+    message: Option<(String, Instant)>,
+    report: Vec<String>,
+    frames: u32,
+    cycles: u32,
+    sample_start: Instant,
+    fps: f32,
+    ips: f32,
+}
+
+impl Osd {
+    /// Creates a hidden OSD; `nominal_ips` is the instruction rate that counts as full speed
+    pub fn new(nominal_ips: f32) -> Self {
+        Osd {
+            visible: false,
+            paused: false,
+            nominal_ips,
+            message: None,
+            report: Vec::new(),
+            frames: 0,
+            cycles: 0,
+            sample_start: Instant::now(),
+            fps: 0.0,
+            ips: 0.0,
+        }
+    }
+
+    /// Shows a message for a couple of seconds, even while the stats are hidden
+    pub fn show_message(&mut self, message: &str) {
+        self.message = Some((message.to_string(), Instant::now()));
+    }
+
+    /// Shows lines that stay on screen until cleared, such as a crash report
+    pub fn show_report(&mut self, lines: Vec<String>) {
+        self.report = lines;
+    }
+
+    pub fn clear_report(&mut self) {
+        self.report.clear();
+    }
+
+    /// Counts executed instructions
+    pub fn record_cycles(&mut self, cycles: u32) {
+        self.cycles += cycles;
+        self.sample();
+    }
+
+    /// Counts a presented frame
+    pub fn record_frame(&mut self) {
+        self.frames += 1;
+        self.sample();
+    }
+
+    /// Updates the rates about once a second
+    fn sample(&mut self) {
+        let elapsed = self.sample_start.elapsed();
+        if elapsed >= Duration::from_secs(1) {
+            let seconds = elapsed.as_secs_f32();
+            self.fps = self.frames as f32 / seconds;
+            self.ips = self.cycles as f32 / seconds;
+            self.frames = 0;
+            self.cycles = 0;
+            self.sample_start = Instant::now();
+        }
+    }
+
+    /// Whether there is anything to draw
+    pub fn is_active(&self) -> bool {
+        self.visible || self.paused || !self.report.is_empty() || self.current_message().is_some()
+    }
+
+    fn current_message(&self) -> Option<&str> {
+        match &self.message {
+            Some((message, shown)) if shown.elapsed() < MESSAGE_DURATION => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Lines of text to draw, top to bottom
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.visible {
+            lines.push(format!("FPS {:.0}", self.fps));
+            lines.push(format!("IPS {:.0}", self.ips));
+            lines.push(format!("SPEED {:.0}%", self.ips / self.nominal_ips * 100.0));
+        }
+        if self.paused {
+            lines.push(String::from("PAUSED"));
+        }
+        lines.extend(self.report.iter().cloned());
+        if let Some(message) = self.current_message() {
+            lines.push(message.to_string());
+        }
+        lines
+    }
+}
+
+/// Font pixel rows of a character, left-aligned in the high nibble like `FONTS`
+pub fn glyph(c: char) -> [u8; 5] {
+    let c = c.to_ascii_uppercase();
+    if let Some(digit) = c.to_digit(16) {
+        let start = digit as usize * 5;
+        let mut rows = [0; 5];
+        rows.copy_from_slice(&FONTS[start..start + 5]);
+        return rows;
+    }
+    match c {
+        'G' => [0xF0, 0x80, 0xB0, 0x90, 0xF0],
+        'H' => [0x90, 0x90, 0xF0, 0x90, 0x90],
+        'I' => [0xE0, 0x40, 0x40, 0x40, 0xE0],
+        'J' => [0x30, 0x10, 0x10, 0x90, 0x60],
+        'K' => [0x90, 0xA0, 0xC0, 0xA0, 0x90],
+        'L' => [0x80, 0x80, 0x80, 0x80, 0xF0],
+        'M' => [0x90, 0xF0, 0xF0, 0x90, 0x90],
+        'N' => [0x90, 0xD0, 0xF0, 0xB0, 0x90],
+        'O' => [0x60, 0x90, 0x90, 0x90, 0x60],
+        'P' => [0xE0, 0x90, 0xE0, 0x80, 0x80],
+        'Q' => [0x60, 0x90, 0x90, 0xB0, 0x70],
+        'R' => [0xE0, 0x90, 0xE0, 0xA0, 0x90],
+        'S' => [0x70, 0x80, 0x60, 0x10, 0xE0],
+        'T' => [0xE0, 0x40, 0x40, 0x40, 0x40],
+        'U' => [0x90, 0x90, 0x90, 0x90, 0xF0],
+        'V' => [0xA0, 0xA0, 0xA0, 0xA0, 0x40],
+        'W' => [0x90, 0x90, 0xF0, 0xF0, 0x90],
+        'X' => [0x90, 0x90, 0x60, 0x90, 0x90],
+        'Y' => [0xA0, 0xA0, 0x40, 0x40, 0x40],
+        'Z' => [0xF0, 0x10, 0x60, 0x80, 0xF0],
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x40],
+        ',' => [0x00, 0x00, 0x00, 0x40, 0x80],
+        ':' => [0x00, 0x40, 0x00, 0x40, 0x00],
+        '-' => [0x00, 0x00, 0xE0, 0x00, 0x00],
+        '+' => [0x00, 0x40, 0xE0, 0x40, 0x00],
+        '=' => [0x00, 0xE0, 0x00, 0xE0, 0x00],
+        '/' => [0x10, 0x10, 0x20, 0x40, 0x80],
+        '%' => [0x90, 0x10, 0x60, 0x80, 0x90],
+        '#' => [0x50, 0xF0, 0x50, 0xF0, 0x50],
+        '(' => [0x20, 0x40, 0x40, 0x40, 0x20],
+        ')' => [0x40, 0x20, 0x20, 0x20, 0x40],
+        '[' => [0x60, 0x40, 0x40, 0x40, 0x60],
+        ']' => [0x60, 0x20, 0x20, 0x20, 0x60],
+        '>' => [0x40, 0x20, 0x10, 0x20, 0x40],
+        '<' => [0x20, 0x40, 0x80, 0x40, 0x20],
+        '!' => [0x40, 0x40, 0x40, 0x00, 0x40],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0xF0],
+        _ => [0xE0, 0x10, 0x60, 0x00, 0x40], // "?"
+    }
+}
+
+/// Font pixels that are lit for a line of text, as (x, y) from its top left
+pub fn text_pixels(text: &str) -> Vec<(usize, usize)> {
+    let mut pixels = Vec::new();
+    for (i, c) in text.chars().enumerate() {
+        for (y, row) in glyph(c).iter().enumerate() {
+            for x in 0..4 {
+                if row & (0x80 >> x) != 0 {
+                    pixels.push((i * GLYPH_WIDTH + x, y));
+                }
+            }
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glyph_reuses_fonts() {
+        assert_eq!(FONTS[0..5], glyph('0'));
+        assert_eq!(FONTS[75..80], glyph('F'));
+        assert_eq!(glyph('A'), glyph('a'));
+    }
+
+    #[test]
+    fn test_text_pixels() {
+        let pixels = text_pixels(" 1");
+        assert!(pixels.iter().all(|&(x, _)| x >= GLYPH_WIDTH));
+        // "1" is 0b00100000 0b01100000 0b00100000 0b00100000 0b01110000
+        assert_eq!(
+            vec![
+                (7, 0),
+                (6, 1),
+                (7, 1),
+                (7, 2),
+                (7, 3),
+                (6, 4),
+                (7, 4),
+                (8, 4)
+            ],
+            pixels
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut osd = Osd::new(600.0);
+        assert!(!osd.is_active());
+        assert!(osd.lines().is_empty());
+
+        osd.show_message("State saved to slot 3");
+        assert!(osd.is_active());
+        assert_eq!(vec!["State saved to slot 3"], osd.lines());
+
+        osd.visible = true;
+        osd.paused = true;
+        let lines = osd.lines();
+        assert_eq!("FPS 0", lines[0]);
+        assert_eq!("SPEED 0%", lines[2]);
+        assert_eq!("PAUSED", lines[3]);
+
+        osd.show_report(vec![String::from("Stack overflow at 2A4")]);
+        assert_eq!("Stack overflow at 2A4", osd.lines()[4]);
+        osd.clear_report();
+        assert_eq!(5, osd.lines().len());
+    }
+}
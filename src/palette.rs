@@ -0,0 +1,213 @@
+/// An RGB color
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Parses a `#RRGGBB` or `RRGGBB` hex color
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        let digits = hex.trim().trim_start_matches('#');
+        if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Not a hex color: {:?}", hex));
+        }
+        let channel = |i: usize| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("Not a hex color: {:?}", hex))
+        };
+        Ok(Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    /// Mixes two colors, `amount` of 0.0 being `self` and 1.0 being `other`
+    pub fn blend(self, other: Rgb, amount: f32) -> Rgb {
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+        Rgb(
+            mix(self.0, other.0),
+            mix(self.1, other.1),
+            mix(self.2, other.2),
+        )
+    }
+}
+
+/// Display colors for unlit and lit pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub background: Rgb,
+    pub foreground: Rgb,
+}
+
+/// Built-in palettes, by name
+pub const PRESETS: [(&str, Palette); 5] = [
+    (
+        "classic",
+        Palette {
+            background: Rgb(0, 0, 0),
+            foreground: Rgb(255, 255, 255),
+        },
+    ),
+    (
+        "amber",
+        Palette {
+            background: Rgb(26, 13, 0),
+            foreground: Rgb(255, 176, 0),
+        },
+    ),
+    (
+        "green",
+        Palette {
+            background: Rgb(0, 20, 0),
+            foreground: Rgb(51, 255, 51),
+        },
+    ),
+    (
+        "gameboy",
+        Palette {
+            background: Rgb(155, 188, 15),
+            foreground: Rgb(15, 56, 15),
+        },
+    ),
+    (
+        "high-contrast",
+        Palette {
+            background: Rgb(0, 0, 0),
+            foreground: Rgb(255, 255, 0),
+        },
+    ),
+];
+
+/// The VP-590 color board's foreground colors, by CHIP-8X color number
+pub const CHIP8X_COLORS: [Rgb; 8] = [
+    Rgb(0, 0, 0),
+    Rgb(255, 0, 0),
+    Rgb(0, 0, 255),
+    Rgb(255, 0, 255),
+    Rgb(0, 255, 0),
+    Rgb(255, 255, 0),
+    Rgb(0, 255, 255),
+    Rgb(255, 255, 255),
+];
+
+/// The backgrounds CHIP-8X's `02A0` steps through, starting with dark blue
+pub const CHIP8X_BACKGROUNDS: [Rgb; 4] =
+    [Rgb(0, 0, 128), Rgb(0, 0, 0), Rgb(0, 128, 0), Rgb(128, 0, 0)];
+
+impl Palette {
+    /// Looks up a built-in palette
+    pub fn preset(name: &str) -> Option<Self> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, palette)| *palette)
+    }
+
+    /// The colors of a CHIP-8X pixel in a zone of color `color`
+    pub fn chip8x(color: u8, background: u8) -> Self {
+        Palette {
+            background: CHIP8X_BACKGROUNDS[background as usize % 4],
+            foreground: CHIP8X_COLORS[color as usize % 8],
+        }
+    }
+
+    /// Color of a pixel with the given brightness, 0.0 being off and 1.0 being fully lit
+    pub fn shade(&self, brightness: f32) -> Rgb {
+        self.background.blend(self.foreground, brightness)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        PRESETS[0].1
+    }
+}
+
+/// Per-pixel brightness that decays over a number of frames once a pixel turns off
+pub struct Phosphor {
+    brightness: Vec<f32>,
+    decay: f32,
+}
+
+impl Phosphor {
+    /// Creates a phosphor layer, `frames` being how long an unlit pixel takes to fade
+    pub fn new(pixels: usize, frames: u32) -> Self {
+        Phosphor {
+            brightness: vec![0.0; pixels],
+            decay: if frames == 0 {
+                1.0
+            } else {
+                1.0 / frames as f32
+            },
+        }
+    }
+
+    /// Advances one frame against the current `gfx`, returning each pixel's brightness
+    ///
+    /// A display of a different size, after switching platforms, starts dark.
+    pub fn update(&mut self, gfx: &[u8]) -> &[f32] {
+        if self.brightness.len() != gfx.len() {
+            self.brightness = vec![0.0; gfx.len()];
+        }
+        for (brightness, &pixel) in self.brightness.iter_mut().zip(gfx.iter()) {
+            *brightness = if pixel == 1 {
+                1.0
+            } else {
+                (*brightness - self.decay).max(0.0)
+            };
+        }
+        &self.brightness
+    }
+
+    /// Whether any unlit pixel is still fading out
+    pub fn is_fading(&self, gfx: &[u8]) -> bool {
+        self.brightness
+            .iter()
+            .zip(gfx.iter())
+            .any(|(&brightness, &pixel)| pixel == 0 && brightness > 0.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_hex() {
+        assert_eq!(Ok(Rgb(0xFF, 0xB0, 0x00)), Rgb::from_hex("#FFB000"));
+        assert_eq!(Ok(Rgb(0x12, 0x34, 0x56)), Rgb::from_hex("123456"));
+        assert!(Rgb::from_hex("#12345").is_err());
+        assert!(Rgb::from_hex("#12345G").is_err());
+        assert!(Rgb::from_hex("aééx").is_err(), "6 bytes, but not 6 digits");
+        assert!(Rgb::from_hex("+1+2+3").is_err());
+    }
+
+    #[test]
+    fn test_preset() {
+        assert_eq!(Some(Palette::default()), Palette::preset("classic"));
+        assert_eq!(None, Palette::preset("sepia"));
+    }
+
+    #[test]
+    fn test_shade() {
+        let palette = Palette::default();
+        assert_eq!(Rgb(0, 0, 0), palette.shade(0.0));
+        assert_eq!(Rgb(255, 255, 255), palette.shade(1.0));
+        assert_eq!(Rgb(128, 128, 128), palette.shade(0.5));
+    }
+
+    #[test]
+    fn test_phosphor_fades_unlit_pixels() {
+        let mut phosphor = Phosphor::new(2, 4);
+        phosphor.update(&[1, 1]);
+        assert_eq!(&[1.0, 0.75], phosphor.update(&[1, 0]));
+        assert!(phosphor.is_fading(&[1, 0]));
+        for _ in 0..3 {
+            phosphor.update(&[1, 0]);
+        }
+        assert_eq!(&[1.0, 0.0], phosphor.update(&[1, 0]));
+        assert!(!phosphor.is_fading(&[1, 0]));
+    }
+
+    #[test]
+    fn test_phosphor_disabled() {
+        let mut phosphor = Phosphor::new(1, 0);
+        phosphor.update(&[1]);
+        assert_eq!(&[0.0], phosphor.update(&[0]));
+    }
+}
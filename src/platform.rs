@@ -0,0 +1,84 @@
+/// The machine a ROM was written for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Platform {
+    /// The CHIP-8 core
+    #[default]
+    Chip8,
+    /// CHIP-8 with the two-page display patch: 64x64, the patch in the ROM's
+    /// first 0xC0 bytes and the program proper starting after it
+    Hires,
+    /// The ETI-660's CHIP-8: 64x48, with programs loaded at 0x600
+    Eti660,
+    /// CHIP-8X for a VIP with the VP-590 color board and a second keypad
+    Chip8x,
+    /// A whole COSMAC VIP running a dump of its CHIP-8 interpreter
+    Vip,
+}
+
+impl Platform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "chip8" => Some(Platform::Chip8),
+            "hires" => Some(Platform::Hires),
+            "eti660" => Some(Platform::Eti660),
+            "chip8x" => Some(Platform::Chip8x),
+            "vip" => Some(Platform::Vip),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "chip8",
+            Platform::Hires => "hires",
+            Platform::Eti660 => "eti660",
+            Platform::Chip8x => "chip8x",
+            Platform::Vip => "vip",
+        }
+    }
+
+    /// Display width and height, in CHIP-8 pixels
+    pub fn display(&self) -> (usize, usize) {
+        match self {
+            Platform::Hires => (64, 64),
+            Platform::Eti660 => (64, 48),
+            _ => (64, 32),
+        }
+    }
+
+    /// Where the ROM goes in memory
+    pub fn load_address(&self) -> u16 {
+        match self {
+            Platform::Eti660 => 0x600,
+            Platform::Chip8x => 0x300,
+            _ => 0x200,
+        }
+    }
+
+    /// Where execution starts, past any patch the ROM carries
+    pub fn start(&self) -> u16 {
+        match self {
+            Platform::Hires => 0x2C0,
+            platform => platform.load_address(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        for platform in [
+            Platform::Chip8,
+            Platform::Hires,
+            Platform::Eti660,
+            Platform::Chip8x,
+            Platform::Vip,
+        ] {
+            assert_eq!(Some(platform), Platform::from_name(platform.name()));
+        }
+        assert_eq!(None, Platform::from_name("schip"));
+    }
+}
@@ -0,0 +1,59 @@
+/// Behaviors that differ between CHIP-8 interpreters, and what to do when a
+/// program goes beyond what the core supports
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// `Fx0A` finishes as soon as a key goes down, rather than waiting for it to
+    /// be released as the COSMAC VIP interpreter does
+    pub key_wait_on_press: bool,
+    pub invalid_opcode: InvalidOpcode,
+    pub machine_code: MachineCode,
+}
+
+/// What to do with a word that does not decode to an instruction
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InvalidOpcode {
+    /// Stop the machine with a crash report
+    #[default]
+    Halt,
+    /// Skip it
+    Nop,
+    /// Stop in the debugger at the word; resuming skips it
+    Trap,
+    /// Print a warning to stderr and skip it
+    Log,
+}
+
+impl InvalidOpcode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "halt" => Some(InvalidOpcode::Halt),
+            "nop" => Some(InvalidOpcode::Nop),
+            "trap" => Some(InvalidOpcode::Trap),
+            "log" => Some(InvalidOpcode::Log),
+            _ => None,
+        }
+    }
+}
+
+/// What `0nnn`, a call to machine code on the original hardware, does
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MachineCode {
+    /// Skip it, as most later interpreters do
+    #[default]
+    Ignore,
+    /// Stop the machine with a crash report
+    Error,
+    /// Run the CDP1802 code at `nnn` as the COSMAC VIP interpreter would
+    Vip,
+}
+
+impl MachineCode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ignore" => Some(MachineCode::Ignore),
+            "error" => Some(MachineCode::Error),
+            "vip" => Some(MachineCode::Vip),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::capture;
+use crate::controls::FRAME_RATE;
+use crate::image::Image;
+use crate::palette::Rgb;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Audio sample rate; a whole number of samples per 60 Hz frame
+const SAMPLE_RATE: u32 = 44100;
+
+/// Buzzer pitch, as the SDL frontend plays it
+const TONE: u64 = 440;
+
+/// Buzzer loudness, a quarter of full scale like the SDL frontend
+const AMPLITUDE: i16 = i16::MAX / 4;
+
+/// Where the video goes
+enum Video {
+    /// One YUV4MPEG2 stream
+    Y4m(BufWriter<File>),
+    /// A numbered PNG per frame, `game-000000.png` and on for `game.png`
+    Png { stem: String, count: u64 },
+}
+
+/// Dumps every emulated frame's display and buzzer, for encoding offline
+///
+/// Both are driven by emulated frames rather than the host clock, so the
+/// video runs at exactly 60 fps, the audio stays in step with it however fast
+/// the host ran, and a replayed run produces the same files every time.
+pub struct Recorder {
+    video: Video,
+    audio: Wav<BufWriter<File>>,
+    colors: Vec<Rgb>,
+    width: usize,
+    scale: u32,
+}
+
+impl Recorder {
+    /// Records video of a `width` by `height` display in `colors` to `path`,
+    /// a `.y4m` stream or the first of a `.png` sequence, and audio to the
+    /// same name with a `.wav` extension
+    pub fn create(
+        path: &str,
+        (width, height): (usize, usize),
+        colors: Vec<Rgb>,
+        scale: u32,
+    ) -> Result<Self, String> {
+        let error = |path: &str, e: io::Error| format!("Error writing {}: {:?}", path, e);
+        let stem = Path::new(path)
+            .with_extension("")
+            .to_string_lossy()
+            .into_owned();
+        let extension = Path::new(path).extension().and_then(|e| e.to_str());
+        let video = match extension {
+            Some("y4m") => {
+                let mut out = BufWriter::new(File::create(path).map_err(|e| error(path, e))?);
+                out.write_all(y4m_header(width as u32 * scale, height as u32 * scale).as_bytes())
+                    .map_err(|e| error(path, e))?;
+                Video::Y4m(out)
+            }
+            Some("png") => Video::Png {
+                stem: stem.clone(),
+                count: 0,
+            },
+            _ => return Err(format!("Expected a .y4m or .png path: {:?}", path)),
+        };
+
+        let wav = format!("{}.wav", stem);
+        let file = File::create(&wav).map_err(|e| error(&wav, e))?;
+        let audio = Wav::new(BufWriter::new(file)).map_err(|e| error(&wav, e))?;
+        Ok(Recorder {
+            video,
+            audio,
+            colors,
+            width,
+            scale,
+        })
+    }
+
+    /// Adds one frame of the display, as `capture::pixels`, and whether the
+    /// buzzer sounded during it
+    pub fn frame(&mut self, pixels: &[u8], buzzer: bool) -> Result<(), String> {
+        let image = capture::screenshot(pixels, self.width, &self.colors, self.scale);
+        match &mut self.video {
+            Video::Y4m(out) => out
+                .write_all(&y4m_frame(&image))
+                .map_err(|e| format!("Error writing video: {:?}", e))?,
+            Video::Png { stem, count } => {
+                let path = format!("{}-{:06}.png", stem, count);
+                image.save_png(&path)?;
+                *count += 1;
+            }
+        }
+        self.audio
+            .frame(buzzer)
+            .map_err(|e| format!("Error writing audio: {:?}", e))
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        if let Video::Y4m(mut out) = self.video {
+            out.flush()
+                .map_err(|e| format!("Error writing video: {:?}", e))?;
+        }
+        self.audio
+            .finish()
+            .map(|_| ())
+            .map_err(|e| format!("Error writing audio: {:?}", e))
+    }
+}
+
+/// The stream header: progressive 60 fps, square pixels, full-resolution color
+fn y4m_header(width: u32, height: u32) -> String {
+    format!(
+        "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444\n",
+        width, height, FRAME_RATE
+    )
+}
+
+/// A captured frame as Y, U and V planes
+fn y4m_frame(image: &Image) -> Vec<u8> {
+    let pixels: Vec<(u8, u8, u8)> = image.pixels.iter().map(|&pixel| yuv(pixel)).collect();
+    let mut frame = b"FRAME\n".to_vec();
+    for plane in 0..3 {
+        frame.extend(pixels.iter().map(|&(y, u, v)| [y, u, v][plane]));
+    }
+    frame
+}
+
+/// Converts to studio-range BT.601 YCbCr, which Y4M readers assume
+fn yuv(Rgb(r, g, b): Rgb) -> (u8, u8, u8) {
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let y = 16 + ((66 * r + 129 * g + 25 * b + 128) >> 8);
+    let u = 128 + ((-38 * r - 74 * g + 112 * b + 128) >> 8);
+    let v = 128 + ((112 * r - 94 * g - 18 * b + 128) >> 8);
+    (y as u8, u as u8, v as u8)
+}
+
+/// A mono 16-bit WAV file of the buzzer's square wave
+struct Wav<W: Write + Seek> {
+    out: W,
+    /// Samples written so far, which also keeps the wave's phase across frames
+    samples: u64,
+}
+
+impl<W: Write + Seek> Wav<W> {
+    fn new(mut out: W) -> io::Result<Self> {
+        // the sizes are filled in by `finish`
+        out.write_all(&wav_header(0))?;
+        Ok(Wav { out, samples: 0 })
+    }
+
+    /// Adds a frame's worth of tone, or of silence
+    fn frame(&mut self, on: bool) -> io::Result<()> {
+        let count = (SAMPLE_RATE / FRAME_RATE) as u64;
+        let mut bytes = Vec::with_capacity(count as usize * 2);
+        for n in self.samples..self.samples + count {
+            // the wave is high for the first half of each period
+            let high = (n * TONE * 2 / SAMPLE_RATE as u64).is_multiple_of(2);
+            let sample = match (on, high) {
+                (false, _) => 0,
+                (true, true) => AMPLITUDE,
+                (true, false) => -AMPLITUDE,
+            };
+            bytes.extend(sample.to_le_bytes());
+        }
+        self.samples += count;
+        self.out.write_all(&bytes)
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&wav_header(self.samples as u32 * 2))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// RIFF header for `data_size` bytes of 16-bit mono PCM
+fn wav_header(data_size: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(44);
+    header.extend(b"RIFF");
+    header.extend((36 + data_size).to_le_bytes());
+    header.extend(b"WAVEfmt ");
+    // PCM, one channel
+    header.extend(16u32.to_le_bytes());
+    header.extend(1u16.to_le_bytes());
+    header.extend(1u16.to_le_bytes());
+    header.extend(SAMPLE_RATE.to_le_bytes());
+    header.extend((SAMPLE_RATE * 2).to_le_bytes());
+    // two bytes per sample frame, 16 bits per sample
+    header.extend(2u16.to_le_bytes());
+    header.extend(16u16.to_le_bytes());
+    header.extend(b"data");
+    header.extend(data_size.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_wav() {
+        let mut wav = Wav::new(Cursor::new(Vec::new())).unwrap();
+        wav.frame(false).unwrap();
+        wav.frame(true).unwrap();
+        let bytes = wav.finish().unwrap().into_inner();
+        let samples = 2 * SAMPLE_RATE as usize / 60;
+        assert_eq!(44 + samples * 2, bytes.len());
+        assert_eq!(wav_header(samples as u32 * 2), bytes[..44]);
+        assert_eq!(
+            (36 + samples as u32 * 2).to_le_bytes(),
+            bytes[4..8],
+            "should patch the sizes in"
+        );
+
+        let sample = |n: usize| i16::from_le_bytes([bytes[44 + n * 2], bytes[45 + n * 2]]);
+        assert_eq!(0, sample(0));
+        // 735 samples in, a 440 Hz wave has done 7.33 periods, so it is high
+        assert_eq!(AMPLITUDE, sample(735));
+        assert_eq!(-AMPLITUDE, sample(735 + 40));
+    }
+
+    #[test]
+    fn test_y4m() {
+        assert_eq!(
+            "YUV4MPEG2 W128 H64 F60:1 Ip A1:1 C444\n",
+            y4m_header(128, 64)
+        );
+        assert_eq!((16, 128, 128), yuv(Rgb(0, 0, 0)));
+        assert_eq!((235, 128, 128), yuv(Rgb(255, 255, 255)));
+
+        let colors = [Rgb(0, 0, 0), Rgb(255, 255, 255), Rgb(255, 0, 0)];
+        let mut pixels = [0; 64 * 32];
+        pixels[1] = 1;
+        pixels[2] = 2;
+        let frame = y4m_frame(&capture::screenshot(&pixels, 64, &colors, 2));
+        let planes = &frame[6..];
+        assert_eq!(3 * 128 * 64, planes.len());
+        assert_eq!([16, 16, 235, 235, 82], planes[..5]);
+        assert_eq!(128, planes[128 * 64 + 2], "should have no color");
+        assert_eq!(yuv(Rgb(255, 0, 0)).2, planes[2 * 128 * 64 + 4]);
+    }
+}
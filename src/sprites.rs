@@ -0,0 +1,205 @@
+use crate::analyze::Traversal;
+use crate::image::Image;
+use crate::observer::Observer;
+use crate::osd::GLYPH_HEIGHT;
+use crate::palette::Palette;
+use crate::platform::Platform;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Size of a sprite pixel in sheets and bitmaps
+pub const SHEET_SCALE: u32 = 4;
+
+/// Sprites per row of a sheet
+const COLUMNS: usize = 8;
+
+/// Bytes per column when viewing memory as a bitmap
+const BITMAP_ROWS: u16 = 32;
+
+/// Sprites by address, with the most rows drawn from each
+pub type Sprites = BTreeMap<u16, u8>;
+
+/// Sprites a ROM for `platform` draws through an `I` set by `Annn`, found by
+/// traversal, with the height of the `Dxyn` that draws them
+pub fn scan(rom: &[u8], platform: Platform) -> Sprites {
+    Traversal::run(rom, platform).sprites
+}
+
+/// Observer recording the sprites the game actually draws, and their heights
+///
+/// Clones share the same record, so one can be installed on the core and
+/// another kept to read it.
+#[derive(Clone, Default)]
+pub struct SpriteTracker(Rc<RefCell<Sprites>>);
+
+impl SpriteTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sprites(&self) -> Sprites {
+        self.0.borrow().clone()
+    }
+}
+
+impl Observer for SpriteTracker {
+    fn draw(&mut self, _x: u8, _y: u8, sprite: u16, height: u8, _collision: bool) {
+        if height > 0 {
+            let mut sprites = self.0.borrow_mut();
+            let rows = sprites.entry(sprite).or_insert(0);
+            *rows = (*rows).max(height);
+        }
+    }
+}
+
+/// Sprites found by `scan`, with heights the game was seen drawing them at
+/// taking over and sprites only found at runtime added
+pub fn merge(scanned: Sprites, drawn: &Sprites) -> Sprites {
+    let mut sprites = scanned;
+    sprites.extend(drawn);
+    sprites
+}
+
+/// The sprites in a grid, each under its address in hex
+pub fn sheet(memory: &[u8], sprites: &Sprites, palette: Palette) -> Image {
+    let cells: Vec<(u16, u16)> = sprites
+        .iter()
+        .map(|(&address, &height)| (address, height as u16))
+        .collect();
+    grid(memory, &cells, COLUMNS, palette)
+}
+
+/// Memory from `start` to `end`, inclusive, one byte per 8-pixel row, in
+/// columns of 32 bytes each under its starting address
+pub fn bitmap(memory: &[u8], start: u16, end: u16, palette: Palette) -> Image {
+    let cells: Vec<(u16, u16)> = (start..=end)
+        .step_by(BITMAP_ROWS as usize)
+        .map(|address| (address, BITMAP_ROWS.min(end - address + 1)))
+        .collect();
+    grid(memory, &cells, cells.len(), palette)
+}
+
+/// Draws (address, rows) cells of memory left to right, `columns` to a row
+///
+/// Unlit pixels inside a cell are drawn a little lighter than the background
+/// so the sprite's extent shows.
+fn grid(memory: &[u8], cells: &[(u16, u16)], columns: usize, palette: Palette) -> Image {
+    let scale = SHEET_SCALE;
+    let text = (scale / 2).max(1);
+    let label = GLYPH_HEIGHT as u32 * text;
+    let tallest = cells.iter().map(|&(_, rows)| rows).max().unwrap_or(0) as u32;
+    let (cell_width, cell_height) = (9 * scale, label + tallest * scale + scale);
+    let columns = columns.min(cells.len()).max(1) as u32;
+    let rows = (cells.len() as u32).div_ceil(columns);
+    let mut image = Image::new(
+        scale + columns * cell_width,
+        scale + rows * cell_height,
+        palette.background,
+    );
+    let unlit = palette.background.blend(palette.foreground, 0.15);
+
+    for (i, &(address, height)) in cells.iter().enumerate() {
+        let left = scale + (i as u32 % columns) * cell_width;
+        let top = scale + (i as u32 / columns) * cell_height;
+        let name = format!("{:03X}", address);
+        image.text(left, top, &name, palette.foreground, text);
+        for row in 0..height {
+            let byte = memory[(address + row) as usize % memory.len()];
+            for bit in 0..8 {
+                let lit = byte & (0x80 >> bit) != 0;
+                image.fill(
+                    left + bit * scale,
+                    top + label + row as u32 * scale,
+                    scale,
+                    scale,
+                    if lit { palette.foreground } else { unlit },
+                );
+            }
+        }
+    }
+    image
+}
+
+/// Where to export a ROM's sprite sheet when no path is configured: next to
+/// it, as `game.sprites.png` for `game.ch8`
+pub fn default_path(rom: &str) -> String {
+    Path::new(rom)
+        .with_extension("sprites.png")
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::palette::Rgb;
+
+    const PALETTE: Palette = Palette {
+        background: Rgb(0, 0, 0),
+        foreground: Rgb(255, 255, 255),
+    };
+
+    #[test]
+    fn test_scan_and_track() {
+        // 200: call 20A; 202: draw 3 rows; 204: I = 20E; 206: draw 2 rows; 208: loop
+        // 20A: I = 210; 20C: ret; 20E: sprite; 210: sprite
+        let program = [
+            0x22, 0x0A, 0xD0, 0x13, 0xA2, 0x0E, 0xD0, 0x12, 0x12, 0x08, 0xA2, 0x10, 0x00, 0xEE,
+            0x80, 0xC0, 0xE0, 0xA0, 0xE0,
+        ];
+        let scanned = scan(&program, Platform::Chip8);
+        assert_eq!(
+            vec![(0x20E, 2)],
+            scanned.clone().into_iter().collect::<Vec<_>>()
+        );
+
+        let mut chip8 = Chip8::initialize();
+        for (i, &byte) in program.iter().enumerate() {
+            chip8.write_memory(0x200 + i, byte);
+        }
+        let tracker = SpriteTracker::new();
+        chip8.add_observer(Box::new(tracker.clone()));
+        for _ in 0..4 {
+            chip8.emulate_cycle();
+        }
+        let sprites = merge(scanned, &tracker.sprites());
+        assert_eq!(
+            vec![(0x20E, 2), (0x210, 3)],
+            sprites.into_iter().collect::<Vec<_>>(),
+            "should add the sprite only seen at runtime, where I came from a subroutine"
+        );
+    }
+
+    #[test]
+    fn test_sheet() {
+        let mut memory = vec![0; 4096];
+        memory[0x300] = 0x81;
+        let sprites = Sprites::from([(0x300, 2), (0x310, 15)]);
+        let image = sheet(&memory, &sprites, PALETTE);
+        let (cell_width, label) = (9 * SHEET_SCALE, GLYPH_HEIGHT as u32 * 2);
+        assert_eq!(SHEET_SCALE + 2 * cell_width, image.width);
+        assert_eq!(SHEET_SCALE + label + 16 * SHEET_SCALE, image.height);
+
+        let at = |x: u32, y: u32| image.pixels[(y * image.width + x) as usize];
+        let top = SHEET_SCALE + label;
+        assert_eq!(PALETTE.foreground, at(SHEET_SCALE, top));
+        assert_ne!(PALETTE.foreground, at(2 * SHEET_SCALE, top));
+        assert_eq!(PALETTE.foreground, at(8 * SHEET_SCALE, top));
+        assert_eq!(
+            PALETTE.background,
+            at(SHEET_SCALE, top + 2 * SHEET_SCALE),
+            "should stop after the sprite's rows"
+        );
+    }
+
+    #[test]
+    fn test_bitmap() {
+        let memory = vec![0xFF; 4096];
+        let image = bitmap(&memory, 0x200, 0x250, PALETTE);
+        assert_eq!(SHEET_SCALE + 3 * 9 * SHEET_SCALE, image.width);
+        assert_eq!("roms/game.sprites.png", default_path("roms/game.ch8"));
+    }
+}
@@ -0,0 +1,207 @@
+use crate::expr::parse_number;
+use std::fs;
+use std::path::Path;
+
+/// Where an address came from in the assembler's source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLine {
+    pub address: u16,
+    pub file: String,
+    pub line: u32,
+}
+
+/// A name the assembler gave an address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Label {
+    pub address: u16,
+    pub name: String,
+}
+
+/// Debug information from an assembler: labels and source lines by address
+///
+/// The file has one entry per line, an address followed by a label, a
+/// `file:line` or both, with blank lines and `#` comments skipped:
+///
+/// ```text
+/// 0x200 main game.8o:12
+/// 0x202 game.8o:13
+/// 0x2A4 draw_player
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SymbolMap {
+    pub labels: Vec<Label>,
+    pub lines: Vec<SourceLine>,
+}
+
+impl SymbolMap {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading symbols {}: {:?}", path, e))?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut map = SymbolMap::default();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let address = parse_address(fields.next().unwrap_or(""))?;
+            let mut empty = true;
+            for field in fields {
+                if field.contains(':') {
+                    map.lines.push(parse_source_line(address, field)?);
+                } else if is_label(field) {
+                    map.labels.push(Label {
+                        address,
+                        name: field.to_string(),
+                    });
+                } else {
+                    return Err(format!("Invalid label: {:?}", field));
+                }
+                empty = false;
+            }
+            if empty {
+                return Err(format!("Expected a label or `file:line`: {:?}", line));
+            }
+        }
+        map.labels.sort_by_key(|label| label.address);
+        map.lines.sort_by_key(|line| line.address);
+        Ok(map)
+    }
+
+    /// The label naming exactly this address
+    pub fn label_at(&self, address: u16) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|label| label.address == address)
+            .map(|label| label.name.as_str())
+    }
+
+    /// The address of a label
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.labels
+            .iter()
+            .find(|label| label.name == name)
+            .map(|label| label.address)
+    }
+
+    /// Names an address by the closest label at or before it, e.g. `draw_player+4`,
+    /// or as plain hex when no label comes before it
+    pub fn describe(&self, address: u16) -> String {
+        match self
+            .labels
+            .iter()
+            .rev()
+            .find(|label| label.address <= address)
+        {
+            Some(label) if label.address == address => label.name.clone(),
+            Some(label) => format!("{}+{:X}", label.name, address - label.address),
+            None => format!("{:03X}", address),
+        }
+    }
+
+    /// An address given either as a label or as a number
+    pub fn resolve(&self, text: &str) -> Result<u16, String> {
+        let text = text.trim();
+        match self.address_of(text) {
+            Some(address) => Ok(address),
+            None if is_label(text) && !text.starts_with(|c: char| c.is_ascii_digit()) => {
+                Err(format!("Unknown label: {:?}", text))
+            }
+            None => parse_address(text),
+        }
+    }
+
+    /// The source line an address was assembled from
+    pub fn line_for(&self, address: u16) -> Option<&SourceLine> {
+        self.lines.iter().find(|line| line.address == address)
+    }
+
+    /// The first address assembled from a line, matching `path` against the
+    /// recorded file name by its trailing components
+    pub fn address_for(&self, path: &str, line: u32) -> Option<u16> {
+        self.lines
+            .iter()
+            .find(|source| source.line == line && same_file(path, &source.file))
+            .map(|source| source.address)
+    }
+}
+
+fn is_label(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn parse_source_line(address: u16, location: &str) -> Result<SourceLine, String> {
+    let split = location
+        .rfind(':')
+        .ok_or_else(|| format!("Expected `file:line`: {:?}", location))?;
+    let line = location[split + 1..]
+        .parse()
+        .map_err(|_| format!("Invalid line number: {:?}", location))?;
+    Ok(SourceLine {
+        address,
+        file: location[..split].to_string(),
+        line,
+    })
+}
+
+/// Whether a path from an editor, usually absolute, names the same file as a
+/// path from the symbol map, usually relative
+fn same_file(path: &str, file: &str) -> bool {
+    Path::new(path).ends_with(file) || Path::new(file).ends_with(path)
+}
+
+/// Parses a numeric address within the 4K of memory
+pub fn parse_address(text: &str) -> Result<u16, String> {
+    let address = parse_number(text.trim())?;
+    if (0..0x1000).contains(&address) {
+        Ok(address as u16)
+    } else {
+        Err(format!("Address out of range: {:?}", text))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_lines() {
+        let map =
+            SymbolMap::parse("# game\n0x202 src/game.8o:13\n\n0x200 src/game.8o:12\n").unwrap();
+        assert_eq!(0x200, map.lines[0].address, "should sort by address");
+        assert_eq!(Some(13), map.line_for(0x202).map(|line| line.line));
+        assert_eq!(Some(0x202), map.address_for("/home/me/rom/src/game.8o", 13));
+        assert_eq!(None, map.address_for("/home/me/rom/src/other.8o", 13));
+        assert_eq!(None, map.address_for("/home/me/rom/src/game.8o", 14));
+    }
+
+    #[test]
+    fn test_labels() {
+        let map = SymbolMap::parse("0x200 main game.8o:1\n0x2A4 draw_player\n").unwrap();
+        assert_eq!(Some("main"), map.label_at(0x200));
+        assert_eq!(Some(1), map.line_for(0x200).map(|line| line.line));
+        assert_eq!(Some(0x2A4), map.address_of("draw_player"));
+        assert_eq!("draw_player", map.describe(0x2A4));
+        assert_eq!("draw_player+A", map.describe(0x2AE));
+        assert_eq!("main+2", map.describe(0x202));
+        assert_eq!("050", map.describe(0x50));
+        assert_eq!(Ok(0x2A4), map.resolve("draw_player"));
+        assert_eq!(Ok(0x300), map.resolve("0x300"));
+        assert!(map.resolve("draw_enemy").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(SymbolMap::parse("0x200").is_err());
+        assert!(SymbolMap::parse("0x200 game-8o").is_err());
+        assert!(SymbolMap::parse("0x200 game.8o:x").is_err());
+        assert!(SymbolMap::parse("zz game.8o:1").is_err());
+    }
+}
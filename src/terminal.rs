@@ -0,0 +1,346 @@
+use crate::chip8::Chip8;
+use crate::frontend::{Frontend, Hotkey, InputEvent};
+use crate::keypad::{HostKey, Keymap};
+use crate::palette::Palette;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest wait between autorepeats of a held key, with room for a frame of
+/// polling; the usual rates repeat every 30 to 100ms
+const REPEAT_GAP: Duration = Duration::from_millis(150);
+
+/// Text frontend that draws to the terminal and reads the keypad from stdin
+///
+/// Terminals only report key presses, and repeat them while a key is held, so
+/// releases are worked out by `KeyHolds`.
+pub struct Terminal {
+    original: libc::termios,
+    palette: Option<Palette>,
+    keymap: Keymap,
+    holds: KeyHolds,
+    start: Instant,
+}
+
+/// Which keys are down, from the presses and autorepeats a terminal sends
+///
+/// Until a key repeats there is no telling a tap from the start of a hold, so
+/// a key seen once stays down for `timeout`, longer than the autorepeat
+/// delay. Once it repeats, the first gap longer than `REPEAT_GAP` releases it.
+struct KeyHolds {
+    timeout: Duration,
+    keys: [Option<Hold>; 32],
+}
+
+#[derive(Clone, Copy)]
+struct Hold {
+    last_seen: Instant,
+    repeating: bool,
+}
+
+impl KeyHolds {
+    fn new(timeout: Duration) -> Self {
+        KeyHolds {
+            timeout,
+            keys: [None; 32],
+        }
+    }
+
+    /// Notes a press or repeat of `key`, returning the key down if it was up
+    fn press(&mut self, key: usize, now: Instant) -> Option<InputEvent> {
+        let was_down = self.keys[key].is_some();
+        self.keys[key] = Some(Hold {
+            last_seen: now,
+            repeating: was_down,
+        });
+        match was_down {
+            true => None,
+            false => Some(InputEvent::KeyDown(key)),
+        }
+    }
+
+    /// Key ups for the keys that have stopped repeating or timed out
+    fn release(&mut self, now: Instant) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        for (key, hold) in self.keys.iter_mut().enumerate() {
+            if let Some(Hold {
+                last_seen,
+                repeating,
+            }) = *hold
+            {
+                let limit = if repeating { REPEAT_GAP } else { self.timeout };
+                if now.duration_since(last_seen) > limit {
+                    events.push(InputEvent::KeyUp(key));
+                    *hold = None;
+                }
+            }
+        }
+        events
+    }
+}
+
+impl Terminal {
+    /// Switches the terminal to raw mode and the alternate screen
+    pub fn initialize(
+        palette: Option<Palette>,
+        keymap: Keymap,
+        key_timeout: Duration,
+    ) -> io::Result<Self> {
+        let original = unsafe {
+            let mut termios: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = termios;
+            libc::cfmakeraw(&mut raw);
+            // reads return immediately, with whatever input is waiting
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            termios
+        };
+
+        // alternate screen, hide cursor, clear
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        io::stdout().flush()?;
+
+        Ok(Self {
+            original,
+            palette,
+            keymap,
+            holds: KeyHolds::new(key_timeout),
+            start: Instant::now(),
+        })
+    }
+
+    /// Reads waiting input as keypad and hotkey events
+    fn read_input(&mut self) -> Vec<InputEvent> {
+        let mut buffer = [0u8; 64];
+        let count = io::stdin().lock().read(&mut buffer).unwrap_or(0);
+        let now = Instant::now();
+        let mut events = Vec::new();
+
+        let mut bytes = buffer[..count].iter().peekable();
+        while let Some(&byte) = bytes.next() {
+            match byte {
+                // Ctrl-C, or Escape on its own rather than starting a sequence
+                0x03 => events.push(InputEvent::Quit),
+                0x1b if bytes.peek().is_none() => events.push(InputEvent::Quit),
+                0x1b => {
+                    let mut sequence = Vec::new();
+                    for &next in bytes.by_ref() {
+                        sequence.push(next);
+                        if sequence.len() > 1 && (next.is_ascii_alphabetic() || next == b'~') {
+                            break;
+                        }
+                    }
+                    if let Some(key) = escape_arrow(&sequence).and_then(|a| self.keymap.key(a)) {
+                        events.extend(self.holds.press(key, now));
+                    }
+                    events.extend(escape_hotkey(&sequence).map(InputEvent::Hotkey));
+                }
+                _ => {
+                    if let Some(hotkey) = char_hotkey(byte as char) {
+                        events.push(InputEvent::Hotkey(hotkey));
+                    } else if let Some(key) = self.keymap.key(HostKey::Char(byte as char)) {
+                        events.extend(self.holds.press(key, now));
+                    }
+                }
+            }
+        }
+
+        events.extend(self.holds.release(now));
+        events
+    }
+}
+
+impl Frontend for Terminal {
+    fn present(&mut self, chip8: &Chip8, overlay: &[String]) {
+        let mut out = io::stdout();
+        let mut text = render(&chip8.gfx, chip8.width(), self.palette.as_ref());
+        // the OSD goes under the display, clearing what was left from last time
+        for line in overlay {
+            text.push_str(&format!("\x1b[2K{}\r\n", line));
+        }
+        text.push_str("\x1b[J");
+        let _ = out.write_all(text.as_bytes());
+        let _ = out.flush();
+    }
+
+    fn set_buzzer(&mut self, on: bool) {
+        if on {
+            // terminal bell; there is no way to hold a tone
+            print!("\x07");
+            let _ = io::stdout().flush();
+        }
+    }
+
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        self.read_input()
+    }
+
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// Hotkeys on keys outside the keypad; there is no fast-forward since terminals
+/// don't report keys being held
+fn char_hotkey(c: char) -> Option<Hotkey> {
+    match c {
+        'p' => Some(Hotkey::TogglePause),
+        'n' => Some(Hotkey::AdvanceFrame),
+        'm' => Some(Hotkey::ToggleSlowMotion),
+        '=' => Some(Hotkey::SpeedUp),
+        '-' => Some(Hotkey::SpeedDown),
+        _ => None,
+    }
+}
+
+/// Hotkeys on function keys, from the escape sequence after the `ESC`
+fn escape_hotkey(sequence: &[u8]) -> Option<Hotkey> {
+    match sequence {
+        b"OP" | b"[11~" => Some(Hotkey::ToggleOsd),
+        b"[15~" => Some(Hotkey::SoftReset),
+        b"[15;2~" => Some(Hotkey::HardReset),
+        _ => None,
+    }
+}
+
+/// Arrow keys, from the escape sequence after the `ESC`, in either cursor mode
+fn escape_arrow(sequence: &[u8]) -> Option<HostKey> {
+    match sequence {
+        b"[A" | b"OA" => Some(HostKey::Up),
+        b"[B" | b"OB" => Some(HostKey::Down),
+        b"[C" | b"OC" => Some(HostKey::Right),
+        b"[D" | b"OD" => Some(HostKey::Left),
+        _ => None,
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// Renders `gfx`, `width` pixels across, as half-block characters, two
+/// display rows per line of text
+///
+/// With a palette each cell gets truecolor escapes, the upper pixel as the
+/// foreground of `▀` and the lower pixel as its background. Without one the
+/// cell is picked from blank, `▀`, `▄` and `█` in the terminal's own colors.
+pub fn render(gfx: &[u8], width: usize, palette: Option<&Palette>) -> String {
+    let mut out = String::from("\x1b[H");
+    for row in (0..gfx.len() / width).step_by(2) {
+        let mut last_cell = None;
+        for column in 0..width {
+            let top = gfx[row * width + column];
+            let bottom = gfx.get((row + 1) * width + column).copied().unwrap_or(0);
+            match palette {
+                Some(palette) => {
+                    if last_cell != Some((top, bottom)) {
+                        let fg = palette.shade(top as f32);
+                        let bg = palette.shade(bottom as f32);
+                        out.push_str(&format!(
+                            "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                            fg.0, fg.1, fg.2, bg.0, bg.1, bg.2
+                        ));
+                        last_cell = Some((top, bottom));
+                    }
+                    out.push('▀');
+                }
+                None => out.push(match (top, bottom) {
+                    (0, 0) => ' ',
+                    (_, 0) => '▀',
+                    (0, _) => '▄',
+                    _ => '█',
+                }),
+            }
+        }
+        out.push_str("\x1b[0m\r\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(rendered: &str) -> Vec<&str> {
+        rendered
+            .trim_start_matches("\x1b[H")
+            .split("\x1b[0m\r\n")
+            .collect()
+    }
+
+    #[test]
+    fn test_render_half_blocks() {
+        let mut gfx = [0; 64 * 32];
+        gfx[0] = 1; // top only
+        gfx[64 + 1] = 1; // bottom only
+        gfx[2] = 1; // both
+        gfx[64 + 2] = 1;
+        gfx[31 * 64 + 63] = 1; // last row
+
+        let rendered = render(&gfx, 64, None);
+        let lines = lines(&rendered);
+        assert_eq!(17, lines.len(), "should draw 16 lines of text");
+        assert!(lines[0].starts_with("▀▄█ "));
+        assert!(lines[15].ends_with(" ▄"));
+    }
+
+    #[test]
+    fn test_key_holds() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut holds = KeyHolds::new(Duration::from_millis(500));
+
+        // a tap is held until it could no longer be the start of a hold
+        assert_eq!(Some(InputEvent::KeyDown(5)), holds.press(5, at(0)));
+        assert!(holds.release(at(400)).is_empty());
+        assert_eq!(vec![InputEvent::KeyUp(5)], holds.release(at(501)));
+
+        // once it repeats, the first gap releases it
+        holds.press(5, at(1000));
+        assert_eq!(None, holds.press(5, at(1400)));
+        assert_eq!(None, holds.press(5, at(1430)));
+        assert!(holds.release(at(1500)).is_empty());
+        assert_eq!(vec![InputEvent::KeyUp(5)], holds.release(at(1600)));
+        assert_eq!(Some(InputEvent::KeyDown(5)), holds.press(5, at(1700)));
+    }
+
+    #[test]
+    fn test_escape_hotkey() {
+        assert_eq!(Some(Hotkey::ToggleOsd), escape_hotkey(b"OP"));
+        assert_eq!(Some(Hotkey::HardReset), escape_hotkey(b"[15;2~"));
+        assert_eq!(None, escape_hotkey(b"[A"));
+        assert_eq!(Some(HostKey::Up), escape_arrow(b"[A"));
+        assert_eq!(Some(HostKey::Left), escape_arrow(b"OD"));
+        assert_eq!(None, escape_arrow(b"[15~"));
+    }
+
+    #[test]
+    fn test_render_truecolor() {
+        let mut gfx = [0; 64 * 32];
+        gfx[0] = 1;
+        gfx[1] = 1;
+
+        let rendered = render(&gfx, 64, Some(&Palette::default()));
+        let lines = lines(&rendered);
+        assert!(lines[0].starts_with(
+            "\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m▀▀\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m▀"
+        ));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::chip8::Chip8;
+use crate::decode::{Instruction, Op};
+
+/// How much runs each 60 Hz frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Timing {
+    /// A flat number of instructions per frame
+    #[default]
+    Ipf,
+    /// As many instructions as the COSMAC VIP interpreter got through in a frame
+    Vip,
+}
+
+impl Timing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ipf" => Some(Timing::Ipf),
+            "vip" => Some(Timing::Vip),
+            _ => None,
+        }
+    }
+}
+
+/// 1802 machine cycles per frame: a 1.7609 MHz clock, 8 clocks to a cycle
+pub const FRAME_CYCLES: u32 = 3668;
+
+/// Cycles the 1861 takes each frame for display DMA, 8 bytes on each of 128
+/// scanlines, and the interrupt routine that sets it up and ticks the timers
+const DISPLAY_CYCLES: u32 = 128 * 8 + 46;
+
+/// Cycles left each frame for the interpreter
+pub const BUDGET: u32 = FRAME_CYCLES - DISPLAY_CYCLES;
+
+/// The interpreter's fetch and dispatch, paid by every instruction
+const FETCH: u32 = 40;
+
+/// Extra cycles a skip takes when it skips
+const SKIP: u32 = 4;
+
+/// Machine cycles the VIP interpreter spent on an instruction, fetch included
+///
+/// `v` holds the registers from before it ran. The counts follow the shape of
+/// the interpreter's routines: `00E0` clears 256 display bytes in a loop,
+/// `Dxyn` shifts each sprite row bit by bit into place when `Vx` is not a
+/// multiple of 8, `Fx33` counts each digit out by repeated subtraction and
+/// `Fx55`/`Fx65` copy one register at a time.
+pub fn vip_cycles(ins: Instruction, v: &[u8; 16], skipped: bool) -> u32 {
+    let vx = v[ins.x as usize] as u32;
+    let skip = if skipped { SKIP } else { 0 };
+    let cycles = match ins.op {
+        Op::Sys | Op::Invalid => 0,
+        Op::Cls => 24 + 256 * 4,
+        Op::Ret => 10,
+        Op::Jp | Op::LdIAddr => 12,
+        Op::Call => 26,
+        Op::SeVxByte | Op::SneVxByte => 10 + skip,
+        Op::SeVxVy | Op::SneVxVy | Op::SkpVx | Op::SknpVx => 14 + skip,
+        Op::LdVxByte => 6,
+        Op::AddVxByte | Op::LdVxDt | Op::LdVxK | Op::LdDtVx | Op::LdStVx => 10,
+        Op::LdVxVy => 12,
+        Op::OrVxVy
+        | Op::AndVxVy
+        | Op::XorVxVy
+        | Op::AddVxVy
+        | Op::SubVxVy
+        | Op::ShrVxVy
+        | Op::SubnVxVy
+        | Op::ShlVxVy => 44,
+        // a target in the next page takes a carry
+        Op::JpV0Addr if (ins.nnn & 0xFF) + v[0] as u16 > 0xFF => 24,
+        Op::JpV0Addr => 22,
+        Op::RndVxByte => 36,
+        Op::Drw => 26 + ins.n as u32 * (46 + 8 * (vx % 8)),
+        Op::AddIVx | Op::LdFVx => 16,
+        Op::LdBVx => 80 + 16 * (vx / 100 + vx / 10 % 10 + vx % 10),
+        Op::LdIVx | Op::LdVxI => 14 + 14 * (ins.x as u32 + 1),
+    };
+    FETCH + cycles
+}
+
+/// The instruction at `pc`, for deciding whether it should wait for the next frame
+pub fn next_instruction(chip8: &Chip8) -> Option<Instruction> {
+    let pc = chip8.pc as usize;
+    let bytes = chip8.memory.get(pc..pc + 2)?;
+    Some(Instruction::decode(
+        (bytes[0] as u16) << 8 | bytes[1] as u16,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vip_cycles() {
+        let mut v = [0; 16];
+        let cycles = |word: u16, v: &[u8; 16], skipped: bool| {
+            vip_cycles(Instruction::decode(word), v, skipped)
+        };
+        assert_eq!(FETCH + 6, cycles(0x6012, &v, false));
+        assert_eq!(FETCH + 14, cycles(0x3012, &v, true));
+
+        let aligned = cycles(0xD125, &v, false);
+        v[1] = 3;
+        let shifted = cycles(0xD125, &v, false);
+        assert_eq!(5 * 3 * 8, shifted - aligned, "should shift each row 3 bits");
+        assert!(cycles(0xD12F, &v, false) > shifted);
+
+        assert!(cycles(0xF555, &v, false) > cycles(0xF055, &v, false));
+        v[0] = 199;
+        assert_eq!(FETCH + 80 + 16 * 19, cycles(0xF033, &v, false));
+        assert_eq!(FETCH + 24, cycles(0xB380, &v, false));
+    }
+}
@@ -0,0 +1,88 @@
+use crate::disasm::mnemonic;
+use crate::observer::Observer;
+use crate::symbols::SymbolMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Observer writing a line for every instruction the core fetches
+///
+/// Lines look like `main+4  204  A300  LD I, sprite`, naming the address by
+/// the closest label when there are symbols.
+pub struct Tracer {
+    symbols: SymbolMap,
+    out: Box<dyn Write>,
+}
+
+impl Tracer {
+    pub fn new(symbols: SymbolMap, out: Box<dyn Write>) -> Self {
+        Tracer { symbols, out }
+    }
+
+    /// Traces to a file, or to stderr for `-`
+    pub fn create(path: &str, symbols: SymbolMap) -> io::Result<Self> {
+        let out: Box<dyn Write> = if path == "-" {
+            Box::new(io::stderr())
+        } else {
+            Box::new(BufWriter::new(File::create(path)?))
+        };
+        Ok(Tracer::new(symbols, out))
+    }
+}
+
+impl Observer for Tracer {
+    fn instruction_fetched(&mut self, pc: u16, opcode: u16) {
+        let _ = writeln!(
+            self.out,
+            "{:<16}  {:03X}  {:04X}  {}",
+            self.symbols.describe(pc),
+            pc,
+            opcode,
+            mnemonic(opcode, &self.symbols)
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chip8::Chip8;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Writer the test can read back after handing it to the tracer
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_trace_with_labels() {
+        let mut chip8 = Chip8::initialize();
+        for (i, &byte) in [0x22, 0x04, 0x00, 0x00, 0xA2, 0x00].iter().enumerate() {
+            chip8.write_memory(0x200 + i, byte);
+        }
+        let symbols = SymbolMap::parse("0x200 main\n0x204 load").unwrap();
+        let out = Shared::default();
+        chip8.add_observer(Box::new(Tracer::new(symbols, Box::new(out.clone()))));
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+
+        let trace = String::from_utf8(out.0.borrow().clone()).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+        assert_eq!(
+            vec![
+                "main              200  2204  CALL load",
+                "load              204  A200  LD I, main",
+            ],
+            lines
+        );
+    }
+}
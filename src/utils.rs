@@ -84,6 +84,26 @@ where
     bits
 }
 
+/// Bytes as lowercase hex, two digits each
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Bytes from hex, two digits each
+pub fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    if text.len() % 2 == 1 {
+        return Err(format!("Odd length hex: {:?}", text));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            text.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| format!("Invalid hex: {:?}", text))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,437 @@
+use crate::cdp1802::{Bus, Cdp1802};
+use crate::chip8::{Chip8, Fault};
+
+/// Where the VIP interpreter keeps V0-VF
+pub const REGISTERS: u16 = 0xEF0;
+
+/// The VIP display buffer: 32 rows of 8 bytes, high bit leftmost
+pub const DISPLAY: u16 = 0xF00;
+
+/// Where the interpreter starts its 1802 stack, in `R2`
+pub const STACK_TOP: u16 = 0xECF;
+
+/// Instructions machine code gets to return before it counts as a runaway
+const CALL_LIMIT: u32 = 1_000_000;
+
+/// Machine cycles per scanline of the CDP1861
+const LINE_CYCLES: u32 = 14;
+
+/// Machine cycles per 60 Hz frame of 262 scanlines
+const FRAME_CYCLES: u32 = 262 * LINE_CYCLES;
+
+/// Scanlines the 1861 fetches by DMA, 8 bytes each, starting at `DISPLAY_START`
+const DISPLAY_LINES: u32 = 128;
+const DISPLAY_START: u32 = 80;
+
+/// The 1861 interrupts this many cycles before the first DMA
+const INTERRUPT_LEAD: u32 = 29;
+
+/// Scanlines EF1 is asserted for before the display starts and before it ends
+const EF1_LINES: u32 = 4;
+
+/// Size of the interpreter the VIP loads below the program at 0x200
+pub const INTERPRETER_SIZE: usize = 0x200;
+
+/// CHIP-8 memory as the 1802 sees it, with writes going through the core
+struct Memory<'a>(&'a mut Chip8);
+
+impl Bus for Memory<'_> {
+    fn read(&mut self, address: u16) -> u8 {
+        self.0.memory[address as usize & 0xFFF]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.0.write_memory(address as usize & 0xFFF, value);
+    }
+}
+
+/// Runs the machine code subroutine at `address` for `0nnn`, as the VIP interpreter would
+///
+/// The routine starts with `P` = 3 and returns with `SEP R4` (`D4`). As on the
+/// VIP it finds V0-VF at `REGISTERS`, the display at `DISPLAY`, `I` in `RA`,
+/// the next CHIP-8 `pc` in `R5`, pointers to Vx and Vy in `R6` and `R7`, and
+/// the delay and sound timers in `R8.1` and `R8.0`; whatever it leaves there is
+/// copied back. Like the VIP, this overwrites the top of memory. Code that
+/// never returns faults. Taller displays than the VIP's would run into the
+/// registers, so routines on those platforms don't see the display.
+pub fn call_machine_code(chip8: &mut Chip8, address: u16) {
+    for x in 0..16 {
+        chip8.write_memory(REGISTERS as usize + x, chip8.v[x]);
+    }
+    let shared = chip8.gfx.len() == 64 * 32;
+    if shared {
+        for (i, byte) in pack_display(&chip8.gfx).iter().enumerate() {
+            chip8.write_memory(DISPLAY as usize + i, *byte);
+        }
+    }
+
+    let mut cpu = Cdp1802::new();
+    cpu.p = 3;
+    cpu.x = 2;
+    cpu.r[2] = STACK_TOP;
+    cpu.r[3] = address;
+    cpu.r[5] = chip8.pc + 2;
+    cpu.r[6] = REGISTERS + (chip8.opcode >> 8 & 0xF);
+    cpu.r[7] = REGISTERS + (chip8.opcode >> 4 & 0xF);
+    cpu.r[8] = (chip8.delay_timer as u16) << 8 | chip8.sound_timer as u16;
+    cpu.r[0xA] = chip8.i;
+    cpu.r[0xB] = DISPLAY;
+    let mut returned = false;
+    for _ in 0..CALL_LIMIT {
+        cpu.step(&mut Memory(chip8));
+        // nothing interrupts the routine here, so `IDL` just carries on
+        cpu.idle = false;
+        if cpu.p == 4 {
+            returned = true;
+            break;
+        }
+    }
+    if !returned {
+        chip8.fault(Fault::MachineCode);
+        return;
+    }
+
+    for x in 0..16 {
+        let value = chip8.memory[REGISTERS as usize + x];
+        if value != chip8.v[x] {
+            chip8.set_v(x, value);
+        }
+    }
+    let display = &chip8.memory[DISPLAY as usize..DISPLAY as usize + 256];
+    let gfx = unpack_display(display);
+    if shared && gfx != chip8.gfx {
+        chip8.gfx = gfx;
+        chip8.should_draw = true;
+    }
+    if cpu.r[0xA] != chip8.i {
+        chip8.set_i(cpu.r[0xA]);
+    }
+    chip8.delay_timer = (cpu.r[8] >> 8) as u8;
+    chip8.sound_timer = cpu.r[8] as u8;
+    chip8.pc = cpu.r[5] & 0xFFF;
+}
+
+/// What the VIP's 1802 is wired to: 4K of RAM, mirrored through the address
+/// space, the CDP1861 video chip and the hex keypad
+struct Board {
+    memory: [u8; 4096],
+    keys: [bool; 16],
+    /// The key `OUT 2` selected, which EF3 reports on
+    key_latch: usize,
+    /// Turned on by `INP 1` and off by `OUT 1`
+    display_on: bool,
+    /// Scanline being drawn, for EF1
+    line: u32,
+}
+
+impl Bus for Board {
+    fn read(&mut self, address: u16) -> u8 {
+        self.memory[address as usize & 0xFFF]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory[address as usize & 0xFFF] = value;
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.display_on = true;
+        }
+        0
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.display_on = false,
+            2 => self.key_latch = value as usize & 0xF,
+            _ => {}
+        }
+    }
+
+    fn flag(&mut self, flag: u8) -> bool {
+        match flag {
+            1 => {
+                let end = DISPLAY_START + DISPLAY_LINES;
+                (DISPLAY_START - EF1_LINES..DISPLAY_START).contains(&self.line)
+                    || (end - EF1_LINES..end).contains(&self.line)
+            }
+            3 => self.keys[self.key_latch],
+            _ => false,
+        }
+    }
+}
+
+/// A whole COSMAC VIP, running the original CHIP-8 interpreter rather than the core
+///
+/// The interpreter is a dump of the 512 bytes the VIP loaded at 0x000. The
+/// machine runs a frame at a time, interleaving the 1802 with the 1861's
+/// interrupt and display DMA at instruction boundaries, as the 1802 does.
+/// The display comes from what DMA fetched, so it shows what the VIP would
+/// have, and `0nnn` machine code runs on the same CPU as everything else.
+pub struct Vip {
+    pub cpu: Cdp1802,
+    board: Board,
+    /// Bytes the 1861 fetched for each scanline of the last frame
+    lines: [[u8; 8]; DISPLAY_LINES as usize],
+    /// Cycles the last instruction of a frame ran into the next
+    overrun: u32,
+}
+
+impl Vip {
+    /// Starts the interpreter at 0x000 on a copy of `memory`, which holds the program
+    pub fn new(interpreter: &[u8], memory: &[u8]) -> Self {
+        let mut board = Board {
+            memory: [0; 4096],
+            keys: [false; 16],
+            key_latch: 0,
+            display_on: false,
+            line: 0,
+        };
+        board.memory.copy_from_slice(&memory[..4096]);
+        let size = interpreter.len().min(INTERPRETER_SIZE);
+        board.memory[..size].copy_from_slice(&interpreter[..size]);
+
+        let mut cpu = Cdp1802::new();
+        // the interpreter finds the top page of RAM in R1.1, where the VIP's
+        // operating system leaves it
+        cpu.r[1] = 0x0F00;
+        Vip {
+            cpu,
+            board,
+            lines: [[0; 8]; DISPLAY_LINES as usize],
+            overrun: 0,
+        }
+    }
+
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        // the VIP has the one keypad
+        if let Some(down) = self.board.keys.get_mut(key) {
+            *down = pressed;
+        }
+    }
+
+    /// Runs one 60 Hz frame of 1802 machine cycles
+    pub fn run_frame(&mut self) {
+        let interrupt_at = DISPLAY_START * LINE_CYCLES - INTERRUPT_LEAD;
+        let (mut cycle, mut line, mut interrupted) = (self.overrun, DISPLAY_START, false);
+        while cycle < FRAME_CYCLES {
+            self.board.line = cycle / LINE_CYCLES;
+            if !interrupted && cycle >= interrupt_at {
+                interrupted = true;
+                if self.board.display_on && self.cpu.interrupt() {
+                    cycle += 1;
+                    continue;
+                }
+            }
+            if line < DISPLAY_START + DISPLAY_LINES && cycle >= line * LINE_CYCLES {
+                if self.board.display_on {
+                    let bytes = &mut self.lines[(line - DISPLAY_START) as usize];
+                    for byte in bytes.iter_mut() {
+                        *byte = self.cpu.dma_out(&mut self.board);
+                    }
+                    cycle += 8;
+                } else {
+                    self.lines[(line - DISPLAY_START) as usize] = [0; 8];
+                }
+                line += 1;
+                continue;
+            }
+            cycle += self.cpu.step(&mut self.board);
+        }
+        self.overrun = cycle - FRAME_CYCLES;
+    }
+
+    /// The display as the core lays it out, one entry per pixel, from the
+    /// first of the four scanlines the interpreter shows each row on
+    pub fn gfx(&self) -> Vec<u8> {
+        let rows: Vec<u8> = self.lines.iter().step_by(4).flatten().copied().collect();
+        unpack_display(&rows)
+    }
+
+    /// Copies the interpreter's state into the core's registers, memory and
+    /// display, so frontends and debug views can show it
+    pub fn sync(&self, chip8: &mut Chip8) {
+        chip8.memory.copy_from_slice(&self.board.memory);
+        chip8.invalidate_decoded();
+        let registers = REGISTERS as usize;
+        chip8
+            .v
+            .copy_from_slice(&self.board.memory[registers..registers + 16]);
+        chip8.i = self.cpu.r[0xA] & 0xFFF;
+        chip8.pc = self.cpu.r[5] & 0xFFF;
+        chip8.delay_timer = (self.cpu.r[8] >> 8) as u8;
+        chip8.sound_timer = self.cpu.r[8] as u8;
+        chip8.key[..16].copy_from_slice(&self.board.keys);
+        let gfx = self.gfx();
+        if gfx[..] != chip8.gfx[..] {
+            chip8.gfx.copy_from_slice(&gfx);
+            chip8.should_draw = true;
+        }
+    }
+    /// Copies whatever was changed in the core since the last `sync`, e.g.
+    /// from the memory view, into the VIP's memory and the interpreter's
+    /// registers, so the next frame doesn't throw it away
+    pub fn write_back(&mut self, chip8: &Chip8) {
+        let registers = REGISTERS as usize;
+        // Vx and the memory holding it are separate edits, so check Vx first
+        let v: Vec<(usize, u8)> = (0..16)
+            .filter(|&x| chip8.v[x] != self.board.memory[registers + x])
+            .map(|x| (registers + x, chip8.v[x]))
+            .collect();
+        self.board.memory.copy_from_slice(&chip8.memory);
+        for (address, value) in v {
+            self.board.memory[address] = value;
+        }
+        if chip8.i != self.cpu.r[0xA] & 0xFFF {
+            self.cpu.r[0xA] = chip8.i;
+        }
+        if chip8.pc != self.cpu.r[5] & 0xFFF {
+            self.cpu.r[5] = chip8.pc;
+        }
+        self.cpu.r[8] = (chip8.delay_timer as u16) << 8 | chip8.sound_timer as u16;
+    }
+}
+
+/// The display as the VIP lays it out in memory, one bit per pixel
+pub fn pack_display(gfx: &[u8]) -> Vec<u8> {
+    gfx.chunks(8)
+        .map(|pixels| {
+            pixels
+                .iter()
+                .fold(0, |byte, &pixel| byte << 1 | (pixel != 0) as u8)
+        })
+        .collect()
+}
+
+/// One `gfx` entry per pixel from the VIP's packed layout
+pub fn unpack_display(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).map(move |bit| byte >> (7 - bit) & 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::quirks::MachineCode;
+
+    #[test]
+    fn test_machine_code_call() {
+        let mut chip8 = Chip8::initialize();
+        chip8.quirks.machine_code = MachineCode::Vip;
+        // 200: V3 = 0x20; 202: call machine code at 0x300; 204: V4 = 1
+        for (i, &byte) in [0x63, 0x20, 0x03, 0x00, 0x64, 0x01].iter().enumerate() {
+            chip8.write_memory(0x200 + i, byte);
+        }
+        // 300: LDN R6 (V3 through R6); SHL; STR R6; LDI 0xFF; PLO RB; STR RB (the
+        // last display byte); SEP R4
+        let routine = [0x06, 0xFE, 0x56, 0xF8, 0xFF, 0xAB, 0x5B, 0xD4];
+        for (i, &byte) in routine.iter().enumerate() {
+            chip8.write_memory(0x300 + i, byte);
+        }
+        chip8.emulate_cycle();
+        chip8.emulate_cycle();
+        assert_eq!(None, chip8.fault);
+        assert_eq!(
+            0x40, chip8.v[3],
+            "should double V3 in memory and copy it back"
+        );
+        assert_eq!(1, chip8.gfx[64 * 32 - 1]);
+        assert_eq!(0x204, chip8.pc);
+        chip8.emulate_cycle();
+        assert_eq!(1, chip8.v[4]);
+    }
+
+    #[test]
+    fn test_runaway_machine_code() {
+        let mut chip8 = Chip8::initialize();
+        chip8.quirks.machine_code = MachineCode::Vip;
+        chip8.write_memory(0x200, 0x03);
+        // 300: BR 300
+        chip8.write_memory(0x300, 0x30);
+        chip8.emulate_cycle();
+        assert_eq!(Some(Fault::MachineCode), chip8.fault);
+        assert_eq!(0x200, chip8.pc);
+    }
+
+    /// A stand-in for the interpreter: main code idles with P = 3 while the
+    /// interrupt routine shows each display row on four scanlines, as the VIP's does
+    fn interpreter() -> Vec<u8> {
+        let mut code = vec![
+            0xF8, 0x0E, 0xB2, 0xF8, 0xCF, 0xA2, // R2 = 0x0ECF
+            0xF8, 0x00, 0xB1, 0xF8, 0x42, 0xA1, // R1 = 0x0042
+            0xF8, 0x10, 0xA3, 0xD3, // R3 = 0x0010; SEP R3
+            0xE2, 0x69, 0x00, 0x30, 0x12, // SEX R2; INP 1; IDL; BR 12
+        ];
+        code.resize(0x40, 0);
+        code.extend_from_slice(&[
+            0x72, 0x70, // LDXA; RET
+            0x22, 0x78, 0x22, 0x52, 0xC4, 0xC4, 0xC4, // save T and D, then wait
+            0xF8, 0x0F, 0xB0, 0xF8, 0x00, 0xA0, // R0 = 0x0F00
+            0x80, 0xE2, 0xE2, 0x20, 0xA0, 0xE2, 0x20, 0xA0, 0xE2, 0x20, 0xA0, // a row
+            0x3C, 0x4F, 0x30, 0x40, // BN1 4F; BR 40
+        ]);
+        code
+    }
+
+    #[test]
+    fn test_vip_display() {
+        let mut memory = [0; 4096];
+        memory[0xF00] = 0x80;
+        memory[0xFFF] = 0x01;
+        let mut vip = Vip::new(&interpreter(), &memory);
+        vip.run_frame();
+        let gfx = vip.gfx();
+        assert_eq!(1, gfx[0]);
+        assert_eq!(0, gfx[1]);
+        assert_eq!(1, gfx[64 * 32 - 1], "should reach the last row");
+        assert!(
+            vip.lines
+                .chunks(4)
+                .all(|group| group.iter().all(|line| *line == group[0])),
+            "should repeat each row on four scanlines"
+        );
+        assert!(vip.cpu.ie, "should return from the interrupt");
+        assert_eq!(3, vip.cpu.p);
+
+        let mut chip8 = Chip8::initialize();
+        vip.board.memory[REGISTERS as usize + 2] = 0x42;
+        vip.cpu.r[5] = 0x234;
+        vip.sync(&mut chip8);
+        assert_eq!((0x42, 0x234), (chip8.v[2], chip8.pc));
+        assert!(chip8.should_draw);
+        assert_eq!(1, chip8.gfx[0]);
+
+        // an edit to V3's memory and one to V4 both survive the next sync
+        chip8.write_memory(REGISTERS as usize + 3, 0x33);
+        chip8.set_v(4, 0x44);
+        chip8.memory[0x300] = 0xAB;
+        chip8.pc = 0x250;
+        vip.write_back(&chip8);
+        vip.sync(&mut chip8);
+        assert_eq!((0x33, 0x44, 0x42), (chip8.v[3], chip8.v[4], chip8.v[2]));
+        assert_eq!((0xAB, 0x250), (chip8.memory[0x300], chip8.pc));
+    }
+
+    #[test]
+    fn test_vip_keypad() {
+        let mut vip = Vip::new(&interpreter(), &[0; 4096]);
+        vip.set_key(5, true);
+        vip.board.output(2, 0x05);
+        assert!(vip.board.flag(3));
+        vip.board.output(2, 0x06);
+        assert!(!vip.board.flag(3));
+    }
+
+    #[test]
+    fn test_display_layout() {
+        let mut gfx = vec![0; 64 * 32];
+        gfx[0] = 1;
+        gfx[9] = 1;
+        let bytes = pack_display(&gfx);
+        assert_eq!(256, bytes.len());
+        assert_eq!(&[0x80, 0x40], &bytes[..2]);
+        assert_eq!(gfx, unpack_display(&bytes));
+    }
+}